@@ -0,0 +1,182 @@
+use crate::{Result, TestDataFile};
+use mongodb::{
+    bson::{self, doc, Bson, Document},
+    Client,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// The ADF store name used when none is provided.
+pub(crate) const DEFAULT_STORE_NAME: &str = "localmongo";
+
+/// AdfDatabase is a database of an ADF storage configuration, in the format of the
+/// storage.databases list of an ADF tenant config.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AdfDatabase {
+    pub(crate) name: String,
+    pub(crate) collections: Vec<AdfCollection>,
+    /// The views of the database, or None if it has none.
+    pub(crate) views: Option<Vec<AdfView>>,
+}
+
+/// AdfCollection maps the collections matching name to the namespaces of its data sources. A name
+/// of "*" maps every collection to the collection with the same name in each data source.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AdfCollection {
+    pub(crate) name: String,
+    pub(crate) data_sources: Vec<AdfDataSource>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AdfDataSource {
+    pub(crate) store_name: String,
+    pub(crate) database: String,
+}
+
+/// AdfView is an ADF view: an aggregation pipeline, written as a JSON string, run on the source
+/// collection.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AdfView {
+    pub(crate) name: String,
+    pub(crate) source: String,
+    pub(crate) pipeline: String,
+}
+
+impl AdfView {
+    /// new returns the ADF view for a view defined on source by the pipeline. The pipeline is
+    /// written as relaxed Extended JSON.
+    pub(crate) fn new(name: &str, source: &str, pipeline: &[Document]) -> Self {
+        let pipeline = Bson::Array(pipeline.iter().cloned().map(Bson::Document).collect());
+        AdfView {
+            name: name.to_string(),
+            source: source.to_string(),
+            pipeline: pipeline.into_relaxed_extjson().to_string(),
+        }
+    }
+}
+
+/// adf_databases returns the ADF storage configuration for every database in the test data files,
+/// sorted by name. Each database maps every collection to the mongod database of the same name in
+/// the named store, and has the views defined in the data files.
+pub(crate) fn adf_databases(
+    test_data_files: &[TestDataFile],
+    store_name: &str,
+) -> Vec<AdfDatabase> {
+    let mut views: BTreeMap<&str, Vec<AdfView>> = BTreeMap::new();
+    for entry in test_data_files.iter().flat_map(|tdf| &tdf.dataset) {
+        let db_views = views.entry(entry.db.as_str()).or_default();
+        if let Some((v, d)) = entry
+            .view
+            .as_ref()
+            .and_then(|v| v.definition.as_ref().map(|d| (v, d)))
+        {
+            db_views.push(AdfView::new(&v.name, &d.view_on, &d.pipeline));
+        }
+    }
+
+    views
+        .into_iter()
+        .map(|(db, views)| AdfDatabase {
+            name: db.to_string(),
+            collections: vec![AdfCollection {
+                name: "*".to_string(),
+                data_sources: vec![AdfDataSource {
+                    store_name: store_name.to_string(),
+                    database: db.to_string(),
+                }],
+            }],
+            views: (!views.is_empty()).then_some(views),
+        })
+        .collect()
+}
+
+/// install_views adds the views in the test data files to ADF's storage configuration, via the
+/// storageGetConfig and storageSetConfig commands, so that ADF serves them without a separate
+/// config. Views with the same name replace those already configured, and databases that are not
+/// configured yet are added, backed by the named store. Like schemas, the views are not removed if
+/// the run fails.
+pub(crate) async fn install_views(
+    client: &Client,
+    test_data_files: &[TestDataFile],
+    store_name: &str,
+) -> Result<()> {
+    let databases = adf_databases(test_data_files, store_name);
+    if databases.iter().all(|db| db.views.is_none()) {
+        println!("\tNo views to install in ADF");
+        return Ok(());
+    }
+
+    let admin = client.database("admin");
+    let res = admin.run_command(doc! {"storageGetConfig": 1}).await?;
+    let mut storage = res.get_document("storage").cloned().unwrap_or_default();
+    let installed = add_views(&mut storage, &databases)?;
+    admin
+        .run_command(doc! {"storageSetConfig": storage})
+        .await?;
+    for view in installed {
+        println!("\tInstalled view {view} in ADF");
+    }
+    Ok(())
+}
+
+/// add_views adds the views of the databases to an ADF storage configuration, replacing views with
+/// the same name, and adding any database that is not configured. Every other part of the
+/// configuration is kept as is. Returns the namespaces of the views added.
+pub(crate) fn add_views(storage: &mut Document, databases: &[AdfDatabase]) -> Result<Vec<String>> {
+    if !matches!(storage.get("databases"), Some(Bson::Array(_))) {
+        storage.insert("databases", Bson::Array(vec![]));
+    }
+    let Some(Bson::Array(configured)) = storage.get_mut("databases") else {
+        unreachable!("databases was just set to an array")
+    };
+
+    let mut installed = vec![];
+    for database in databases {
+        let Some(views) = &database.views else {
+            continue;
+        };
+        installed.extend(
+            views
+                .iter()
+                .map(|v| format!("{}.{}", database.name, v.name)),
+        );
+
+        let existing = configured.iter_mut().find_map(|db| match db {
+            Bson::Document(db) if db.get_str("name") == Ok(database.name.as_str()) => Some(db),
+            _ => None,
+        });
+        let Some(existing) = existing else {
+            configured.push(Bson::Document(bson::to_document(database)?));
+            continue;
+        };
+        let mut configured_views = match existing.remove("views") {
+            Some(Bson::Array(views)) => views,
+            _ => vec![],
+        };
+        for view in views {
+            configured_views.retain(|v| {
+                v.as_document()
+                    .and_then(|v| v.get_str("name").ok())
+                    .is_none_or(|name| name != view.name)
+            });
+            configured_views.push(bson::to_bson(view)?);
+        }
+        existing.insert("views", configured_views);
+    }
+    Ok(installed)
+}
+
+/// write_adf_config writes the databases as a JSON list to the provided path, or to stdout if no
+/// path is provided.
+pub(crate) fn write_adf_config(databases: &[AdfDatabase], path: Option<&Path>) -> Result<()> {
+    let json = serde_json::to_string_pretty(databases)?;
+    match path {
+        Some(path) => fs::write(path, json + "\n")?,
+        None => println!("{json}"),
+    }
+    Ok(())
+}
@@ -0,0 +1,416 @@
+use crate::{
+    adf_config, dump,
+    loader::{DataLoader, Event, Mode, Step},
+    profile::{self, Connection},
+    read_and_check, tpch, validate,
+    validator::{self, parse_validation_action, parse_validation_level},
+    verify, DataLoaderError, FileFilter, Result, SchemaValidator, TestDataFile,
+};
+use clap::{Parser, Subcommand};
+use glob::Pattern;
+use mongodb::{
+    options::{ValidationAction, ValidationLevel},
+    Client,
+};
+use std::{collections::BTreeMap, num::NonZeroUsize, path::PathBuf};
+
+/// This is a standalone executable that loads test data for SQL Engines integration tests. This
+/// tool must connect to a mongod to write data, and may connect to an ADF to write schema. Test
+/// data must be specified in YAML or JSON files (using the .y[a]ml or .json extensions), and they
+/// must follow the format described by the TestDataFile and TestDataEntry types. See those types
+/// for more details.
+///
+/// When run with the adf flag enabled, with an adf_uri provided, or with a profile that uses ADF,
+/// this tool connects to an ADF instance in addition to a mongod. In this mode, data and indexes
+/// are written to the mongod, and schemas are written to ADF (via sqlSetSchema or
+/// sqlGenerateSchema, depending on the presence of schema info in the data files). In this mode,
+/// views are not written to mongod, as they are assumed to be ADF views which are specified
+/// separately, in the ADF config. With the adf_views flag enabled, the views are instead installed
+/// in ADF's storage configuration before schemas are written, so the data files fully define them.
+///
+/// When run without the adf flag enabled, and without an adf_uri provided, this tool only connects
+/// to a mongod. In this mode, documents, indexes, views, and schema are written directly to the
+/// mongod.
+///
+/// When run with the validate subcommand, this tool does not connect to anything. It checks every
+/// data file and prints the plan of what a load would do.
+///
+/// Connection settings can be kept in named profiles in a data-loader.toml config file, and
+/// selected with the profile option. See profile::ConfigFile for more details.
+///
+/// When run with the dump subcommand, this tool reads namespaces from a mongod and writes them out
+/// in the test data file format.
+///
+/// When run with the verify subcommand, this tool compares the namespaces on a mongod (and the
+/// schemas in ADF, in ADF mode) with the data files, and reports any differences.
+///
+/// When run with the gen-adf-config subcommand, this tool does not connect to anything. It writes
+/// the ADF storage configuration for the databases and views in the data files.
+#[derive(Parser, Debug)]
+#[command(version)]
+pub(crate) struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// mongod URI. Optional.
+    /// Defaults to the profile's mongod URI, if a profile is used, and otherwise to
+    /// "mongodb://$MDB_TEST_LOCAL_HOST:$MDB_TEST_LOCAL_PORT".
+    #[arg(long, global = true)]
+    mongod_uri: Option<String>,
+
+    /// ADF URI. Optional.
+    /// Defaults to the profile's ADF URI, if a profile is used, and otherwise to
+    /// "mongodb://$ADF_TEST_LOCAL_USER:$ADF_TEST_LOCAL_PASSWORD@$ADF_TEST_LOCAL_HOST:$ADF_TEST_LOCAL_PORT".
+    /// If an adf_uri is provided, the adf flag is assumed to be true. A user can choose to omit the
+    /// adf_uri option and still connect to ADF by providing the adf flag; in this case, the ADF URI
+    /// will use the default value described previously.
+    #[arg(long, global = true)]
+    adf_uri: Option<String>,
+
+    /// Path to a directory containing test data files, or to an individual test data file.
+    /// Required, unless the profile provides test_data_directories. May be specified multiple
+    /// times. Directories are searched recursively.
+    #[arg(short = 'd', long = "testDataDirectory", global = true)]
+    test_data_directory: Vec<String>,
+
+    /// Glob pattern for files to read from test data directories. Optional. May be specified
+    /// multiple times. Patterns are matched against paths relative to the test data directory
+    /// being searched, e.g. "tdvt/**" or "*.json". If no include patterns are provided, every file
+    /// is included. Files passed directly via -d are always read.
+    #[arg(long, global = true)]
+    include: Vec<Pattern>,
+
+    /// Glob pattern for files to skip when searching test data directories. Optional. May be
+    /// specified multiple times. Exclude patterns take precedence over include patterns.
+    #[arg(long, global = true)]
+    exclude: Vec<Pattern>,
+
+    /// Skip checking that documents conform to their entry's schema before loading them. Use
+    /// this for fixtures that intentionally contain documents which contradict their schema.
+    #[arg(long)]
+    skip_document_validation: bool,
+
+    /// Reload every namespace, even those whose fingerprint shows they are unchanged since the
+    /// last load, and replace users and roles the data loader did not create.
+    #[arg(long)]
+    force: bool,
+
+    /// The maximum number of write operations (document batches, index builds, and view
+    /// creations) sent to mongod at the same time. Namespaces are loaded in parallel.
+    #[arg(long, default_value = "8")]
+    concurrency: NonZeroUsize,
+
+    /// The maximum number of documents sent to mongod in a single insert. Collections with more
+    /// documents are inserted in multiple batches, which may be sent concurrently.
+    #[arg(long, default_value = "10000")]
+    batch_size: NonZeroUsize,
+
+    /// Scale factor of a built-in TPC-H-like dataset to load along with the data files, e.g. 0.1.
+    /// Optional. When provided, test data directories may be omitted.
+    #[arg(long, global = true)]
+    tpch_scale_factor: Option<f64>,
+
+    /// Reject documents with malformed Extended JSON type wrappers, like {"$dat": ...} or
+    /// {"$numberLong": 1}, instead of loading them as plain subdocuments. Applies to the docs in
+    /// data files and to JSON docs files, and reports the file, line, and column of the error. Only
+    /// canonical and relaxed Extended JSON v2 are accepted.
+    #[arg(long, global = true)]
+    strict_extended_json: bool,
+
+    /// Keep reading and validating after the first error, and report every error in every data
+    /// file together before failing. Without this flag, the first error stops the run.
+    #[arg(long, global = true)]
+    all_errors: bool,
+
+    /// Also install the schema of every collection entry as the collection's $jsonSchema validator,
+    /// so the mongod rejects documents that contradict it. Entries that specify their own
+    /// schema_validator, or a validator, validationLevel, or validationAction in their options,
+    /// keep theirs.
+    #[arg(long, global = true)]
+    schema_validator: bool,
+
+    /// The validationLevel of the validators installed by schema_validator: "off", "strict", or
+    /// "moderate". Optional. Defaults to the server's default, "strict".
+    #[arg(
+        long,
+        global = true,
+        requires = "schema_validator",
+        value_parser = parse_validation_level
+    )]
+    validation_level: Option<ValidationLevel>,
+
+    /// The validationAction of the validators installed by schema_validator: "error" or "warn".
+    /// Optional. Defaults to the server's default, "error".
+    #[arg(
+        long,
+        global = true,
+        requires = "schema_validator",
+        value_parser = parse_validation_action
+    )]
+    validation_action: Option<ValidationAction>,
+
+    /// Install the views in the data files in ADF, via storageSetConfig, before writing schemas to
+    /// ADF. Databases ADF does not have yet are added, backed by the store named by store_name.
+    /// Only applies in ADF mode.
+    #[arg(long, global = true)]
+    adf_views: bool,
+
+    /// The name of the ADF store the mongod is configured as, used by the adf_views flag and the
+    /// gen-adf-config subcommand.
+    #[arg(long, global = true, default_value = adf_config::DEFAULT_STORE_NAME)]
+    store_name: String,
+
+    /// The database the TPC-H-like dataset is loaded into.
+    #[arg(long, global = true, default_value = "tpch")]
+    tpch_db: String,
+
+    /// Indicates whether the data loader needs to connect to ADF
+    #[arg(long, global = true)]
+    adf: bool,
+
+    /// The name of the connection profile to use, from the config file. Optional. Defaults to the
+    /// config file's default_profile, if it has one. Settings provided on the command line take
+    /// precedence over the profile's. See profile::ConfigFile for the format of the config file.
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// Path to the config file holding connection profiles. Optional. Defaults to
+    /// "data-loader.toml" in the working directory, which is only read if it exists.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+}
+
+/// The modes this tool can run in. When no subcommand is provided, the data files are loaded.
+#[derive(Subcommand, Debug)]
+pub(crate) enum Command {
+    /// Check every data file without connecting to mongod or ADF, and print the load plan.
+    ///
+    /// This checks namespaces, view targets, index models, and schemas. If the adf flag is
+    /// enabled, the printed plan describes an ADF-mode load.
+    Validate,
+
+    /// Export namespaces from a mongod in the test data file format.
+    ///
+    /// Documents, indexes, and view definitions are read from the mongod. Schemas are read from
+    /// ADF via sqlGetSchema if the adf flag is enabled, and from __sql_schemas otherwise.
+    Dump {
+        /// A namespace to export, either "<db>.<name>" for a single collection or view, or
+        /// "<db>" for every collection and view in a database. Required. May be specified
+        /// multiple times.
+        #[arg(short = 'n', long = "namespace", required = true)]
+        namespaces: Vec<String>,
+
+        /// The file to write. Written as JSON if the path has a '.json' extension and as YAML
+        /// otherwise. Optional. Defaults to writing YAML to stdout.
+        #[arg(short = 'o', long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Compare the mongod with the data files, and exit with an error if they differ.
+    ///
+    /// Documents, indexes, view definitions, and schemas are compared for every namespace in the
+    /// data files. Schemas are compared with ADF if the adf flag is enabled, and with
+    /// __sql_schemas otherwise.
+    #[command(alias = "diff")]
+    Verify,
+
+    /// Generate the ADF storage configuration for the databases in the data files.
+    ///
+    /// The configuration is a JSON list in the format of the storage.databases list of an ADF
+    /// tenant config. Every collection of each database is mapped to the mongod database of the
+    /// same name in the store, and the views in the data files are included as ADF views.
+    GenAdfConfig {
+        /// The file to write. Optional. Defaults to writing to stdout.
+        #[arg(short = 'o', long)]
+        output: Option<PathBuf>,
+    },
+}
+
+/// run parses the command-line arguments and runs the data loader in the mode they select.
+pub async fn run() -> Result<()> {
+    let mut args = Args::parse();
+
+    let profile =
+        profile::load(args.config.as_deref(), args.profile.as_deref())?.unwrap_or_default();
+    let mongod = Connection::resolve(
+        args.mongod_uri.clone(),
+        &profile.mongod,
+        profile::default_mongod_uri,
+    );
+    let adf = (args.adf || args.adf_uri.is_some() || profile.use_adf)
+        .then(|| Connection::resolve(args.adf_uri.clone(), &profile.adf, profile::default_adf_uri));
+    if args.test_data_directory.is_empty() {
+        args.test_data_directory = profile
+            .test_data_directories
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+    }
+
+    if let Some(Command::Dump { namespaces, output }) = args.command {
+        println!("Step 1: Connecting to mongod.");
+        let mdb_client = mongod.connect().await?;
+        let adf_client = connect_to_adf(adf.as_ref()).await?;
+
+        println!("Step 2: Reading namespaces.");
+        let tdf = dump::dump(&mdb_client, adf_client.as_ref(), &namespaces).await?;
+
+        println!("Step 3: Writing test data file.");
+        return dump::write_test_data_file(&tdf, output.as_deref());
+    }
+
+    if args.test_data_directory.is_empty() && args.tpch_scale_factor.is_none() {
+        return Err(DataLoaderError::MissingTestDataDirectory);
+    }
+    let file_filter = FileFilter {
+        include: args.include.clone(),
+        exclude: args.exclude.clone(),
+    };
+    let schema_validator = args.schema_validator.then(|| SchemaValidator {
+        validation_level: args.validation_level.clone(),
+        validation_action: args.validation_action.clone(),
+    });
+    let tpch = args.tpch_scale_factor.map(|scale_factor| {
+        tpch_data_file(
+            &args.tpch_db,
+            tpch::TpchOptions {
+                scale_factor,
+                seed: 0,
+                tables: vec![],
+            },
+        )
+    });
+
+    if let Some(Command::Validate) = args.command {
+        println!("Step 1: Reading data files.");
+        let mut test_data_files = read_and_check(
+            &args.test_data_directory,
+            &file_filter,
+            args.strict_extended_json,
+            tpch,
+            args.all_errors,
+            |test_data_files| {
+                println!("Step 2: Validating data files.");
+                validate::validate(test_data_files)
+            },
+        )?;
+        if let Some(schema_validator) = &schema_validator {
+            validator::apply_default(&mut test_data_files, schema_validator);
+        }
+
+        println!("Step 3: Printing load plan.");
+        validate::print_load_plan(&test_data_files, adf.is_some());
+        return Ok(());
+    }
+
+    if let Some(Command::GenAdfConfig { output }) = &args.command {
+        println!("Step 1: Reading data files.");
+        let test_data_files = read_and_check(
+            &args.test_data_directory,
+            &file_filter,
+            args.strict_extended_json,
+            tpch,
+            args.all_errors,
+            |_| Ok(()),
+        )?;
+
+        println!("Step 2: Writing ADF storage configuration.");
+        let databases = adf_config::adf_databases(&test_data_files, &args.store_name);
+        return adf_config::write_adf_config(&databases, output.as_deref());
+    }
+
+    if let Some(Command::Verify) = args.command {
+        println!("Step 1: Reading data files.");
+        let mut test_data_files = read_and_check(
+            &args.test_data_directory,
+            &file_filter,
+            args.strict_extended_json,
+            tpch,
+            args.all_errors,
+            |_| Ok(()),
+        )?;
+        if let Some(schema_validator) = &schema_validator {
+            validator::apply_default(&mut test_data_files, schema_validator);
+        }
+
+        println!("Step 2: Connecting to mongod.");
+        let mdb_client = mongod.connect().await?;
+        let adf_client = connect_to_adf(adf.as_ref()).await?;
+
+        println!("Step 3: Comparing namespaces with data files.");
+        return verify::verify(&mdb_client, adf_client.as_ref(), &test_data_files).await;
+    }
+
+    let adf_mode = adf.is_some();
+    let mut builder = DataLoader::builder()
+        .targets(args.test_data_directory)
+        .data_files(tpch)
+        .strict_extended_json(args.strict_extended_json)
+        .all_errors(args.all_errors)
+        .skip_document_validation(args.skip_document_validation)
+        .force(args.force)
+        .schema_validator(schema_validator)
+        .adf_views(args.adf_views.then_some(args.store_name))
+        .concurrency(args.concurrency)
+        .batch_size(args.batch_size)
+        .hook(move |event| {
+            if let Event::Step(step) = event {
+                println!("{}", step_message(step, adf_mode));
+            }
+        });
+    for pattern in args.include {
+        builder = builder.include(pattern);
+    }
+    for pattern in args.exclude {
+        builder = builder.exclude(pattern);
+    }
+
+    println!("Step 1: Reading and validating data files.");
+    let loader = builder.build()?;
+
+    println!("Step 2: Connecting to mongod.");
+    println!("\tUsing mongod URI: {}", mongod.redacted_uri()?);
+    let mdb_client = mongod.connect().await?;
+    let mode = match connect_to_adf(adf.as_ref()).await? {
+        Some(adf_client) => Mode::Adf(adf_client),
+        None => Mode::Mongod,
+    };
+    loader.load(&mdb_client, &mode).await
+}
+
+/// step_message returns the message printed when the loader begins a step of a load, numbered
+/// after the steps run prints before loading.
+fn step_message(step: Step, adf_mode: bool) -> &'static str {
+    match step {
+        Step::Comparing => "Step 3: Comparing data files with previously loaded namespaces.",
+        Step::Dropping => "Step 4: Dropping existing data based on namespaces in data files.",
+        Step::Loading => "Step 5: Loading data into mongod.",
+        Step::CreatingUsers => "Step 6: Creating users and roles.",
+        Step::SettingSchemas if adf_mode => "Step 7: Writing schema to ADF.",
+        Step::SettingSchemas => "Step 7: Writing schema directly to mongod.",
+        Step::RecordingFingerprints => "Recording fingerprints of loaded namespaces.",
+    }
+}
+
+/// connect_to_adf connects to ADF if running in ADF mode, and returns None otherwise.
+async fn connect_to_adf(adf: Option<&Connection>) -> Result<Option<Client>> {
+    let Some(adf) = adf else {
+        return Ok(None);
+    };
+    println!("\tADF mode detected. Connecting to ADF.");
+    Ok(Some(adf.connect().await?))
+}
+
+/// tpch_data_file returns a test data file holding the TPC-H-like dataset, for loading the dataset
+/// without a data file. Its path names the command-line flag the dataset came from.
+fn tpch_data_file(db: &str, options: tpch::TpchOptions) -> TestDataFile {
+    TestDataFile {
+        path: PathBuf::from("--tpch-scale-factor"),
+        include: vec![],
+        schemas: BTreeMap::new(),
+        schema_file: None,
+        dataset: tpch::entries(db, &options),
+        users: vec![],
+        roles: vec![],
+    }
+}
@@ -0,0 +1,479 @@
+use crate::{DataLoaderError, Result};
+use mongodb::bson::{
+    self, oid::ObjectId, spec::BinarySubtype, Binary, Bson, Decimal128, Document, Regex, Timestamp,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+/// SchemaGenerateOptions describes documents generated from an entry's schema.
+///
+/// Example:
+///   generate_from_schema: { count: 100, seed: 1, coverage: all_branches }
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SchemaGenerateOptions {
+    /// count is the minimum number of documents to generate. Required.
+    pub count: usize,
+
+    /// seed seeds the random number generator. Defaults to 0.
+    #[serde(default)]
+    pub seed: u64,
+
+    /// coverage controls which branches of the schema the documents exercise. Defaults to
+    /// all_branches.
+    #[serde(default)]
+    pub coverage: Coverage,
+}
+
+/// Coverage decides which branches of the schema generated documents exercise.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Coverage {
+    /// The first documents exercise every branch of the schema: every anyOf alternative and
+    /// bsonType, every optional property both present and missing, every array both empty and
+    /// with multiple elements, and the boundary values of every numeric type. If that takes more
+    /// than count documents, more are generated. The remaining documents choose branches at
+    /// random. Under either coverage, a top-level _id is not a branch: see Node::Id.
+    #[default]
+    AllBranches,
+    /// Every document chooses branches at random.
+    Random,
+}
+
+/// Node is a schema compiled into the choices a generated document makes.
+enum Node {
+    /// Exactly one of the options is generated. Every choice has a unique id, which indexes the
+    /// coverage of its options.
+    Choice {
+        id: usize,
+        options: Vec<Node>,
+    },
+    Value(Bson),
+    /// A top-level _id, which must be unique across documents. It is the document's index,
+    /// converted to the bsonType.
+    Id(&'static str),
+    /// The field is omitted from its document.
+    Missing,
+    Object(Vec<(String, Node)>),
+    Array {
+        items: Box<Node>,
+        length: usize,
+    },
+    Tuple(Vec<Node>),
+}
+
+/// The number of elements in the non-empty arrays generated for an items schema.
+const ARRAY_LENGTH: usize = 2;
+
+/// The bsonTypes a top-level _id can be generated for, in order of preference.
+const ID_TYPES: &[&str] = &["int", "long", "double", "decimal", "string", "objectId"];
+
+/// validate_schema_generate_options returns a message for every part of the schema that documents
+/// cannot be generated for. The root schema must describe documents: it must have bsonType
+/// "object", or properties that imply it, or be an anyOf of such schemas.
+pub(crate) fn validate_schema_generate_options(schema: Option<&Bson>) -> Vec<String> {
+    match schema {
+        Some(schema) => compile_schema(schema).err().into_iter().flatten().collect(),
+        None => vec!["generate_from_schema requires the entry to have a schema".to_string()],
+    }
+}
+
+fn compile_schema(schema: &Bson) -> std::result::Result<(Node, usize), Vec<String>> {
+    let mut compiler = Compiler::default();
+    let node = compiler.compile(schema, "");
+    if !generates_documents(&node) {
+        compiler.errors.push(
+            "/: documents can only be generated for a schema with bsonType \"object\", or an anyOf of such schemas"
+                .to_string(),
+        );
+    }
+    if compiler.errors.is_empty() {
+        Ok((node, compiler.choices))
+    } else {
+        Err(compiler.errors)
+    }
+}
+
+/// generates_documents returns whether every value the node generates is a document.
+fn generates_documents(node: &Node) -> bool {
+    match node {
+        Node::Object(_) => true,
+        Node::Choice { options, .. } => options.iter().all(generates_documents),
+        _ => false,
+    }
+}
+
+impl SchemaGenerateOptions {
+    /// documents returns the documents generated from the schema, one at a time. Returns an
+    /// InvalidGenerator error if the schema is missing or documents cannot be generated for it.
+    pub(crate) fn documents(
+        &self,
+        schema: Option<&Bson>,
+    ) -> Result<impl Iterator<Item = Document> + Send + 'static> {
+        let (root, choices) = match schema {
+            Some(schema) => compile_schema(schema).map_err(DataLoaderError::InvalidGenerator)?,
+            None => {
+                return Err(DataLoaderError::InvalidGenerator(
+                    validate_schema_generate_options(None),
+                ))
+            }
+        };
+
+        let mut generator = Generator {
+            rng: ChaCha8Rng::seed_from_u64(self.seed),
+            covered: vec![vec![]; choices],
+            cover: self.coverage == Coverage::AllBranches,
+            index: 0,
+        };
+        init_coverage(&root, &mut generator.covered);
+
+        let count = self.count;
+        let mut generated = 0;
+        Ok(std::iter::from_fn(move || {
+            if generator.cover && !generator.uncovered(&root) {
+                generator.cover = false;
+            }
+            if generated >= count && !generator.cover {
+                return None;
+            }
+            generator.index = generated as u64;
+            generated += 1;
+            match generator.generate(&root) {
+                Some(Bson::Document(doc)) => Some(doc),
+                _ => unreachable!("Invariant failed: the root schema only generates documents."),
+            }
+        }))
+    }
+}
+
+#[derive(Default)]
+struct Compiler {
+    choices: usize,
+    errors: Vec<String>,
+}
+
+impl Compiler {
+    fn choice(&mut self, options: Vec<Node>) -> Node {
+        if options.len() == 1 {
+            return options.into_iter().next().unwrap();
+        }
+        self.choices += 1;
+        Node::Choice {
+            id: self.choices - 1,
+            options,
+        }
+    }
+
+    /// compile converts a schema to a Node. The path is the JSON pointer of the schema, used for
+    /// error messages. A schema with an anyOf is compiled to a choice among its alternatives, and
+    /// its other keywords are ignored.
+    fn compile(&mut self, schema: &Bson, path: &str) -> Node {
+        let location = if path.is_empty() { "/" } else { path };
+        let Bson::Document(schema) = schema else {
+            self.errors
+                .push(format!("{location}: schema must be a document"));
+            return Node::Value(Bson::Null);
+        };
+
+        if let Ok(alternatives) = schema.get_array("anyOf") {
+            let options = alternatives
+                .iter()
+                .enumerate()
+                .map(|(i, alternative)| self.compile(alternative, &format!("{path}/anyOf/{i}")))
+                .collect::<Vec<_>>();
+            if options.is_empty() {
+                self.errors
+                    .push(format!("{location}: anyOf must not be empty"));
+                return Node::Value(Bson::Null);
+            }
+            return self.choice(options);
+        }
+
+        let types = match schema.get("bsonType") {
+            Some(Bson::String(t)) => vec![t.as_str()],
+            Some(Bson::Array(types)) => types.iter().filter_map(Bson::as_str).collect(),
+            // Without a bsonType, properties or items imply the type.
+            _ if schema.contains_key("properties") => vec!["object"],
+            _ if schema.contains_key("items") => vec!["array"],
+            // An empty schema matches any value.
+            _ => vec!["null"],
+        };
+
+        if types.is_empty() {
+            self.errors
+                .push(format!("{location}: bsonType must not be empty"));
+            return Node::Value(Bson::Null);
+        }
+
+        let mut options = vec![];
+        for t in types {
+            match t {
+                "object" => options.push(self.compile_object(schema, path)),
+                "array" => options.extend(self.compile_array(schema, path)),
+                "number" => {
+                    for t in ["int", "long", "double", "decimal"] {
+                        options.extend(scalar_values(t).into_iter().map(Node::Value));
+                    }
+                }
+                t => match scalar_values(t) {
+                    values if values.is_empty() => self.errors.push(format!(
+                        "{location}: cannot generate values of bsonType {t:?}"
+                    )),
+                    values => options.extend(values.into_iter().map(Node::Value)),
+                },
+            }
+        }
+        if options.is_empty() {
+            return Node::Value(Bson::Null);
+        }
+        self.choice(options)
+    }
+
+    /// compile_object generates every property of a schema. Required properties are always
+    /// present, and other properties are a choice between present and missing. The _id of a
+    /// top-level document is compiled with compile_id.
+    fn compile_object(&mut self, schema: &Document, path: &str) -> Node {
+        let required = schema
+            .get_array("required")
+            .map(|r| r.iter().filter_map(Bson::as_str).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let properties = schema
+            .get_document("properties")
+            .map(|properties| {
+                properties
+                    .iter()
+                    .map(|(name, property)| {
+                        let property_path = format!("{path}/properties/{name}");
+                        let node = if name == "_id" && is_top_level(path) {
+                            self.compile_id(property, &property_path)
+                        } else {
+                            self.compile(property, &property_path)
+                        };
+                        let node = if required.contains(&name.as_str()) {
+                            node
+                        } else {
+                            self.choice(vec![node, Node::Missing])
+                        };
+                        (name.clone(), node)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Node::Object(properties)
+    }
+
+    /// compile_id compiles the schema of a top-level _id to a Node::Id of the first of its
+    /// bsonTypes that unique values can be generated for. An empty schema generates ints.
+    fn compile_id(&mut self, schema: &Bson, path: &str) -> Node {
+        let types = match schema.as_document().and_then(|s| s.get("bsonType")) {
+            Some(Bson::String(t)) => vec![t.as_str()],
+            Some(Bson::Array(types)) => types.iter().filter_map(Bson::as_str).collect(),
+            _ if schema.as_document().is_some_and(Document::is_empty) => vec!["int"],
+            _ => vec![],
+        };
+        let types = types
+            .into_iter()
+            .map(|t| if t == "number" { "int" } else { t })
+            .collect::<Vec<_>>();
+        match ID_TYPES.iter().find(|t| types.contains(t)) {
+            Some(t) => Node::Id(t),
+            None => {
+                self.errors.push(format!(
+                    "{path}: cannot generate unique _id values, \
+                     _id must have a bsonType in {ID_TYPES:?}"
+                ));
+                Node::Value(Bson::Null)
+            }
+        }
+    }
+
+    /// compile_array returns the options for an array schema: an empty array and an array of
+    /// several elements for a single items schema, or one element per schema for positional items.
+    fn compile_array(&mut self, schema: &Document, path: &str) -> Vec<Node> {
+        match schema.get("items") {
+            Some(Bson::Array(items)) => vec![Node::Tuple(
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| self.compile(item, &format!("{path}/items/{i}")))
+                    .collect(),
+            )],
+            Some(items) => vec![
+                Node::Array {
+                    items: Box::new(Node::Missing),
+                    length: 0,
+                },
+                Node::Array {
+                    items: Box::new(self.compile(items, &format!("{path}/items"))),
+                    length: ARRAY_LENGTH,
+                },
+            ],
+            None => vec![Node::Array {
+                items: Box::new(Node::Missing),
+                length: 0,
+            }],
+        }
+    }
+}
+
+/// scalar_values returns the values generated for a non-object, non-array bsonType, including the
+/// boundary values of numeric types. Returns no values for types that cannot be generated.
+fn scalar_values(bson_type: &str) -> Vec<Bson> {
+    match bson_type {
+        "double" => vec![
+            Bson::Double(0.0),
+            Bson::Double(-1.5),
+            Bson::Double(f64::MIN),
+            Bson::Double(f64::MAX),
+            Bson::Double(f64::MIN_POSITIVE),
+        ],
+        "int" => vec![Bson::Int32(0), Bson::Int32(i32::MIN), Bson::Int32(i32::MAX)],
+        "long" => vec![Bson::Int64(0), Bson::Int64(i64::MIN), Bson::Int64(i64::MAX)],
+        "decimal" => [
+            "0",
+            "-9.999999999999999999999999999999999E+6144",
+            "9.999999999999999999999999999999999E+6144",
+        ]
+        .iter()
+        .filter_map(|d| d.parse::<Decimal128>().ok())
+        .map(Bson::Decimal128)
+        .collect(),
+        "string" => vec![Bson::String(String::new()), Bson::String("abc".to_string())],
+        "bool" => vec![Bson::Boolean(false), Bson::Boolean(true)],
+        "null" => vec![Bson::Null],
+        "date" => vec![
+            Bson::DateTime(bson::DateTime::from_millis(0)),
+            Bson::DateTime(bson::DateTime::MIN),
+            Bson::DateTime(bson::DateTime::MAX),
+        ],
+        "objectId" => vec![
+            Bson::ObjectId(ObjectId::from_bytes([0; 12])),
+            Bson::ObjectId(ObjectId::from_bytes([0xff; 12])),
+        ],
+        "binData" => vec![Bson::Binary(Binary {
+            subtype: BinarySubtype::Generic,
+            bytes: vec![],
+        })],
+        "timestamp" => vec![Bson::Timestamp(Timestamp {
+            time: 0,
+            increment: 0,
+        })],
+        "regex" => vec![Bson::RegularExpression(Regex {
+            pattern: "^a".to_string(),
+            options: String::new(),
+        })],
+        "javascript" => vec![Bson::JavaScriptCode("function() {}".to_string())],
+        "minKey" => vec![Bson::MinKey],
+        "maxKey" => vec![Bson::MaxKey],
+        _ => vec![],
+    }
+}
+
+/// is_top_level returns true if the schema at the path describes whole documents, that is, if it
+/// is the root schema or an anyOf alternative of one.
+fn is_top_level(path: &str) -> bool {
+    let mut segments = path.split('/').skip(1);
+    while let Some(segment) = segments.next() {
+        if segment != "anyOf" || segments.next().is_none() {
+            return false;
+        }
+    }
+    true
+}
+
+/// id_value returns the _id of the document with the given index, as a value of the bsonType.
+fn id_value(bson_type: &str, index: u64) -> Bson {
+    match bson_type {
+        "int" => Bson::Int32(index as i32),
+        "long" => Bson::Int64(index as i64),
+        "double" => Bson::Double(index as f64),
+        "decimal" => Bson::Decimal128(index.to_string().parse().unwrap()),
+        "objectId" => {
+            let mut bytes = [0; 12];
+            bytes[4..].copy_from_slice(&index.to_be_bytes());
+            Bson::ObjectId(ObjectId::from_bytes(bytes))
+        }
+        _ => Bson::String(index.to_string()),
+    }
+}
+
+fn init_coverage(node: &Node, covered: &mut [Vec<bool>]) {
+    match node {
+        Node::Choice { id, options } => {
+            covered[*id] = vec![false; options.len()];
+            options.iter().for_each(|o| init_coverage(o, covered));
+        }
+        Node::Object(properties) => properties
+            .iter()
+            .for_each(|(_, p)| init_coverage(p, covered)),
+        Node::Array { items, .. } => init_coverage(items, covered),
+        Node::Tuple(items) => items.iter().for_each(|i| init_coverage(i, covered)),
+        Node::Value(_) | Node::Id(_) | Node::Missing => {}
+    }
+}
+
+struct Generator {
+    rng: ChaCha8Rng,
+    /// covered records, for every choice, which of its options have been generated.
+    covered: Vec<Vec<bool>>,
+    /// cover is true while there are uncovered options left to generate.
+    cover: bool,
+    /// index is the index of the document being generated.
+    index: u64,
+}
+
+impl Generator {
+    /// uncovered returns true if any option of any choice reachable from the node has not been
+    /// generated yet.
+    fn uncovered(&self, node: &Node) -> bool {
+        match node {
+            Node::Choice { id, options } => {
+                self.covered[*id].contains(&false) || options.iter().any(|o| self.uncovered(o))
+            }
+            Node::Object(properties) => properties.iter().any(|(_, p)| self.uncovered(p)),
+            Node::Array { items, .. } => self.uncovered(items),
+            Node::Tuple(items) => items.iter().any(|i| self.uncovered(i)),
+            Node::Value(_) | Node::Id(_) | Node::Missing => false,
+        }
+    }
+
+    /// generate returns a value for the node, or None if the node is a missing field. While
+    /// covering, every choice prefers an option it has not generated yet, and then an option
+    /// that leads to a choice with options not generated yet, so every document covers at least
+    /// one new option.
+    fn generate(&mut self, node: &Node) -> Option<Bson> {
+        match node {
+            Node::Choice { id, options } => {
+                let chosen = if self.cover {
+                    self.covered[*id]
+                        .iter()
+                        .position(|covered| !covered)
+                        .or_else(|| options.iter().position(|o| self.uncovered(o)))
+                } else {
+                    None
+                };
+                let chosen = chosen.unwrap_or_else(|| self.rng.random_range(0..options.len()));
+                self.covered[*id][chosen] = true;
+                self.generate(&options[chosen])
+            }
+            Node::Value(value) => Some(value.clone()),
+            Node::Id(bson_type) => Some(id_value(bson_type, self.index)),
+            Node::Missing => None,
+            Node::Object(properties) => {
+                let mut doc = Document::new();
+                for (name, property) in properties {
+                    if let Some(value) = self.generate(property) {
+                        doc.insert(name, value);
+                    }
+                }
+                Some(Bson::Document(doc))
+            }
+            Node::Array { items, length } => Some(Bson::Array(
+                (0..*length).filter_map(|_| self.generate(items)).collect(),
+            )),
+            Node::Tuple(items) => Some(Bson::Array(
+                items.iter().filter_map(|i| self.generate(i)).collect(),
+            )),
+        }
+    }
+}
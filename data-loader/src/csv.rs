@@ -0,0 +1,282 @@
+use crate::{DataLoaderError, Result};
+use mongodb::bson::{self, Bson, Decimal128, Document};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, io::BufRead};
+use time::{
+    format_description::{self, OwnedFormatItem},
+    Date, OffsetDateTime, PrimitiveDateTime,
+};
+
+/// The bsonTypes CSV values can be converted to.
+pub(crate) const CSV_COLUMN_TYPES: &[&str] =
+    &["string", "int", "long", "double", "decimal", "date", "bool"];
+
+/// CsvOptions describes how the rows of a '.csv' docs file are converted into documents. The first
+/// row of the file is a header that names the field each column is written to. A dotted header,
+/// such as "a.b", writes the field b of the nested document a.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CsvOptions {
+    /// columns maps header names to the bsonType their values are converted to. Optional.
+    ///
+    /// Each value is either a bsonType name or a document with a bsonType and, for dates, a format
+    /// in the time crate's format description syntax. Dates without a format must be RFC 3339
+    /// strings. Columns that are not listed are loaded as strings.
+    ///
+    /// Example:
+    ///   columns:
+    ///     int0: "int"
+    ///     date0: { bsonType: "date", format: "[year]-[month]-[day]" }
+    pub columns: BTreeMap<String, CsvColumn>,
+
+    /// null_values lists the values that are loaded as null, whatever their column's type.
+    /// Defaults to [""].
+    pub null_values: Vec<String>,
+
+    /// delimiter separates the values of a row. It must be an ASCII character. Defaults to ','.
+    pub delimiter: char,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            columns: BTreeMap::new(),
+            null_values: vec![String::new()],
+            delimiter: ',',
+        }
+    }
+}
+
+/// CsvColumn is the conversion of a CSV column: either just a bsonType, or a bsonType with the
+/// format of its dates.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum CsvColumn {
+    BsonType(String),
+    Detailed {
+        #[serde(rename = "bsonType")]
+        bson_type: String,
+        format: Option<String>,
+    },
+}
+
+impl CsvColumn {
+    fn bson_type(&self) -> &str {
+        match self {
+            CsvColumn::BsonType(t) | CsvColumn::Detailed { bson_type: t, .. } => t,
+        }
+    }
+
+    fn format(&self) -> Option<&str> {
+        match self {
+            CsvColumn::BsonType(_) => None,
+            CsvColumn::Detailed { format, .. } => format.as_deref(),
+        }
+    }
+}
+
+/// Conversion is the parsed form of a CsvColumn, applied to every value in its column.
+enum Conversion {
+    String,
+    Int,
+    Long,
+    Double,
+    Decimal,
+    Bool,
+    Date(Option<OwnedFormatItem>),
+}
+
+impl Conversion {
+    fn new(column: &CsvColumn) -> std::result::Result<Self, String> {
+        let conversion = match (column.bson_type(), column.format()) {
+            ("date", Some(format)) => Conversion::Date(Some(
+                format_description::parse_owned::<2>(format)
+                    .map_err(|e| format!("invalid date format {format:?}: {e}"))?,
+            )),
+            ("date", None) => Conversion::Date(None),
+            (t, Some(_)) => return Err(format!("format is only supported for dates, not {t:?}")),
+            ("string", None) => Conversion::String,
+            ("int", None) => Conversion::Int,
+            ("long", None) => Conversion::Long,
+            ("double", None) => Conversion::Double,
+            ("decimal", None) => Conversion::Decimal,
+            ("bool", None) => Conversion::Bool,
+            (t, None) => {
+                return Err(format!(
+                    "unsupported bsonType {t:?}, expected one of {CSV_COLUMN_TYPES:?}"
+                ))
+            }
+        };
+        Ok(conversion)
+    }
+
+    fn convert(&self, value: &str) -> Option<Bson> {
+        match self {
+            Conversion::String => Some(Bson::String(value.to_string())),
+            Conversion::Int => value.parse().ok().map(Bson::Int32),
+            Conversion::Long => value.parse().ok().map(Bson::Int64),
+            Conversion::Double => value.parse().ok().map(Bson::Double),
+            Conversion::Decimal => value.parse::<Decimal128>().ok().map(Bson::Decimal128),
+            Conversion::Bool => match value.to_ascii_lowercase().as_str() {
+                "true" => Some(Bson::Boolean(true)),
+                "false" => Some(Bson::Boolean(false)),
+                _ => None,
+            },
+            Conversion::Date(None) => bson::DateTime::parse_rfc3339_str(value)
+                .ok()
+                .map(Bson::DateTime),
+            Conversion::Date(Some(format)) => OffsetDateTime::parse(value, format)
+                .or_else(|_| PrimitiveDateTime::parse(value, format).map(|d| d.assume_utc()))
+                .or_else(|_| Date::parse(value, format).map(|d| d.midnight().assume_utc()))
+                .ok()
+                .map(|d| {
+                    Bson::DateTime(bson::DateTime::from_millis(
+                        (d.unix_timestamp_nanos() / 1_000_000) as i64,
+                    ))
+                }),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Conversion::String => "string",
+            Conversion::Int => "int",
+            Conversion::Long => "long",
+            Conversion::Double => "double",
+            Conversion::Decimal => "decimal",
+            Conversion::Bool => "bool",
+            Conversion::Date(_) => "date",
+        }
+    }
+}
+
+/// validate_csv_options checks that the delimiter is an ASCII character, that every column maps to
+/// a supported bsonType, and that every date format is valid.
+pub(crate) fn validate_csv_options(options: &CsvOptions) -> Vec<String> {
+    delimiter_byte(options.delimiter)
+        .err()
+        .into_iter()
+        .chain(options.columns.iter().filter_map(|(name, column)| {
+            Conversion::new(column)
+                .err()
+                .map(|e| format!("column {name:?}: {e}"))
+        }))
+        .collect()
+}
+
+/// read_csv reads the header of a CSV file and returns its remaining rows as documents. Rows are
+/// read one at a time. Values may be quoted, in which case they may contain the delimiter, line
+/// breaks, and doubled quotes.
+pub(crate) fn read_csv(
+    reader: impl BufRead + Send + 'static,
+    file: String,
+    options: &CsvOptions,
+) -> Result<impl Iterator<Item = Result<Bson>> + Send + 'static> {
+    let error =
+        move |message: String| DataLoaderError::InvalidDocsFile(format!("{file}: {message}"));
+    let delimiter = delimiter_byte(options.delimiter).map_err(&error)?;
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(reader);
+
+    let headers = reader
+        .headers()
+        .map_err(|e| error(e.to_string()))?
+        .iter()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    if headers.is_empty() {
+        return Err(error("missing header row".to_string()));
+    }
+    for name in options.columns.keys() {
+        if !headers.contains(name) {
+            return Err(error(format!("column {name:?} is not in the header")));
+        }
+    }
+    for (i, a) in headers.iter().enumerate() {
+        for b in &headers[i + 1..] {
+            if a == b || b.starts_with(&format!("{a}.")) || a.starts_with(&format!("{b}.")) {
+                return Err(error(format!("header {a:?} conflicts with header {b:?}")));
+            }
+        }
+    }
+
+    let columns = headers
+        .iter()
+        .map(|name| {
+            let conversion = match options.columns.get(name) {
+                Some(column) => {
+                    Conversion::new(column).map_err(|e| error(format!("column {name:?}: {e}")))?
+                }
+                None => Conversion::String,
+            };
+            let path = name.split('.').map(str::to_string).collect::<Vec<_>>();
+            Ok((path, conversion))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let null_values = options.null_values.clone();
+
+    let mut records = reader.into_records();
+    Ok(std::iter::from_fn(move || {
+        let record = match records.next()? {
+            Ok(record) => record,
+            Err(e) => return Some(Err(error(e.to_string()))),
+        };
+        let line = record.position().map_or(0, csv::Position::line);
+        if record.len() != columns.len() {
+            return Some(Err(error(format!(
+                "line {line}: expected {} values, found {}",
+                columns.len(),
+                record.len()
+            ))));
+        }
+
+        let mut doc = Document::new();
+        for ((path, conversion), value) in columns.iter().zip(&record) {
+            let converted = if null_values.iter().any(|null| null == value) {
+                Bson::Null
+            } else {
+                match conversion.convert(value) {
+                    Some(converted) => converted,
+                    None => {
+                        return Some(Err(error(format!(
+                            "line {line}: column {:?}: cannot convert {value:?} to {}",
+                            path.join("."),
+                            conversion.name()
+                        ))))
+                    }
+                }
+            };
+            insert_path(&mut doc, path, converted);
+        }
+        Some(Ok(Bson::Document(doc)))
+    }))
+}
+
+/// delimiter_byte returns the delimiter as a single byte, which is all the CSV reader supports.
+fn delimiter_byte(delimiter: char) -> std::result::Result<u8, String> {
+    u8::try_from(delimiter)
+        .ok()
+        .filter(u8::is_ascii)
+        .ok_or_else(|| format!("delimiter {delimiter:?} is not an ASCII character"))
+}
+
+/// insert_path inserts a value at a dotted path, creating nested documents as needed. Headers are
+/// checked for conflicts beforehand, so every intermediate value is a document.
+fn insert_path(doc: &mut Document, path: &[String], value: Bson) {
+    match path {
+        [field] => {
+            doc.insert(field, value);
+        }
+        [field, rest @ ..] => {
+            if let Bson::Document(nested) = doc
+                .entry(field.clone())
+                .or_insert_with(|| Bson::Document(Document::new()))
+            {
+                insert_path(nested, rest, value);
+            }
+        }
+        [] => {}
+    }
+}
@@ -0,0 +1,260 @@
+use crate::{
+    journal::BACKUP_PREFIX, validate::SCHEMA_COLLECTION, CollectionData, DataLoaderError, Result,
+    TestDataEntry, TestDataFile, ViewData, ViewDefinition,
+};
+use mongodb::{
+    bson::{self, doc, Bson, Document},
+    options::CreateCollectionOptions,
+    Client, Database, IndexModel,
+};
+use std::{fs, path::Path};
+
+/// dump reads the requested namespaces from the mongod and returns them as a TestDataFile. Each
+/// namespace is either "<db>.<name>", for a single collection or view, or "<db>", for every
+/// collection and view in the database. Schemas are read from ADF via sqlGetSchema when an ADF
+/// client is provided, and from the mongod's __sql_schemas collection otherwise.
+pub(crate) async fn dump(
+    mdb_client: &Client,
+    adf_client: Option<&Client>,
+    namespaces: &[String],
+) -> Result<TestDataFile> {
+    let mut dataset = vec![];
+    for namespace in namespaces {
+        let (db_name, names) = match namespace.split_once('.') {
+            Some((db_name, name)) => (db_name, vec![name.to_string()]),
+            None => {
+                let mut names = mdb_client
+                    .database(namespace)
+                    .list_collection_names()
+                    .await?
+                    .into_iter()
+                    .filter(|name| {
+                        !name.starts_with("system.")
+                            && !name.starts_with(BACKUP_PREFIX)
+                            && name != SCHEMA_COLLECTION
+                    })
+                    .collect::<Vec<_>>();
+                names.sort();
+                (namespace.as_str(), names)
+            }
+        };
+
+        let db = mdb_client.database(db_name);
+        for name in names {
+            println!("\tDumping {db_name}.{name}");
+            let mut entry = read_namespace(&db, &name)
+                .await?
+                .ok_or_else(|| DataLoaderError::NamespaceNotFound(format!("{db_name}.{name}")))?;
+            entry.schema = read_schema(&db, adf_client, &name).await?;
+            dataset.push(to_fixture_entry(entry));
+        }
+    }
+
+    Ok(TestDataFile {
+        path: Default::default(),
+        include: vec![],
+        schemas: Default::default(),
+        schema_file: None,
+        dataset,
+        users: vec![],
+        roles: vec![],
+    })
+}
+
+/// write_test_data_file writes a TestDataFile to the provided path, as JSON if the path has a
+/// '.json' extension and as YAML otherwise. If no path is provided, YAML is written to stdout.
+pub(crate) fn write_test_data_file(tdf: &TestDataFile, path: Option<&Path>) -> Result<()> {
+    match path {
+        Some(path) if path.extension().is_some_and(|ext| ext == "json") => {
+            fs::write(path, serde_json::to_string_pretty(tdf)?)?
+        }
+        Some(path) => fs::write(path, serde_yaml::to_string(tdf)?)?,
+        None => print!("{}", serde_yaml::to_string(tdf)?),
+    }
+    Ok(())
+}
+
+/// to_fixture_entry converts the documents and view pipeline of an entry read from the mongod with
+/// to_fixture_bson.
+fn to_fixture_entry(mut entry: TestDataEntry) -> TestDataEntry {
+    if let Some(c) = entry.collection.as_mut() {
+        c.docs = c.docs.drain(..).map(to_fixture_bson).collect();
+    }
+    if let Some(d) = entry.view.as_mut().and_then(|v| v.definition.as_mut()) {
+        d.pipeline = d
+            .pipeline
+            .drain(..)
+            .filter_map(|stage| match to_fixture_bson(Bson::Document(stage)) {
+                Bson::Document(stage) => Some(stage),
+                _ => None,
+            })
+            .collect();
+    }
+    entry
+}
+
+/// read_namespace reads a single collection, with its documents and indexes, or a single view,
+/// with its definition. Returns None if the namespace does not exist. The schema is not read.
+pub(crate) async fn read_namespace(db: &Database, name: &str) -> Result<Option<TestDataEntry>> {
+    let mut specs = db.list_collections().filter(doc! {"name": name}).await?;
+    if !specs.advance().await? {
+        return Ok(None);
+    }
+    let mut spec = specs.deserialize_current()?;
+
+    if let Some(view_on) = spec.options.view_on.take() {
+        return Ok(Some(TestDataEntry {
+            db: db.name().to_string(),
+            collection: None,
+            view: Some(ViewData {
+                name: name.to_string(),
+                definition: Some(ViewDefinition {
+                    view_on,
+                    pipeline: spec.options.pipeline.take().unwrap_or_default(),
+                }),
+            }),
+            schema: None,
+            tpch: None,
+            location: String::new(),
+        }));
+    }
+
+    let collection = db.collection::<Bson>(name);
+    let mut docs = vec![];
+    let mut cursor = collection.find(doc! {}).sort(doc! {"_id": 1}).await?;
+    while cursor.advance().await? {
+        docs.push(cursor.deserialize_current()?);
+    }
+
+    // The _id index always exists, so it is not part of the dumped indexes. The index version is
+    // chosen by the server, so it is omitted as well.
+    let mut indexes = vec![];
+    let mut cursor = collection.list_indexes().await?;
+    while cursor.advance().await? {
+        let mut index: IndexModel = cursor.deserialize_current()?;
+        if index.keys == doc! {"_id": 1} {
+            continue;
+        }
+        if let Some(options) = index.options.as_mut() {
+            options.version = None;
+        }
+        indexes.push(index);
+    }
+
+    Ok(Some(TestDataEntry {
+        db: db.name().to_string(),
+        collection: Some(CollectionData {
+            name: name.to_string(),
+            docs,
+            docs_file: None,
+            csv: None,
+            strict_extended_json: false,
+            generate: None,
+            generate_from_schema: None,
+            tpch: None,
+            options: creation_options(spec.options)?,
+            schema_validator: None,
+            indexes: (!indexes.is_empty()).then_some(indexes),
+        }),
+        view: None,
+        schema: None,
+        tpch: None,
+        location: String::new(),
+    }))
+}
+
+/// creation_options returns the options a collection was created with, as reported by
+/// listCollections, or None if it was created without any. Values the server derives from other
+/// options, such as the bucketing of a time-series collection with a granularity, and the
+/// clustered index version, are omitted, so the options can be used to create the collection
+/// again.
+pub(crate) fn creation_options(
+    mut options: CreateCollectionOptions,
+) -> Result<Option<CreateCollectionOptions>> {
+    if let Some(timeseries) = options
+        .timeseries
+        .as_mut()
+        .filter(|t| t.granularity.is_some())
+    {
+        timeseries.bucket_max_span = None;
+        timeseries.bucket_rounding = None;
+    }
+    if let Some(clustered_index) = options.clustered_index.as_mut() {
+        clustered_index.v = None;
+    }
+    Ok((!bson::to_document(&options)?.is_empty()).then_some(options))
+}
+
+/// read_schema reads a namespace's schema from ADF when an ADF client is provided, and from the
+/// mongod's __sql_schemas collection otherwise.
+pub(crate) async fn read_schema(
+    db: &Database,
+    adf_client: Option<&Client>,
+    name: &str,
+) -> Result<Option<Bson>> {
+    match adf_client {
+        Some(adf_client) => adf_schema(&adf_client.database(db.name()), name).await,
+        None => mongod_schema(db, name).await,
+    }
+}
+
+/// mongod_schema reads a namespace's schema from the __sql_schemas collection, if there is one.
+async fn mongod_schema(db: &Database, name: &str) -> Result<Option<Bson>> {
+    let schema_doc = db
+        .collection::<Document>(SCHEMA_COLLECTION)
+        .find_one(doc! {"_id": name})
+        .await?;
+    Ok(schema_doc.and_then(|mut d| d.remove("schema")))
+}
+
+/// adf_schema reads a namespace's schema from ADF via sqlGetSchema, if there is one.
+async fn adf_schema(db: &Database, name: &str) -> Result<Option<Bson>> {
+    let res = db.run_command(doc! {"sqlGetSchema": name}).await?;
+    Ok(res
+        .get_document("schema")
+        .ok()
+        .and_then(|s| s.get_document("jsonSchema").ok())
+        .filter(|s| !s.is_empty())
+        .map(|s| Bson::Document(s.clone())))
+}
+
+/// to_fixture_bson converts a value read from the mongod into a form that serializes to YAML or
+/// JSON without losing its BSON type. Values that YAML and JSON represent natively (non-negative
+/// 32-bit integers, finite doubles, strings, booleans, and null) are kept as they are. Every other
+/// value is converted to its relaxed Extended JSON form, except integers, which would otherwise be
+/// read back with the wrong width and so use the canonical form: negative numbers are read back
+/// as 64-bit integers, and small 64-bit integers as 32-bit integers.
+pub(crate) fn to_fixture_bson(value: Bson) -> Bson {
+    match value {
+        Bson::Document(d) => Bson::Document(
+            d.into_iter()
+                .map(|(k, v)| (k, to_fixture_bson(v)))
+                .collect(),
+        ),
+        Bson::Array(a) => Bson::Array(a.into_iter().map(to_fixture_bson).collect()),
+        Bson::Int32(i) if i < 0 => Bson::Document(doc! {"$numberInt": i.to_string()}),
+        Bson::Int32(_) | Bson::String(_) | Bson::Boolean(_) | Bson::Null => value,
+        Bson::Double(d) if d.is_finite() => value,
+        Bson::Int64(i) => Bson::Document(doc! {"$numberLong": i.to_string()}),
+        _ => json_to_bson(value.into_relaxed_extjson()),
+    }
+}
+
+/// json_to_bson converts a JSON value into BSON structurally, without interpreting Extended JSON
+/// wrappers, so that they are serialized back out as-is.
+fn json_to_bson(value: serde_json::Value) -> Bson {
+    match value {
+        serde_json::Value::Null => Bson::Null,
+        serde_json::Value::Bool(b) => Bson::Boolean(b),
+        serde_json::Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => i32::try_from(i).map_or(Bson::Int64(i), Bson::Int32),
+            (None, Some(f)) => Bson::Double(f),
+            (None, None) => Bson::String(n.to_string()),
+        },
+        serde_json::Value::String(s) => Bson::String(s),
+        serde_json::Value::Array(a) => Bson::Array(a.into_iter().map(json_to_bson).collect()),
+        serde_json::Value::Object(o) => {
+            Bson::Document(o.into_iter().map(|(k, v)| (k, json_to_bson(v))).collect())
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use mongodb::bson::{Bson, DateTime, Decimal128, Document};
+
+/// check_wrapper checks that a document with a field name starting with '$' is a well-formed
+/// Extended JSON v2 type wrapper, in either its canonical or relaxed form, or a DBRef. The values
+/// of the document's fields have already been deserialized, so nested wrappers, like the
+/// {"$numberLong": ...} of a canonical $date, have already been checked and converted.
+///
+/// Legacy Extended JSON forms, such as {"$binary": <base64>, "$type": <subtype>} or a $date with
+/// a plain number, are rejected, as are field names starting with '$' that are not part of a
+/// wrapper.
+pub(crate) fn check_wrapper(doc: &Document) -> Result<(), String> {
+    let keys = doc.keys().map(String::as_str).collect::<Vec<_>>();
+    let Some(first) = keys.first().copied() else {
+        return Ok(());
+    };
+    if !first.starts_with('$') {
+        return match keys.iter().find(|key| key.starts_with('$')) {
+            Some(key) => Err(format!(
+                "field name \"{key}\" starts with '$' but the document is not an Extended JSON type wrapper"
+            )),
+            None => Ok(()),
+        };
+    }
+
+    let value = doc.get(first).unwrap();
+    let (well_formed, expected) = match keys.as_slice() {
+        ["$oid"] => (
+            matches!(value, Bson::String(s) if s.len() == 24 && is_hex(s)),
+            "a string of 24 hex digits",
+        ),
+        ["$symbol"] => (matches!(value, Bson::String(_)), "a string"),
+        ["$numberInt"] => (
+            matches!(value, Bson::String(s) if s.parse::<i32>().is_ok()),
+            "a string holding a 32-bit integer",
+        ),
+        ["$numberLong"] => (
+            matches!(value, Bson::String(s) if s.parse::<i64>().is_ok()),
+            "a string holding a 64-bit integer",
+        ),
+        ["$numberDouble"] => (
+            matches!(value, Bson::String(s) if is_double(s)),
+            "a string holding a number, \"Infinity\", \"-Infinity\", or \"NaN\"",
+        ),
+        ["$numberDecimal"] => (
+            matches!(value, Bson::String(s) if s.parse::<Decimal128>().is_ok()),
+            "a string holding a decimal number",
+        ),
+        ["$binary"] => (
+            matches!(value, Bson::Document(d) if is_binary(d)),
+            "{ \"base64\": <string>, \"subType\": <1 or 2 hex digits> }",
+        ),
+        ["$uuid"] => (
+            matches!(value, Bson::String(s) if is_uuid(s)),
+            "a UUID string with hyphens",
+        ),
+        ["$code"] => (matches!(value, Bson::String(_)), "a string"),
+        ["$code", "$scope"] => (
+            matches!(value, Bson::String(_))
+                && matches!(doc.get("$scope"), Some(Bson::Document(_))),
+            "a string, with a document $scope",
+        ),
+        ["$timestamp"] => (
+            matches!(value, Bson::Document(d) if is_timestamp(d)),
+            "{ \"t\": <uint32>, \"i\": <uint32> }",
+        ),
+        ["$regularExpression"] => (
+            matches!(value, Bson::Document(d) if is_regex(d)),
+            "{ \"pattern\": <string>, \"options\": <string> }",
+        ),
+        ["$dbPointer"] => (
+            matches!(value, Bson::Document(d) if is_db_pointer(d)),
+            "{ \"$ref\": <string>, \"$id\": <ObjectId> }",
+        ),
+        // A canonical date's {"$numberLong": ...} has already been converted to a long.
+        ["$date"] => (
+            match value {
+                Bson::String(s) => DateTime::parse_rfc3339_str(s).is_ok(),
+                Bson::Int64(_) => true,
+                _ => false,
+            },
+            "an ISO-8601 string or { \"$numberLong\": <string> }",
+        ),
+        ["$minKey"] | ["$maxKey"] => (value == &Bson::Int32(1), "1"),
+        ["$undefined"] => (value == &Bson::Boolean(true), "true"),
+        ["$ref", "$id", rest @ ..] => return check_db_ref(doc, rest),
+        _ => {
+            return Err(format!(
+                "{} is not an Extended JSON v2 type wrapper",
+                Bson::Document(doc.clone())
+            ))
+        }
+    };
+
+    if well_formed {
+        Ok(())
+    } else {
+        Err(format!(
+            "malformed Extended JSON {first}: expected {expected}, found {}",
+            Bson::Document(doc.clone())
+        ))
+    }
+}
+
+/// check_db_ref checks the fields of a DBRef after its $ref and $id: an optional $db string,
+/// followed by fields whose names do not start with '$'.
+fn check_db_ref(doc: &Document, rest: &[&str]) -> Result<(), String> {
+    let rest = match rest {
+        ["$db", rest @ ..] if matches!(doc.get("$db"), Some(Bson::String(_))) => rest,
+        rest => rest,
+    };
+    if !matches!(doc.get("$ref"), Some(Bson::String(_))) {
+        return Err(format!(
+            "malformed DBRef: expected $ref to be a string, found {}",
+            Bson::Document(doc.clone())
+        ));
+    }
+    match rest.iter().find(|key| key.starts_with('$')) {
+        Some(key) => Err(format!(
+            "malformed DBRef: unexpected field \"{key}\" in {}",
+            Bson::Document(doc.clone())
+        )),
+        None => Ok(()),
+    }
+}
+
+fn is_hex(s: &str) -> bool {
+    s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn is_double(s: &str) -> bool {
+    matches!(s, "Infinity" | "-Infinity" | "NaN")
+        || (s.parse::<f64>().is_ok()
+            && s.chars()
+                .all(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')))
+}
+
+fn is_binary(d: &Document) -> bool {
+    d.keys().count() == 2
+        && matches!(d.get("base64"), Some(Bson::String(_)))
+        && matches!(d.get("subType"), Some(Bson::String(s)) if (1..=2).contains(&s.len()) && is_hex(s))
+}
+
+fn is_uuid(s: &str) -> bool {
+    let groups = s.split('-').collect::<Vec<_>>();
+    groups.iter().map(|g| g.len()).eq([8, 4, 4, 4, 12]) && groups.iter().all(|g| is_hex(g))
+}
+
+fn is_timestamp(d: &Document) -> bool {
+    let is_u32 = |key| match d.get(key) {
+        Some(Bson::Int32(i)) => *i >= 0,
+        Some(Bson::Int64(i)) => u32::try_from(*i).is_ok(),
+        _ => false,
+    };
+    d.keys().count() == 2 && is_u32("t") && is_u32("i")
+}
+
+fn is_regex(d: &Document) -> bool {
+    d.keys().count() == 2
+        && matches!(d.get("pattern"), Some(Bson::String(_)))
+        && matches!(d.get("options"), Some(Bson::String(_)))
+}
+
+fn is_db_pointer(d: &Document) -> bool {
+    d.keys().count() == 2
+        && matches!(d.get("$ref"), Some(Bson::String(_)))
+        && matches!(d.get("$id"), Some(Bson::ObjectId(_)))
+}
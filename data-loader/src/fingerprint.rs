@@ -0,0 +1,215 @@
+use crate::{
+    journal::{Journal, Operation},
+    Mode, Result, TestDataEntry, TestDataFile,
+};
+use mongodb::{
+    bson::{self, datetime, doc, Document},
+    Client, Collection,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io,
+};
+
+/// The database in which the loader stores its own metadata. A dedicated database is used so that
+/// the test databases only ever contain the namespaces described by the data files.
+pub(crate) const METADATA_DB: &str = "__data_loader";
+
+/// The collection in which the fingerprint of every loaded namespace is stored. Each document has
+/// the form { _id: "<db>.<name>", fingerprint: <hex string>, lastLoaded: <date> }.
+const FINGERPRINT_COLLECTION: &str = "fingerprints";
+
+/// schema_target describes where a load writes schemas: to the mongod, to ADF, or to ADF after
+/// installing the data files' views in the named store.
+pub(crate) fn schema_target(mode: &Mode, adf_views: Option<&str>) -> String {
+    match (mode, adf_views) {
+        (Mode::Mongod, _) => "mongod".to_string(),
+        (Mode::Adf(_), None) => "adf".to_string(),
+        (Mode::Adf(_), Some(store_name)) => format!("adf with views in {store_name}"),
+    }
+}
+
+/// fingerprint computes a content hash of an entry. The hash covers everything that is written for
+/// the entry: its namespace, documents, indexes, view definition, and schema, along with the schema
+/// target, since a load only writes schemas where its mode decides, and an ADF-mode load deletes
+/// the entry's mongod schema. The contents of the entry's docs file, if it has one, are hashed as
+/// well.
+pub(crate) fn fingerprint(entry: &TestDataEntry, schema_target: &str) -> Result<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(bson::to_vec(&bson::to_document(entry)?)?);
+    hasher.update(schema_target.as_bytes());
+    if let Some(docs_file) = entry.collection.as_ref().and_then(|c| c.docs_file.as_ref()) {
+        io::copy(&mut File::open(docs_file)?, &mut hasher)?;
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn fingerprint_collection(client: &Client) -> Collection<Document> {
+    client
+        .database(METADATA_DB)
+        .collection(FINGERPRINT_COLLECTION)
+}
+
+/// changed_entries returns copies of the test data files that only contain the entries which need
+/// to be (re)loaded. An entry is unchanged, and therefore skipped, if the fingerprint recorded by a
+/// previous run matches its current fingerprint for the schema target and its namespace still
+/// exists, and, for a view, if the namespace it is defined on is unchanged as well. When force is
+/// true, every entry is considered changed.
+pub(crate) async fn changed_entries(
+    client: &Client,
+    test_data_files: &[TestDataFile],
+    schema_target: &str,
+    force: bool,
+) -> Result<Vec<TestDataFile>> {
+    if force {
+        println!("\t--force provided, all namespaces will be reloaded");
+        return Ok(test_data_files.to_vec());
+    }
+
+    let mut recorded = HashMap::new();
+    let mut cursor = fingerprint_collection(client).find(doc! {}).await?;
+    while cursor.advance().await? {
+        let doc = cursor.deserialize_current()?;
+        if let (Ok(ns), Ok(fp)) = (doc.get_str("_id"), doc.get_str("fingerprint")) {
+            recorded.insert(ns.to_string(), fp.to_string());
+        }
+    }
+
+    let mut existing: HashMap<String, HashSet<String>> = HashMap::new();
+    for entry in test_data_files.iter().flat_map(|tdf| &tdf.dataset) {
+        if !existing.contains_key(&entry.db) {
+            let names = client
+                .database(entry.db.as_str())
+                .list_collection_names()
+                .await?;
+            existing.insert(entry.db.clone(), names.into_iter().collect());
+        }
+    }
+    let mut changed = changed_namespaces(test_data_files, schema_target, &recorded, &existing)?;
+    for ns in add_dependent_views(test_data_files, &mut changed) {
+        println!("\tReloading {ns}: a namespace it is defined on changed");
+    }
+
+    Ok(test_data_files
+        .iter()
+        .map(|tdf| {
+            let mut changed_file = tdf.clone();
+            changed_file.dataset.retain(|entry| {
+                let ns = entry.namespace();
+                if !changed.contains(&ns) {
+                    println!("\tSkipping {ns}: unchanged since the last load");
+                }
+                changed.contains(&ns)
+            });
+            changed_file
+        })
+        .collect())
+}
+
+/// changed_namespaces returns the namespace of every entry whose recorded fingerprint does not match
+/// its fingerprint for the schema target, or whose namespace does not exist. Recorded fingerprints
+/// are keyed by namespace, and existing namespace names by database.
+pub(crate) fn changed_namespaces(
+    test_data_files: &[TestDataFile],
+    schema_target: &str,
+    recorded: &HashMap<String, String>,
+    existing: &HashMap<String, HashSet<String>>,
+) -> Result<HashSet<String>> {
+    let mut changed = HashSet::new();
+    for entry in test_data_files.iter().flat_map(|tdf| &tdf.dataset) {
+        if recorded.get(&entry.namespace()) != Some(&fingerprint(entry, schema_target)?)
+            || !existing
+                .get(&entry.db)
+                .is_some_and(|names| names.contains(entry.name()))
+        {
+            changed.insert(entry.namespace());
+        }
+    }
+    Ok(changed)
+}
+
+/// add_dependent_views adds every view in the test data files that is defined, directly or through
+/// other views, on a changed namespace to the changed namespaces, since the documents a view
+/// returns, and so the schema inferred for it, depend on the namespace it is defined on. Returns
+/// the namespaces of the views added, in the order they were added.
+pub(crate) fn add_dependent_views(
+    test_data_files: &[TestDataFile],
+    changed: &mut HashSet<String>,
+) -> Vec<String> {
+    let views = test_data_files
+        .iter()
+        .flat_map(|tdf| &tdf.dataset)
+        .filter_map(|entry| {
+            let definition = entry.view.as_ref()?.definition.as_ref()?;
+            Some((
+                entry.namespace(),
+                format!("{}.{}", entry.db, definition.view_on),
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let mut added = vec![];
+    loop {
+        let dependent = views
+            .iter()
+            .filter(|(view, view_on)| !changed.contains(view) && changed.contains(view_on))
+            .map(|(view, _)| view.clone())
+            .collect::<Vec<_>>();
+        if dependent.is_empty() {
+            return added;
+        }
+        changed.extend(dependent.iter().cloned());
+        added.extend(dependent);
+    }
+}
+
+/// forget_fingerprints removes the recorded fingerprints of every entry in the test data files.
+/// This must happen before the entries' namespaces are dropped, so that a failed load is never
+/// mistaken for an unchanged one by a later run. The removed fingerprints are recorded in the
+/// journal, so they are restored along with their namespaces if the run fails.
+pub(crate) async fn forget_fingerprints(
+    client: &Client,
+    test_data_files: &[TestDataFile],
+    journal: &Journal,
+) -> Result<()> {
+    let collection = fingerprint_collection(client);
+    for ns in test_data_files
+        .iter()
+        .flat_map(|tdf| tdf.dataset.iter().map(TestDataEntry::namespace))
+    {
+        if let Some(document) = collection.find_one_and_delete(doc! {"_id": ns}).await? {
+            journal.record(Operation::DeletedDocument {
+                db: METADATA_DB.to_string(),
+                collection: FINGERPRINT_COLLECTION.to_string(),
+                document,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// record_fingerprints stores the fingerprint of every entry in the test data files for the schema
+/// target. This must only happen after the entries are fully loaded.
+pub(crate) async fn record_fingerprints(
+    client: &Client,
+    test_data_files: &[TestDataFile],
+    schema_target: &str,
+) -> Result<()> {
+    let collection = fingerprint_collection(client);
+    for entry in test_data_files.iter().flat_map(|tdf| tdf.dataset.iter()) {
+        collection
+            .replace_one(
+                doc! {"_id": entry.namespace()},
+                doc! {
+                    "_id": entry.namespace(),
+                    "fingerprint": fingerprint(entry, schema_target)?,
+                    "lastLoaded": datetime::DateTime::now(),
+                },
+            )
+            .upsert(true)
+            .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,409 @@
+use crate::{DataLoaderError, Result};
+use indexmap::IndexMap;
+use mongodb::bson::{self, Bson, Document};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The characters random strings are made of when no alphabet is specified.
+const DEFAULT_ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// GenerateOptions describes a set of synthetic documents. The documents are generated from a
+/// seeded random number generator, so the same options always produce the same documents.
+///
+/// Example:
+///   generate:
+///     count: 10000
+///     seed: 42
+///     fields:
+///       _id: { sequence: { start: 1 } }
+///       price: { range: { min: 0.0, max: 100.0 }, null_probability: 0.1 }
+///       tags: { array: { max_length: 3, items: { choice: [ "a", "b" ] } } }
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GenerateOptions {
+    /// count is the number of documents to generate. Required.
+    pub count: usize,
+
+    /// seed seeds the random number generator. Defaults to 0.
+    #[serde(default)]
+    pub seed: u64,
+
+    /// fields maps every field of the generated documents, in order, to the generator of its
+    /// values. Required.
+    pub fields: IndexMap<String, FieldGenerator>,
+}
+
+/// FieldGenerator generates the values of a single field.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FieldGenerator {
+    /// generator decides the values of the field. In data files, it is written as the single key
+    /// of the field's generator, e.g. range: { min: 0, max: 10 }.
+    #[serde(flatten)]
+    pub generator: ValueGenerator,
+
+    /// null_probability is the probability that the value is null. Defaults to 0.
+    #[serde(default)]
+    pub null_probability: f64,
+
+    /// missing_probability is the probability that the field is omitted from the document, or,
+    /// for array items, from the array. Defaults to 0.
+    #[serde(default)]
+    pub missing_probability: f64,
+}
+
+/// ValueGenerator is one of the kinds of values a FieldGenerator can generate.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueGenerator {
+    /// The same value every time.
+    Constant(Bson),
+    /// Consecutive integers, starting at start and incremented by step. Values are ints while they
+    /// fit in 32 bits, and longs afterward.
+    Sequence {
+        #[serde(default)]
+        start: i64,
+        #[serde(default = "default_step")]
+        step: i64,
+    },
+    /// A uniformly distributed number between min and max, inclusive. The values are doubles if
+    /// either bound is a double, ints if both bounds fit in 32 bits, and longs otherwise. Double
+    /// bounds must be finite, and so must the distance between them.
+    Range { min: Bson, max: Bson },
+    /// One of the listed values, chosen uniformly.
+    Choice(Vec<Bson>),
+    /// A string with a uniformly distributed length between min_length and max_length, made of
+    /// characters chosen uniformly from the alphabet.
+    String {
+        #[serde(default)]
+        min_length: usize,
+        max_length: usize,
+        alphabet: Option<String>,
+    },
+    /// A uniformly distributed date between min and max, inclusive, given as RFC 3339 strings.
+    Date { min: String, max: String },
+    /// A nested document with the given fields.
+    Object(IndexMap<String, FieldGenerator>),
+    /// An array with a uniformly distributed length between min_length and max_length.
+    Array {
+        #[serde(default)]
+        min_length: usize,
+        max_length: usize,
+        items: Box<FieldGenerator>,
+    },
+}
+
+fn default_step() -> i64 {
+    1
+}
+
+/// Compiled is the checked form of a FieldGenerator, ready to generate values.
+struct Compiled {
+    /// The path of the field, used to keep a separate counter for every sequence.
+    path: String,
+    kind: CompiledKind,
+    null_probability: f64,
+    missing_probability: f64,
+}
+
+enum CompiledKind {
+    Constant(Bson),
+    Sequence {
+        start: i64,
+        step: i64,
+    },
+    IntRange(i32, i32),
+    LongRange(i64, i64),
+    DoubleRange(f64, f64),
+    Choice(Vec<Bson>),
+    String {
+        min_length: usize,
+        max_length: usize,
+        alphabet: Vec<char>,
+    },
+    Date(i64, i64),
+    Object(Vec<(String, Compiled)>),
+    Array {
+        min_length: usize,
+        max_length: usize,
+        items: Box<Compiled>,
+    },
+}
+
+/// validate_generate_options returns a message for every invalid generator in the options.
+pub(crate) fn validate_generate_options(options: &GenerateOptions) -> Vec<String> {
+    let mut errors = vec![];
+    compile_fields(&options.fields, "", &mut errors);
+    errors
+}
+
+impl GenerateOptions {
+    /// documents returns the generated documents, one at a time. Returns an InvalidGenerator error
+    /// if any generator is invalid.
+    pub(crate) fn documents(&self) -> Result<impl Iterator<Item = Bson> + Send + 'static> {
+        let mut errors = vec![];
+        let fields = compile_fields(&self.fields, "", &mut errors);
+        if !errors.is_empty() {
+            return Err(DataLoaderError::InvalidGenerator(errors));
+        }
+
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        let mut sequences = HashMap::new();
+        Ok((0..self.count)
+            .map(move |_| Bson::Document(generate_document(&fields, &mut rng, &mut sequences))))
+    }
+}
+
+fn compile_fields(
+    fields: &IndexMap<String, FieldGenerator>,
+    path: &str,
+    errors: &mut Vec<String>,
+) -> Vec<(String, Compiled)> {
+    fields
+        .iter()
+        .map(|(name, field)| {
+            let field_path = if path.is_empty() {
+                name.clone()
+            } else {
+                format!("{path}.{name}")
+            };
+            (name.clone(), compile(field, field_path, errors))
+        })
+        .collect()
+}
+
+/// compile checks a generator and converts it to its Compiled form. Problems are added to errors,
+/// each prefixed with the path of the field.
+fn compile(field: &FieldGenerator, path: String, errors: &mut Vec<String>) -> Compiled {
+    let mut problems = vec![];
+    let mut error = |message: String| problems.push(message);
+    for (name, probability) in [
+        ("null_probability", field.null_probability),
+        ("missing_probability", field.missing_probability),
+    ] {
+        if !(0.0..=1.0).contains(&probability) {
+            error(format!("{name} must be between 0 and 1"));
+        }
+    }
+
+    let kind = match &field.generator {
+        ValueGenerator::Constant(value) => CompiledKind::Constant(value.clone()),
+        ValueGenerator::Sequence { start, step } => CompiledKind::Sequence {
+            start: *start,
+            step: *step,
+        },
+        ValueGenerator::Range { min, max } => match (min, max) {
+            (Bson::Int32(_) | Bson::Int64(_), Bson::Int32(_) | Bson::Int64(_))
+                if as_i64(min) <= as_i64(max) =>
+            {
+                match (i32::try_from(as_i64(min)), i32::try_from(as_i64(max))) {
+                    (Ok(min), Ok(max)) => CompiledKind::IntRange(min, max),
+                    _ => CompiledKind::LongRange(as_i64(min), as_i64(max)),
+                }
+            }
+            (Bson::Double(_), _) | (_, Bson::Double(_))
+                if !as_f64(min).is_finite() || !as_f64(max).is_finite() =>
+            {
+                error(format!(
+                    "range bounds must be finite, found {min} and {max}"
+                ));
+                CompiledKind::Constant(Bson::Null)
+            }
+            (
+                Bson::Int32(_) | Bson::Int64(_) | Bson::Double(_),
+                Bson::Int32(_) | Bson::Int64(_) | Bson::Double(_),
+            ) if as_f64(min) <= as_f64(max) && !(as_f64(max) - as_f64(min)).is_finite() => {
+                error("range is too wide: max - min must be finite".to_string());
+                CompiledKind::Constant(Bson::Null)
+            }
+            (
+                Bson::Int32(_) | Bson::Int64(_) | Bson::Double(_),
+                Bson::Int32(_) | Bson::Int64(_) | Bson::Double(_),
+            ) if as_f64(min) <= as_f64(max) => CompiledKind::DoubleRange(as_f64(min), as_f64(max)),
+            (
+                Bson::Int32(_) | Bson::Int64(_) | Bson::Double(_),
+                Bson::Int32(_) | Bson::Int64(_) | Bson::Double(_),
+            ) => {
+                error(format!("range min {min} is greater than max {max}"));
+                CompiledKind::Constant(Bson::Null)
+            }
+            _ => {
+                error(format!(
+                    "range bounds must be numbers, found {min} and {max}"
+                ));
+                CompiledKind::Constant(Bson::Null)
+            }
+        },
+        ValueGenerator::Choice(values) => {
+            if values.is_empty() {
+                error("choice must list at least one value".to_string());
+            }
+            CompiledKind::Choice(values.clone())
+        }
+        ValueGenerator::String {
+            min_length,
+            max_length,
+            alphabet,
+        } => {
+            let alphabet = alphabet
+                .as_deref()
+                .unwrap_or(DEFAULT_ALPHABET)
+                .chars()
+                .collect::<Vec<_>>();
+            if min_length > max_length {
+                error(format!(
+                    "string min_length {min_length} is greater than max_length {max_length}"
+                ));
+            }
+            if alphabet.is_empty() {
+                error("string alphabet must not be empty".to_string());
+            }
+            CompiledKind::String {
+                min_length: *min_length,
+                max_length: *max_length,
+                alphabet,
+            }
+        }
+        ValueGenerator::Date { min, max } => {
+            match (
+                bson::DateTime::parse_rfc3339_str(min),
+                bson::DateTime::parse_rfc3339_str(max),
+            ) {
+                (Ok(min), Ok(max)) if min <= max => {
+                    CompiledKind::Date(min.timestamp_millis(), max.timestamp_millis())
+                }
+                (Ok(_), Ok(_)) => {
+                    error(format!("date min {min} is later than max {max}"));
+                    CompiledKind::Constant(Bson::Null)
+                }
+                _ => {
+                    error(format!(
+                        "date bounds must be RFC 3339 strings, found {min:?} and {max:?}"
+                    ));
+                    CompiledKind::Constant(Bson::Null)
+                }
+            }
+        }
+        ValueGenerator::Object(fields) => {
+            CompiledKind::Object(compile_fields(fields, &path, errors))
+        }
+        ValueGenerator::Array {
+            min_length,
+            max_length,
+            items,
+        } => {
+            if min_length > max_length {
+                error(format!(
+                    "array min_length {min_length} is greater than max_length {max_length}"
+                ));
+            }
+            CompiledKind::Array {
+                min_length: *min_length,
+                max_length: *max_length,
+                items: Box::new(compile(items, format!("{path}.[]"), errors)),
+            }
+        }
+    };
+
+    errors.extend(
+        problems
+            .into_iter()
+            .map(|message| format!("field {path:?}: {message}")),
+    );
+    Compiled {
+        path,
+        kind,
+        null_probability: field.null_probability,
+        missing_probability: field.missing_probability,
+    }
+}
+
+fn as_i64(value: &Bson) -> i64 {
+    match value {
+        Bson::Int32(i) => *i as i64,
+        Bson::Int64(i) => *i,
+        _ => 0,
+    }
+}
+
+fn as_f64(value: &Bson) -> f64 {
+    match value {
+        Bson::Int32(i) => *i as f64,
+        Bson::Int64(i) => *i as f64,
+        Bson::Double(d) => *d,
+        _ => 0.0,
+    }
+}
+
+fn generate_document(
+    fields: &[(String, Compiled)],
+    rng: &mut ChaCha8Rng,
+    sequences: &mut HashMap<String, i64>,
+) -> Document {
+    let mut doc = Document::new();
+    for (name, field) in fields {
+        if let Some(value) = generate(field, rng, sequences) {
+            doc.insert(name, value);
+        }
+    }
+    doc
+}
+
+/// generate returns the next value of a field, or None if the field is omitted. Probabilities of
+/// 0 do not draw from the random number generator, so adding a generator's null or missing
+/// probability only changes that generator's values.
+fn generate(
+    field: &Compiled,
+    rng: &mut ChaCha8Rng,
+    sequences: &mut HashMap<String, i64>,
+) -> Option<Bson> {
+    if field.missing_probability > 0.0 && rng.random_bool(field.missing_probability) {
+        return None;
+    }
+    if field.null_probability > 0.0 && rng.random_bool(field.null_probability) {
+        return Some(Bson::Null);
+    }
+
+    let value = match &field.kind {
+        CompiledKind::Constant(value) => value.clone(),
+        CompiledKind::Sequence { start, step } => {
+            let n = sequences.entry(field.path.clone()).or_insert(0);
+            let value = start.saturating_add(step.saturating_mul(*n));
+            *n += 1;
+            i32::try_from(value).map_or(Bson::Int64(value), Bson::Int32)
+        }
+        CompiledKind::IntRange(min, max) => Bson::Int32(rng.random_range(*min..=*max)),
+        CompiledKind::LongRange(min, max) => Bson::Int64(rng.random_range(*min..=*max)),
+        CompiledKind::DoubleRange(min, max) => Bson::Double(rng.random_range(*min..=*max)),
+        CompiledKind::Choice(values) => values[rng.random_range(0..values.len())].clone(),
+        CompiledKind::String {
+            min_length,
+            max_length,
+            alphabet,
+        } => {
+            let length = rng.random_range(*min_length..=*max_length);
+            Bson::String(
+                (0..length)
+                    .map(|_| alphabet[rng.random_range(0..alphabet.len())])
+                    .collect(),
+            )
+        }
+        CompiledKind::Date(min, max) => {
+            Bson::DateTime(bson::DateTime::from_millis(rng.random_range(*min..=*max)))
+        }
+        CompiledKind::Object(fields) => Bson::Document(generate_document(fields, rng, sequences)),
+        CompiledKind::Array {
+            min_length,
+            max_length,
+            items,
+        } => {
+            let length = rng.random_range(*min_length..=*max_length);
+            Bson::Array(
+                (0..length)
+                    .filter_map(|_| generate(items, rng, sequences))
+                    .collect(),
+            )
+        }
+    };
+    Some(value)
+}
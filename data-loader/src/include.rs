@@ -0,0 +1,190 @@
+use crate::{parse_data_file, DataLoaderError, Result, TestDataEntry, TestDataFile};
+use mongodb::bson::{Bson, Document};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The prefix a $ref may give the name of a schema fragment, as in JSON schema.
+const REF_PREFIX: &str = "#/schemas/";
+
+/// resolve appends the entries of every file the data file includes, directly or indirectly, to
+/// its own, and replaces every $ref in the schemas of its entries with the schema fragment it
+/// names. The fragments a file can reference are its own, those in its schema_file, and those of
+/// the files it includes. References are resolved file by file, so errors name the file that
+/// contains the reference. Afterward, the data file's include field lists the canonical paths of
+/// every file it includes, and of every schema_file read along the way. Included files are parsed
+/// with the same strict_extended_json setting as the data file.
+pub(crate) fn resolve(tdf: &mut TestDataFile, strict_extended_json: bool) -> Result<()> {
+    let path = tdf.path.canonicalize()?;
+    let mut included = HashSet::new();
+    resolve_file(tdf, strict_extended_json, &mut vec![path], &mut included)?;
+    tdf.include = included.into_iter().collect();
+    tdf.include.sort();
+    Ok(())
+}
+
+/// resolve_file resolves a single data file. The stack holds the canonical paths of the files
+/// being included, starting with the file that was read, and detects include cycles. Every file
+/// that is included is added to included, and is not included again.
+fn resolve_file(
+    tdf: &mut TestDataFile,
+    strict_extended_json: bool,
+    stack: &mut Vec<PathBuf>,
+    included: &mut HashSet<PathBuf>,
+) -> Result<()> {
+    let file = tdf.path.display().to_string();
+    let error = |message: String| DataLoaderError::UnresolvedReference(vec![message]);
+
+    if let Some(schema_file) = &tdf.schema_file {
+        if let Ok(path) = schema_file.canonicalize() {
+            included.insert(path);
+        }
+        let fragments = read_schema_file(schema_file).map_err(|e| {
+            error(format!(
+                "{file}: schema_file {}: {e}",
+                schema_file.display()
+            ))
+        })?;
+        merge_fragments(&mut tdf.schemas, fragments).map_err(|e| error(format!("{file}: {e}")))?;
+    }
+
+    let mut included_entries = vec![];
+    for include in tdf.include.clone() {
+        let path = include
+            .canonicalize()
+            .map_err(|e| error(format!("{file}: include {}: {e}", include.display())))?;
+        if let Some(start) = stack.iter().position(|p| *p == path) {
+            let cycle = stack[start..]
+                .iter()
+                .chain([&path])
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>();
+            return Err(error(format!(
+                "{file}: include {}: include cycle {}",
+                include.display(),
+                cycle.join(" -> ")
+            )));
+        }
+        if !included.insert(path.clone()) {
+            continue;
+        }
+
+        let Some(mut included_file) = parse_data_file(include.clone(), strict_extended_json)?
+        else {
+            return Err(error(format!(
+                "{file}: include {}: not a data file",
+                include.display()
+            )));
+        };
+        stack.push(path);
+        resolve_file(&mut included_file, strict_extended_json, stack, included)?;
+        stack.pop();
+
+        included_entries.extend(included_file.dataset);
+        tdf.users.extend(included_file.users);
+        tdf.roles.extend(included_file.roles);
+        merge_fragments(&mut tdf.schemas, included_file.schemas)
+            .map_err(|e| error(format!("{file}: include {}: {e}", include.display())))?;
+    }
+
+    let errors = resolve_entries(&file, &mut tdf.dataset, &tdf.schemas);
+    if !errors.is_empty() {
+        return Err(DataLoaderError::UnresolvedReference(errors));
+    }
+    tdf.dataset.extend(included_entries);
+    Ok(())
+}
+
+/// read_schema_file reads a YAML or JSON file that maps names to schema fragments.
+fn read_schema_file(path: &Path) -> std::result::Result<BTreeMap<String, Bson>, String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::from_str(&contents).map_err(|e| e.to_string()),
+        Some("yml" | "yaml") => serde_yaml::from_str(&contents).map_err(|e| e.to_string()),
+        _ => Err("must have a '.y[a]ml' or '.json' extension".to_string()),
+    }
+}
+
+/// merge_fragments adds the provided fragments to the existing ones. A name may only be defined
+/// more than once if every definition is the same.
+fn merge_fragments(
+    fragments: &mut BTreeMap<String, Bson>,
+    other: BTreeMap<String, Bson>,
+) -> std::result::Result<(), String> {
+    for (name, fragment) in other {
+        match fragments.get(&name) {
+            Some(existing) if *existing != fragment => {
+                return Err(format!(
+                    "schema fragment \"{name}\" is defined more than once, with different schemas"
+                ))
+            }
+            Some(_) => {}
+            None => {
+                fragments.insert(name, fragment);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// resolve_entries replaces the references in the schemas of the provided entries, and returns a
+/// message for every reference that cannot be resolved.
+fn resolve_entries(
+    file: &str,
+    entries: &mut [TestDataEntry],
+    fragments: &BTreeMap<String, Bson>,
+) -> Vec<String> {
+    let mut errors = vec![];
+    for (index, entry) in entries.iter_mut().enumerate() {
+        if let Some(schema) = entry.schema.as_mut() {
+            match resolve_schema(schema, fragments, &mut vec![]) {
+                Ok(resolved) => *schema = resolved,
+                Err(e) => errors.push(format!("{file}, entry {index}: schema: {e}")),
+            }
+        }
+    }
+    errors
+}
+
+/// resolve_schema returns the schema with every reference replaced by the fragment it names. The
+/// stack holds the names of the fragments being resolved, and detects reference cycles.
+fn resolve_schema(
+    schema: &Bson,
+    fragments: &BTreeMap<String, Bson>,
+    stack: &mut Vec<String>,
+) -> std::result::Result<Bson, String> {
+    match schema {
+        Bson::Document(doc) => match doc.get("$ref") {
+            Some(Bson::String(reference)) => {
+                let name = reference.strip_prefix(REF_PREFIX).unwrap_or(reference);
+                if doc.len() > 1 {
+                    return Err(format!("$ref \"{name}\" must not have other keywords"));
+                }
+                if stack.iter().any(|n| n == name) {
+                    return Err(format!("$ref cycle {} -> {name}", stack.join(" -> ")));
+                }
+                let fragment = fragments
+                    .get(name)
+                    .ok_or_else(|| format!("$ref \"{name}\" is not a defined schema fragment"))?;
+                stack.push(name.to_string());
+                let resolved = resolve_schema(fragment, fragments, stack);
+                stack.pop();
+                resolved
+            }
+            Some(reference) => Err(format!("$ref must be a string, found {reference}")),
+            None => doc
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), resolve_schema(value, fragments, stack)?)))
+                .collect::<std::result::Result<Document, String>>()
+                .map(Bson::Document),
+        },
+        Bson::Array(values) => values
+            .iter()
+            .map(|value| resolve_schema(value, fragments, stack))
+            .collect::<std::result::Result<Vec<_>, String>>()
+            .map(Bson::Array),
+        _ => Ok(schema.clone()),
+    }
+}
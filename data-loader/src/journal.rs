@@ -0,0 +1,397 @@
+use crate::{
+    users::{create_role, create_user, drop_role, drop_user, RoleData, UserData},
+    DataLoaderError, Result,
+};
+use mongodb::{
+    bson::{self, doc, Bson, Document},
+    options::CreateCollectionOptions,
+    Client, ClientSession, Collection,
+};
+use std::sync::{Arc, Mutex};
+
+/// The prefix given to the names of collections that a run replaces. Instead of being dropped,
+/// replaced collections are renamed to "<prefix><name>" in the same database, so that they can be
+/// restored if the run fails. They are dropped once the run succeeds. The documents of replaced
+/// time-series collections, which cannot be renamed, are copied to a backup with the same name.
+pub(crate) const BACKUP_PREFIX: &str = "__data_loader_backup.";
+
+/// An Operation is a change this run made to the mongod, recorded with enough information to undo
+/// it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Operation {
+    /// A collection or view was created, either explicitly or by inserting documents.
+    CreatedNamespace { db: String, name: String },
+    /// Indexes were created on a collection.
+    CreatedIndexes {
+        db: String,
+        name: String,
+        index_names: Vec<String>,
+    },
+    /// An existing collection was renamed to its backup name.
+    BackedUpCollection { db: String, name: String },
+    /// The documents of an existing time-series collection were copied to its backup name, and
+    /// the collection was dropped. The options are the collection's creation options, so that it
+    /// can be created again.
+    BackedUpTimeseries {
+        db: String,
+        name: String,
+        options: Document,
+    },
+    /// An existing view was dropped.
+    DroppedView {
+        db: String,
+        name: String,
+        view_on: String,
+        pipeline: Vec<Document>,
+    },
+    /// A document was deleted from a collection. Used for schema and fingerprint documents.
+    DeletedDocument {
+        db: String,
+        collection: String,
+        document: Document,
+    },
+    /// A user was created, after dropping any existing user with the same name.
+    CreatedUser { db: String, user: String },
+    /// A role was created, after dropping any existing role with the same name.
+    CreatedRole { db: String, role: String },
+    /// An existing user the data loader created was dropped. Holds the definition it was created
+    /// with.
+    DroppedUser(UserData),
+    /// An existing role the data loader created was dropped. Holds the definition it was created
+    /// with.
+    DroppedRole(RoleData),
+    /// A document was inserted into a collection. Used for schema and fingerprint documents.
+    InsertedDocument {
+        db: String,
+        collection: String,
+        id: Bson,
+    },
+}
+
+/// A Journal records every change a run makes to the mongod, in order. It is shared by all tasks
+/// of a run. If the run fails, rollback undoes exactly the recorded changes, in reverse order; if
+/// it succeeds, commit discards the backups kept for rollback.
+///
+/// Changes made to ADF are not recorded.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Journal {
+    operations: Arc<Mutex<Vec<Operation>>>,
+}
+
+impl Journal {
+    /// record appends an operation to the journal. Operations that implicitly create namespaces
+    /// must be recorded before they are sent, so that they are undone even if they are interrupted.
+    pub(crate) fn record(&self, operation: Operation) {
+        self.operations.lock().unwrap().push(operation);
+    }
+
+    fn operations(&self) -> Vec<Operation> {
+        self.operations.lock().unwrap().clone()
+    }
+
+    /// commit drops the backups of every collection this run replaced.
+    pub(crate) async fn commit(&self, client: &Client) -> Result<()> {
+        for operation in self.operations() {
+            if let Operation::BackedUpCollection { db, name }
+            | Operation::BackedUpTimeseries { db, name, .. } = operation
+            {
+                client
+                    .database(&db)
+                    .collection::<Document>(&format!("{BACKUP_PREFIX}{name}"))
+                    .drop()
+                    .await?;
+            }
+        }
+        self.operations.lock().unwrap().clear();
+        Ok(())
+    }
+
+    /// undo_plan returns the recorded operations in the order rollback undoes them: changes to
+    /// namespaces, users, and roles, most recent first, followed by changes to documents, most
+    /// recent first. Indexes created on a namespace this run created are left out, since they are
+    /// removed along with the namespace.
+    pub(crate) fn undo_plan(&self) -> Vec<Operation> {
+        let operations = self.operations();
+        let created = |db: &str, name: &str| {
+            operations.iter().any(|o| {
+                matches!(o, Operation::CreatedNamespace { db: d, name: n } if d == db && n == name)
+            })
+        };
+        let (documents, namespaces): (Vec<_>, Vec<_>) = operations
+            .iter()
+            .rev()
+            .filter(
+                |o| !matches!(o, Operation::CreatedIndexes { db, name, .. } if created(db, name)),
+            )
+            .cloned()
+            .partition(Operation::is_document_operation);
+        namespaces.into_iter().chain(documents).collect()
+    }
+
+    /// rollback undoes every recorded operation, in the order given by undo_plan. When the mongod
+    /// is part of a replica set, document changes are undone in a single multi-document
+    /// transaction, so either all or none of them are restored. A failure to undo one operation
+    /// does not stop the others from being undone; every failure is returned together as a
+    /// Rollback error. The journal is cleared either way.
+    pub(crate) async fn rollback(&self, client: &Client) -> Result<()> {
+        let mut errors = vec![];
+        let mut document_operations = vec![];
+        for operation in self.undo_plan() {
+            if operation.is_document_operation() {
+                document_operations.push(operation);
+            } else if let Err(e) = undo_namespace_operation(client, &operation).await {
+                errors.push(format!("{}: {e}", operation.description()));
+            }
+        }
+        if !document_operations.is_empty() {
+            errors.extend(undo_document_operations(client, &document_operations).await);
+        }
+
+        self.operations.lock().unwrap().clear();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(DataLoaderError::Rollback(errors))
+        }
+    }
+}
+
+impl Operation {
+    fn is_document_operation(&self) -> bool {
+        matches!(
+            self,
+            Operation::DeletedDocument { .. } | Operation::InsertedDocument { .. }
+        )
+    }
+
+    /// description describes how the operation is undone.
+    fn description(&self) -> String {
+        match self {
+            Operation::CreatedNamespace { db, name } => format!("dropping {db}.{name}"),
+            Operation::CreatedIndexes {
+                db,
+                name,
+                index_names,
+            } => format!("dropping indexes {index_names:?} on {db}.{name}"),
+            Operation::BackedUpCollection { db, name } => {
+                format!("restoring {db}.{name} from its backup")
+            }
+            Operation::BackedUpTimeseries { db, name, .. } => {
+                format!("recreating time-series collection {db}.{name} from its backup")
+            }
+            Operation::DroppedView {
+                db, name, view_on, ..
+            } => {
+                format!("recreating view {db}.{name} on {view_on}")
+            }
+            Operation::CreatedUser { db, user } => format!("dropping user {user} in {db}"),
+            Operation::CreatedRole { db, role } => format!("dropping role {role} in {db}"),
+            Operation::DroppedUser(user) => {
+                format!("recreating user {} in {}", user.user, user.db)
+            }
+            Operation::DroppedRole(role) => {
+                format!("recreating role {} in {}", role.role, role.db)
+            }
+            Operation::DeletedDocument {
+                db,
+                collection,
+                document,
+            } => format!(
+                "restoring document {} in {db}.{collection}",
+                document.get("_id").unwrap_or(&Bson::Null)
+            ),
+            Operation::InsertedDocument { db, collection, id } => {
+                format!("deleting document {id} from {db}.{collection}")
+            }
+        }
+    }
+}
+
+/// undo_namespace_operation reverts a single operation other than a DeletedDocument or
+/// InsertedDocument operation.
+async fn undo_namespace_operation(client: &Client, operation: &Operation) -> Result<()> {
+    println!("\tRollback: {}", operation.description());
+    match operation {
+        Operation::CreatedNamespace { db, name } => {
+            client
+                .database(db)
+                .collection::<Document>(name)
+                .drop()
+                .await?;
+        }
+        Operation::CreatedIndexes {
+            db,
+            name,
+            index_names,
+        } => {
+            let collection = client.database(db).collection::<Document>(name);
+            for index_name in index_names {
+                collection.drop_index(index_name).await?;
+            }
+        }
+        Operation::BackedUpCollection { db, name } => {
+            client
+                .database("admin")
+                .run_command(doc! {
+                    "renameCollection": format!("{db}.{BACKUP_PREFIX}{name}"),
+                    "to": format!("{db}.{name}"),
+                    "dropTarget": true,
+                })
+                .await?;
+        }
+        Operation::BackedUpTimeseries { db, name, options } => {
+            let database = client.database(db);
+            database.collection::<Document>(name).drop().await?;
+            database
+                .create_collection(name)
+                .with_options(bson::from_document::<CreateCollectionOptions>(
+                    options.clone(),
+                )?)
+                .await?;
+            let backup = database.collection::<Document>(&format!("{BACKUP_PREFIX}{name}"));
+            copy_documents(&backup, &database.collection(name)).await?;
+            backup.drop().await?;
+        }
+        Operation::DroppedView {
+            db,
+            name,
+            view_on,
+            pipeline,
+        } => {
+            let database = client.database(db);
+            database.collection::<Document>(name).drop().await?;
+            database
+                .create_collection(name)
+                .view_on(view_on.clone())
+                .pipeline(pipeline.clone())
+                .await?;
+        }
+        Operation::CreatedUser { db, user } => {
+            drop_user(client, db, user).await?;
+        }
+        Operation::CreatedRole { db, role } => {
+            drop_role(client, db, role).await?;
+        }
+        Operation::DroppedUser(user) => {
+            drop_user(client, &user.db, &user.user).await?;
+            create_user(client, user).await?;
+        }
+        Operation::DroppedRole(role) => {
+            drop_role(client, &role.db, &role.role).await?;
+            create_role(client, role).await?;
+        }
+        Operation::DeletedDocument { .. } | Operation::InsertedDocument { .. } => {
+            unreachable!("Invariant failed: document operations are undone separately.")
+        }
+    }
+    Ok(())
+}
+
+/// The number of documents copied back into a time-series collection per insert.
+const COPY_BATCH_SIZE: usize = 1000;
+
+/// copy_documents inserts every document of from into to, in batches.
+async fn copy_documents(from: &Collection<Document>, to: &Collection<Document>) -> Result<()> {
+    let mut cursor = from.find(doc! {}).await?;
+    let mut batch = vec![];
+    while cursor.advance().await? {
+        batch.push(cursor.deserialize_current()?);
+        if batch.len() == COPY_BATCH_SIZE {
+            to.insert_many(std::mem::take(&mut batch)).await?;
+        }
+    }
+    if !batch.is_empty() {
+        to.insert_many(batch).await?;
+    }
+    Ok(())
+}
+
+/// undo_document_operations reverts DeletedDocument and InsertedDocument operations, in order, and
+/// returns a message for every failure. When the mongod is part of a replica set, they are undone
+/// in a single transaction, so a failure undoes none of them.
+async fn undo_document_operations(client: &Client, operations: &[Operation]) -> Vec<String> {
+    let in_replica_set = match client
+        .database("admin")
+        .run_command(doc! {"hello": 1})
+        .await
+    {
+        Ok(hello) => hello.contains_key("setName"),
+        Err(e) => return vec![format!("restoring documents: {e}")],
+    };
+
+    if !in_replica_set {
+        let mut errors = vec![];
+        for operation in operations {
+            println!("\tRollback: {}", operation.description());
+            if let Err(e) = undo_document_operation(client, operation, None).await {
+                errors.push(format!("{}: {e}", operation.description()));
+            }
+        }
+        return errors;
+    }
+
+    println!("\tRollback: restoring documents in a transaction");
+    let res: Result<()> = async {
+        let mut session = client.start_session().await?;
+        session.start_transaction().await?;
+        for operation in operations {
+            println!("\tRollback: {}", operation.description());
+            undo_document_operation(client, operation, Some(&mut session)).await?;
+        }
+        session.commit_transaction().await?;
+        Ok(())
+    }
+    .await;
+    match res {
+        Ok(()) => vec![],
+        Err(e) => vec![format!("restoring documents in a transaction: {e}")],
+    }
+}
+
+/// undo_document_operation reverts a single DeletedDocument or InsertedDocument operation, within
+/// the provided session's transaction if there is one.
+async fn undo_document_operation(
+    client: &Client,
+    operation: &Operation,
+    session: Option<&mut ClientSession>,
+) -> Result<()> {
+    match operation {
+        Operation::DeletedDocument {
+            db,
+            collection,
+            document,
+        } => {
+            let collection = client.database(db).collection::<Document>(collection);
+            let filter = doc! {"_id": document.get("_id").cloned().unwrap_or(Bson::Null)};
+            match session {
+                Some(session) => {
+                    collection
+                        .replace_one(filter, document)
+                        .upsert(true)
+                        .session(session)
+                        .await?
+                }
+                None => {
+                    collection
+                        .replace_one(filter, document)
+                        .upsert(true)
+                        .await?
+                }
+            };
+        }
+        Operation::InsertedDocument { db, collection, id } => {
+            let collection = client.database(db).collection::<Document>(collection);
+            match session {
+                Some(session) => {
+                    collection
+                        .delete_one(doc! {"_id": id.clone()})
+                        .session(session)
+                        .await?
+                }
+                None => collection.delete_one(doc! {"_id": id.clone()}).await?,
+            };
+        }
+        _ => unreachable!("Invariant failed: only document operations are undone here."),
+    }
+    Ok(())
+}
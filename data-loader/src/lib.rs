@@ -0,0 +1,1293 @@
+//! A library for loading test data for SQL Engines integration tests into a mongod, and writing
+//! schemas to the mongod or ADF. It is also used by the data-loader binary.
+//!
+//! Test data is described by TestDataFiles, which are usually read from YAML or JSON data files.
+//! A DataLoader is configured with a DataLoaderBuilder, which reads and validates the data files
+//! when it is built, without connecting to any server. It can then load, drop, or set the schemas
+//! of every entry in them, using the client it is given:
+//!
+//! ```no_run
+//! # async fn example() -> data_loader::Result<()> {
+//! use data_loader::{DataLoader, Mode};
+//! use mongodb::Client;
+//!
+//! let loader = DataLoader::builder()
+//!     .target("resources/data")
+//!     .build()?;
+//! let client = Client::with_uri_str("mongodb://localhost:27017").await?;
+//! loader.load(&client, &Mode::Mongod).await?;
+//! # Ok(())
+//! # }
+//! ```
+mod adf_config;
+mod cli;
+mod coverage;
+mod csv;
+mod dump;
+mod extjson;
+mod fingerprint;
+mod generate;
+mod include;
+mod journal;
+mod loader;
+mod profile;
+mod schema;
+mod source;
+mod tags;
+#[cfg(test)]
+mod test;
+mod tpch;
+mod users;
+mod validate;
+mod validator;
+mod verify;
+
+pub use cli::run;
+pub use coverage::{Coverage, SchemaGenerateOptions};
+pub use csv::{CsvColumn, CsvOptions};
+pub use generate::{FieldGenerator, GenerateOptions, ValueGenerator};
+pub use loader::{DataLoader, DataLoaderBuilder, Event, Mode, Step};
+pub use tpch::{TpchOptions, TpchTable, TpchTableOptions};
+pub use users::{RoleData, RoleName, UserData};
+pub use validator::SchemaValidator;
+
+use glob::Pattern;
+use journal::{Journal, Operation, BACKUP_PREFIX};
+use loader::Hooks;
+use mongodb::{
+    bson::{self, datetime, doc, Bson, Document},
+    options::CreateCollectionOptions,
+    results::CollectionType,
+    Client, Database, IndexModel,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use thiserror::Error;
+use tokio::{
+    sync::Semaphore,
+    task::{JoinError, JoinSet},
+};
+use validate::SCHEMA_COLLECTION;
+
+/// A struct representing a YAML file that contains test data. Test data files contain a top-level
+/// `dataset` key, whose value is a list of TestDataEntries, and may include other files, define
+/// schema fragments, and define users and roles.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestDataFile {
+    /// The path this file was read from. Not part of the file format.
+    #[serde(skip)]
+    pub path: PathBuf,
+
+    /// include lists other data files whose entries, schema fragments, users, and roles are part of
+    /// this file. Optional.
+    ///
+    /// The paths are resolved relative to this file. Included files may include other files, but
+    /// not, directly or indirectly, themselves, and each file is included at most once. A file
+    /// included by another data file that is read is not read on its own, and neither is a
+    /// schema_file. Once this file is read, this lists every file it includes, directly or
+    /// indirectly, along with their schema files.
+    ///
+    /// Example:
+    ///   include: [ "shared/calcs.yml" ]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<PathBuf>,
+
+    /// schemas defines named schema fragments, which the schemas of entries in this file, and in
+    /// files that include it, can reference with { $ref: <name> }. Optional.
+    ///
+    /// A reference is replaced by the fragment it names, and may appear anywhere in a schema,
+    /// including in other fragments. A document with a $ref must not have any other keywords.
+    ///
+    /// Example:
+    ///   schemas:
+    ///     calcs: { bsonType: "object", properties: { num0: { bsonType: "double" } } }
+    ///   dataset:
+    ///     - { db: "test", view: { name: "calcs_view" }, schema: { $ref: "calcs" } }
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub schemas: BTreeMap<String, Bson>,
+
+    /// schema_file specifies a YAML or JSON file that maps names to schema fragments, which are
+    /// added to the fragments in schemas. Optional. The path is resolved relative to this file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_file: Option<PathBuf>,
+
+    /// dataset lists the entries of this file. Optional if this file only defines schema
+    /// fragments or includes other files.
+    #[serde(default)]
+    pub dataset: Vec<TestDataEntry>,
+
+    /// users lists the users to create on the mongod, so tests can authenticate as principals with
+    /// known credentials and privileges. Optional. See UserData for more details.
+    ///
+    /// Users are created after every entry is loaded, and are recreated on every load. They are
+    /// dropped along with the entries' namespaces.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub users: Vec<UserData>,
+
+    /// roles lists the custom roles to create on the mongod, before any user is created, so they
+    /// can be granted to users. Optional. See RoleData for more details.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<RoleData>,
+}
+
+/// A struct representing a YAML-specified test data entry. See the fields for what a test data
+/// entry may include. Most fields are optional.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestDataEntry {
+    /// db specifies the database for this test entry. Required.
+    pub db: String,
+
+    /// collection specifies the collection for this entry. Conditional.
+    /// Exactly one of 'collection', 'view', or 'tpch' must be specified for every test entry.
+    pub collection: Option<CollectionData>,
+
+    /// view specifies the view for this test entry. Conditional.
+    /// Exactly one of 'collection', 'view', or 'tpch' must be specified for every test entry.
+    ///
+    /// Note that ADF views are defined in ADF itself, not on the underlying datasource(s) -- in
+    /// this case, not on the mongod. They are defined in the ADF config file, separate from the
+    /// test data. Therefore, when run against ADF, this data loader ignores the pipeline field and
+    /// only sets schema for views, unless it is run with adf_views, in which case it installs the
+    /// view in ADF's storage configuration before setting its schema.
+    ///
+    /// When run against mongod directly, this data loader will not only set the schema for the view
+    /// it will also create it on the mongod using the provided pipeline field.
+    pub view: Option<ViewData>,
+
+    /// schema specifies the schema for this test entry. Optional.
+    ///
+    /// When run against ADF:
+    /// If provided, this data loader sets the collection or view schema using the sqlSetSchema
+    /// command. If not provided, this data loader sets the collection or view schema using the
+    /// sqlGenerateSchema command.
+    ///
+    /// When run against mongod:
+    /// If provided, this data loader sets the collection or view schema directly in the
+    /// __sql_schemas collection. If not provided, this data loader infers the schema locally, in
+    /// the same shape sqlGenerateSchema produces, and sets that instead. Collection schemas are
+    /// inferred from the entry's documents; view schemas are inferred from the documents the view
+    /// returns once it is created.
+    pub schema: Option<Bson>,
+
+    /// tpch specifies the built-in TPC-H-like dataset for this test entry. Conditional.
+    /// Exactly one of 'collection', 'view', or 'tpch' must be specified for every test entry.
+    ///
+    /// When the data file is read, the entry is replaced by a collection entry for every table of
+    /// the dataset, with the table's schema. See TpchOptions for more details.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tpch: Option<TpchOptions>,
+
+    /// location records where this entry was defined, as "<file>, entry <index>", for error
+    /// messages. Not part of the file format; set when the data file is read, so entries from
+    /// included files name the file they came from.
+    #[serde(skip)]
+    pub(crate) location: String,
+}
+
+impl TestDataFile {
+    /// new returns a test data file holding the provided entries, as if it had been read from the
+    /// provided path. The path is only used to describe the entries in messages and errors.
+    pub fn new(path: impl Into<PathBuf>, dataset: Vec<TestDataEntry>) -> Self {
+        TestDataFile {
+            path: path.into(),
+            include: vec![],
+            schemas: BTreeMap::new(),
+            schema_file: None,
+            dataset,
+            users: vec![],
+            roles: vec![],
+        }
+    }
+
+    /// read reads the data file at the provided path, along with every file it includes. Returns
+    /// None for files that are not data files.
+    pub fn read(path: impl Into<PathBuf>) -> Result<Option<Self>> {
+        read_data_file(path.into(), false)
+    }
+}
+
+impl TestDataEntry {
+    /// collection returns an entry for the provided collection in the provided database.
+    pub fn collection(db: impl Into<String>, collection: CollectionData) -> Self {
+        Self::new(db.into(), Some(collection), None)
+    }
+
+    /// view returns an entry for the provided view in the provided database.
+    pub fn view(db: impl Into<String>, view: ViewData) -> Self {
+        Self::new(db.into(), None, Some(view))
+    }
+
+    fn new(db: String, collection: Option<CollectionData>, view: Option<ViewData>) -> Self {
+        TestDataEntry {
+            db,
+            collection,
+            view,
+            schema: None,
+            tpch: None,
+            location: String::new(),
+        }
+    }
+
+    /// with_schema returns the entry with the provided schema.
+    pub fn with_schema(self, schema: impl Into<Bson>) -> Self {
+        TestDataEntry {
+            schema: Some(schema.into()),
+            ..self
+        }
+    }
+
+    /// name returns the name of the collection or view this entry describes.
+    fn name(&self) -> &str {
+        match (&self.collection, &self.view) {
+            (Some(c), None) => c.name.as_str(),
+            (None, Some(v)) => v.name.as_str(),
+            _ => unreachable!(
+                "Invariant failed: Each entry must specify exactly one of 'view' or 'collection'."
+            ),
+        }
+    }
+
+    /// namespace returns the "<db>.<name>" namespace this entry describes.
+    fn namespace(&self) -> String {
+        format!("{}.{}", self.db, self.name())
+    }
+
+    /// location returns where this entry was defined, as recorded when its data file was read. For
+    /// entries that were not read from a data file, the provided file and index are used instead.
+    fn location(&self, file: &Path, index: usize) -> String {
+        if self.location.is_empty() {
+            format!("{}, entry {index}", file.display())
+        } else {
+            self.location.clone()
+        }
+    }
+
+    /// context returns the context load errors for this entry are reported with: where the entry
+    /// was defined, and the namespace it describes.
+    fn context(&self, file: &Path, index: usize) -> String {
+        format!("{} ({})", self.location(file, index), self.namespace())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CollectionData {
+    /// name specifies the name of the collection. Required.
+    pub name: String,
+
+    /// docs specifies the documents to insert into the collection. Optional if docs_file is
+    /// specified.
+    ///
+    /// The documents can be specified in extended JSON format. In YAML files, values can also be
+    /// written with tags such as !oid, !date, !decimal, !int32, !int64, !binary, and !regex. See
+    /// tags::deserialize_docs for the full list.
+    #[serde(default, deserialize_with = "tags::deserialize_docs")]
+    pub docs: Vec<Bson>,
+
+    /// docs_file specifies a file of additional documents to insert into the collection, after
+    /// the inline docs. Optional.
+    ///
+    /// The path is resolved relative to the data file. JSON Lines ('.jsonl' or '.ndjson'),
+    /// Extended JSON arrays ('.json'), and mongodump BSON files ('.bson') are supported. JSON Lines
+    /// and BSON files are streamed into the collection, so they can be arbitrarily large.
+    ///
+    /// Example:
+    ///   docs_file: data/calcs.jsonl
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs_file: Option<PathBuf>,
+
+    /// csv specifies how the rows of a '.csv' docs_file are converted into documents. Optional.
+    ///
+    /// Without it, every CSV value is loaded as a string, and empty values as null. See
+    /// CsvOptions for more details.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub csv: Option<CsvOptions>,
+
+    /// generate specifies synthetic documents to insert into the collection, after the inline
+    /// docs and the docs_file. Optional.
+    ///
+    /// The documents are generated deterministically from a seed, so a small block can describe a
+    /// large, reproducible collection. See GenerateOptions for more details.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generate: Option<GenerateOptions>,
+
+    /// generate_from_schema specifies documents to generate from the entry's schema, after all
+    /// other documents. Optional; requires a schema.
+    ///
+    /// With coverage: all_branches, the documents exercise every branch of the schema, so they
+    /// are polymorphic but always conform to it. See SchemaGenerateOptions for more details.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generate_from_schema: Option<SchemaGenerateOptions>,
+
+    /// tpch specifies a table of the built-in TPC-H-like dataset to insert into the collection,
+    /// after all other documents. Optional.
+    ///
+    /// This is usually set by expanding an entry-level tpch field, rather than written directly.
+    /// See TpchTableOptions for more details.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tpch: Option<TpchTableOptions>,
+
+    /// strict_extended_json records whether the documents of the docs file are checked for
+    /// malformed Extended JSON. Not part of the file format; set when the data file is read.
+    #[serde(skip)]
+    pub(crate) strict_extended_json: bool,
+
+    /// options specifies the options the collection is created with. Optional.
+    ///
+    /// These must be specified following the createCollection command's options, e.g. capped,
+    /// size, max, timeseries, clusteredIndex, expireAfterSeconds, collation, validator,
+    /// validationLevel, and validationAction. Without options, the collection is created
+    /// implicitly by inserting its documents. viewOn and pipeline are not allowed; use a view
+    /// entry instead.
+    ///
+    /// Example:
+    ///   options:
+    ///     timeseries: { timeField: ts, metaField: meta, granularity: hours }
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<CreateCollectionOptions>,
+
+    /// schema_validator specifies that the entry's schema is also installed as the collection's
+    /// $jsonSchema validator, so the mongod rejects documents that contradict it. Optional;
+    /// requires a schema.
+    ///
+    /// Example:
+    ///   schema_validator: { validation_level: strict, validation_action: error }
+    ///
+    /// See SchemaValidator for more details.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_validator: Option<SchemaValidator>,
+
+    /// indexes specifies the indexes for this test entry. Optional.
+    ///
+    /// These must be specified following the Rust driver's IndexModel format:
+    ///   { key: <key document>, options: <options document> }
+    ///
+    /// Example:
+    ///   indexes:
+    ///     - { key: {b: 1, a: -1}}
+    ///
+    /// See the docs for more details on possible options.
+    pub indexes: Option<Vec<IndexModel>>,
+}
+
+impl CollectionData {
+    /// new returns a collection with the provided name and documents, and no indexes.
+    pub fn new(name: impl Into<String>, docs: Vec<Bson>) -> Self {
+        CollectionData {
+            name: name.into(),
+            docs,
+            docs_file: None,
+            csv: None,
+            generate: None,
+            generate_from_schema: None,
+            tpch: None,
+            strict_extended_json: false,
+            options: None,
+            schema_validator: None,
+            indexes: None,
+        }
+    }
+
+    /// documents returns the collection's inline documents, followed by the documents in its docs
+    /// file if it has one, followed by its generated documents if it has any, followed by its TPC-H
+    /// table if it has one. The schema is the entry's schema, which documents are generated from
+    /// if generate_from_schema is specified.
+    fn documents(
+        &self,
+        schema: Option<&Bson>,
+    ) -> Result<impl Iterator<Item = Result<Bson>> + Send + '_> {
+        let file_docs = match &self.docs_file {
+            Some(path) => Some(source::read_documents(
+                path,
+                self.csv.as_ref(),
+                self.strict_extended_json,
+            )?),
+            None => None,
+        };
+        let generated_docs = match &self.generate {
+            Some(options) => Some(options.documents()?),
+            None => None,
+        };
+        let schema_docs = match &self.generate_from_schema {
+            Some(options) => Some(options.documents(schema)?),
+            None => None,
+        };
+        let tpch_docs = match &self.tpch {
+            Some(options) => Some(options.documents()?),
+            None => None,
+        };
+        Ok(self
+            .docs
+            .iter()
+            .cloned()
+            .map(Ok)
+            .chain(file_docs.into_iter().flatten())
+            .chain(generated_docs.into_iter().flatten().map(Ok))
+            .chain(
+                schema_docs
+                    .into_iter()
+                    .flatten()
+                    .map(|doc| Ok(Bson::Document(doc))),
+            )
+            .chain(tpch_docs.into_iter().flatten().map(Ok)))
+    }
+
+    /// batches returns the collection's documents, as returned by documents, split into the
+    /// batches they are inserted in. Every batch has batch_size documents, except the last, which
+    /// may have fewer. The batches stop at the first document that cannot be read.
+    fn batches(
+        &self,
+        schema: Option<&Bson>,
+        batch_size: usize,
+    ) -> Result<impl Iterator<Item = Result<Vec<Bson>>> + Send + '_> {
+        let mut docs = self.documents(schema)?;
+        let mut failed = false;
+        Ok(std::iter::from_fn(move || {
+            if failed {
+                return None;
+            }
+            match docs.by_ref().take(batch_size).collect::<Result<Vec<_>>>() {
+                Ok(batch) if batch.is_empty() => None,
+                Ok(batch) => Some(Ok(batch)),
+                Err(e) => {
+                    failed = true;
+                    Some(Err(e))
+                }
+            }
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ViewData {
+    /// The name of the view. Required.
+    pub name: String,
+
+    #[serde(flatten)]
+    pub definition: Option<ViewDefinition>,
+}
+
+impl ViewData {
+    /// new returns a view with the provided name, defined by the pipeline on view_on.
+    pub fn new(
+        name: impl Into<String>,
+        view_on: impl Into<String>,
+        pipeline: Vec<Document>,
+    ) -> Self {
+        ViewData {
+            name: name.into(),
+            definition: Some(ViewDefinition {
+                view_on: view_on.into(),
+                pipeline,
+            }),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ViewDefinition {
+    /// The collection on which to create the view. Optional.
+    ///
+    /// When run against ADF, this field is ignored unless views are installed with adf_views.
+    pub view_on: String,
+
+    /// The pipeline definition of the view. Optional.
+    ///
+    /// When run against ADF, this field is ignored unless views are installed with adf_views.
+    pub pipeline: Vec<Document>,
+}
+
+pub type Result<T> = std::result::Result<T, DataLoaderError>;
+
+#[derive(Error, Debug)]
+pub enum DataLoaderError {
+    #[error(transparent)]
+    FileSystem(#[from] io::Error),
+    #[error(transparent)]
+    Mongo(#[from] mongodb::error::Error),
+    #[error(transparent)]
+    SerdeJson(#[from] serde_json::Error),
+    #[error(transparent)]
+    SerdeYaml(#[from] serde_yaml::Error),
+    #[error(transparent)]
+    Task(#[from] JoinError),
+    #[error(transparent)]
+    BsonSerialization(#[from] mongodb::bson::ser::Error),
+    #[error(transparent)]
+    BsonDeserialization(#[from] mongodb::bson::de::Error),
+    #[error(
+        "Each entry must specify exactly one of 'view', 'collection', or 'tpch', but {0} does not"
+    )]
+    InvalidViewOrCollectionDataEntry(String),
+    #[error(
+        "No test data directory provided; use -d or --testDataDirectory, a profile's test_data_directories, or --tpch-scale-factor"
+    )]
+    MissingTestDataDirectory,
+    #[error("Validation found {} error(s):\n\t{}", .0.len(), .0.join("\n\t"))]
+    InvalidTestData(Vec<String>),
+    #[error("Namespace {0} does not exist")]
+    NamespaceNotFound(String),
+    #[error("{0} namespace(s) differ from the data files")]
+    Drift(usize),
+    #[error("Failed to read docs file {0}")]
+    InvalidDocsFile(String),
+    #[error("Failed to parse data file {0}")]
+    InvalidDataFile(String),
+    #[error("Invalid generate block: {}", .0.join("; "))]
+    InvalidGenerator(Vec<String>),
+    #[error("Invalid connection settings: {0}")]
+    InvalidConfig(String),
+    #[error("Failed to roll back {} change(s):\n\t{}", .0.len(), .0.join("\n\t"))]
+    Rollback(Vec<String>),
+    #[error("Failed to resolve data file references: {}", .0.join("; "))]
+    UnresolvedReference(Vec<String>),
+    #[error(
+        "options.validator, validationLevel, and validationAction cannot be combined with a schema validator"
+    )]
+    ConflictingValidator,
+    #[error(
+        "{} user(s) and role(s) already exist and were not created by the data loader, so they could not be restored if the load failed; use --force to replace them: {}", .0.len(), .0.join(", ")
+    )]
+    UnownedPrincipals(Vec<String>),
+    #[error("{context}: {source}")]
+    Context {
+        context: String,
+        source: Box<DataLoaderError>,
+    },
+}
+
+impl DataLoaderError {
+    /// context wraps the error with a description of what failed, such as the file, entry, and
+    /// namespace being processed.
+    fn context(self, context: impl Into<String>) -> Self {
+        DataLoaderError::Context {
+            context: context.into(),
+            source: Box::new(self),
+        }
+    }
+}
+
+/// FileFilter decides which files found while searching test data directories are read.
+#[derive(Debug, Default)]
+pub(crate) struct FileFilter {
+    pub(crate) include: Vec<Pattern>,
+    pub(crate) exclude: Vec<Pattern>,
+}
+
+impl FileFilter {
+    /// matches returns true if the path, relative to the directory being searched, should be read.
+    fn matches(&self, relative_path: &Path) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| p.matches_path(relative_path)))
+            && !self.exclude.iter().any(|p| p.matches_path(relative_path))
+    }
+}
+
+/// read_and_check reads the test data files at the provided paths, and runs check on them. By
+/// default, the first error is returned as soon as it is found. With all_errors, every file is read
+/// even if others fail, check runs on every file that could be read, and all errors are returned
+/// together as an InvalidTestData error, so a single run reports every mistake in the data files.
+/// See parse_data_file for strict_extended_json.
+fn read_and_check(
+    paths: &[String],
+    filter: &FileFilter,
+    strict_extended_json: bool,
+    extra: impl IntoIterator<Item = TestDataFile>,
+    all_errors: bool,
+    check: impl FnOnce(&[TestDataFile]) -> Result<()>,
+) -> Result<Vec<TestDataFile>> {
+    if !all_errors {
+        let test_data_files = read_data_files(paths, filter, strict_extended_json, extra)?;
+        check(&test_data_files)?;
+        return Ok(test_data_files);
+    }
+
+    let (test_data_files, read_errors) =
+        read_all_data_files(paths, filter, strict_extended_json, extra);
+    let mut errors = read_errors
+        .into_iter()
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>();
+    match check(&test_data_files) {
+        Ok(()) => {}
+        Err(DataLoaderError::InvalidTestData(check_errors)) => errors.extend(check_errors),
+        Err(e) => errors.push(e.to_string()),
+    }
+    if errors.is_empty() {
+        Ok(test_data_files)
+    } else {
+        Err(DataLoaderError::InvalidTestData(errors))
+    }
+}
+
+/// read_data_files reads every test data file found at the provided paths, and returns the first
+/// error encountered, if any. See read_all_data_files for how the files are found.
+fn read_data_files(
+    paths: &[String],
+    filter: &FileFilter,
+    strict_extended_json: bool,
+    extra: impl IntoIterator<Item = TestDataFile>,
+) -> Result<Vec<TestDataFile>> {
+    let (test_data_files, errors) = read_all_data_files(paths, filter, strict_extended_json, extra);
+    match errors.into_iter().next() {
+        Some(e) => Err(e),
+        None => Ok(test_data_files),
+    }
+}
+
+/// read_all_data_files reads every test data file found at the provided paths. Each path may be a
+/// directory, which is searched recursively for files matching the filter, or a file, which is
+/// read regardless of the filter. A file reachable from multiple paths is only read once, and a
+/// file included by another file that is read, or used as its schema_file, is not read on its own.
+/// The extra file, if provided, is appended to the files read, as if it had been read last.
+///
+/// A path or file that cannot be read does not stop the others from being read. The files that
+/// were read are returned along with an error for every one that was not, in the order they were
+/// found, and every error names the path it is about.
+fn read_all_data_files(
+    paths: &[String],
+    filter: &FileFilter,
+    strict_extended_json: bool,
+    extra: impl IntoIterator<Item = TestDataFile>,
+) -> (Vec<TestDataFile>, Vec<DataLoaderError>) {
+    let mut errors = vec![];
+    let mut file_paths = vec![];
+    for path in paths.iter().map(PathBuf::from) {
+        if path.is_dir() {
+            if let Err(e) = find_files(&path, &path, filter, &mut file_paths) {
+                errors.push(e.context(path.display().to_string()));
+            }
+        } else {
+            file_paths.push(path);
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut results = vec![];
+    for path in file_paths {
+        let file = path.display().to_string();
+        let canonical = match path.canonicalize() {
+            Ok(canonical) => canonical,
+            Err(e) => {
+                errors.push(DataLoaderError::from(e).context(file));
+                continue;
+            }
+        };
+        if !seen.insert(canonical.clone()) {
+            continue;
+        }
+        let result = match read_data_file(path, strict_extended_json) {
+            Ok(Some(test_data_file)) => Ok(test_data_file),
+            Ok(None) => continue,
+            // Other errors already name the file they are about.
+            Err(e @ DataLoaderError::FileSystem(_)) => Err(e.context(file.clone())),
+            Err(e) => Err(e),
+        };
+        results.push((canonical, file, result));
+    }
+
+    // Included files and schema files may not be data files of their own, so they are skipped
+    // whether or not they could be read.
+    let included = results
+        .iter()
+        .filter_map(|(_, _, result)| result.as_ref().ok())
+        .flat_map(|tdf| tdf.include.iter().cloned())
+        .collect::<HashSet<_>>();
+    let mut test_data_files = vec![];
+    for (canonical, file, result) in results {
+        if included.contains(&canonical) {
+            println!("\tSkipping file included by another data file: {file:?}");
+            continue;
+        }
+        match result {
+            Ok(test_data_file) => test_data_files.push(test_data_file),
+            Err(e) => errors.push(e),
+        }
+    }
+    test_data_files.extend(extra);
+
+    (test_data_files, errors)
+}
+
+/// find_files recursively collects the paths of all files under dir_path that match the filter.
+/// Entries are visited in sorted order so that files are always read in the same order.
+fn find_files(
+    root: &Path,
+    dir_path: &Path,
+    filter: &FileFilter,
+    file_paths: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let mut entries = fs::read_dir(dir_path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            find_files(root, &path, filter, file_paths)?;
+        } else if filter.matches(path.strip_prefix(root).unwrap_or(&path)) {
+            file_paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// read_data_file reads the file at the provided path, along with every file it includes, and
+/// resolves the schema references of its entries. Returns None for files that are not data files.
+fn read_data_file(path: PathBuf, strict_extended_json: bool) -> Result<Option<TestDataFile>> {
+    println!("\tReading file {path:?}");
+
+    let Some(mut test_data_file) = parse_data_file(path, strict_extended_json)? else {
+        return Ok(None);
+    };
+    include::resolve(&mut test_data_file, strict_extended_json)?;
+    Ok(Some(test_data_file))
+}
+
+/// parse_data_file parses the file at the provided path. Returns None for files without a
+/// '.y[a]ml' or '.json' extension, and for '.json' files that contain an array, which are docs
+/// files rather than data files. Entries with a tpch field are expanded into an entry per TPC-H
+/// table, and the docs_file, include, and schema_file paths of the file are resolved relative to
+/// it. If strict_extended_json is true, the docs of the file are checked for malformed Extended
+/// JSON, and so are the documents of its docs files once they are read.
+fn parse_data_file(path: PathBuf, strict_extended_json: bool) -> Result<Option<TestDataFile>> {
+    let ext = path.extension().and_then(|ext| ext.to_str());
+    // Only parse paths to '.y[a]ml' or '.json' files
+    let contents = match ext {
+        Some("yml" | "yaml" | "json") => fs::read_to_string(&path)?,
+        _ => {
+            println!("\tIgnoring file without '.y[a]ml' or '.json' extension: {path:?}");
+            return Ok(None);
+        }
+    };
+    if ext == Some("json") && contents.trim_start().starts_with('[') {
+        println!("\tIgnoring docs file found among data files: {path:?}");
+        return Ok(None);
+    }
+    // The docs are checked first, so that a malformed wrapper is reported as such rather than as
+    // whatever error converting it produces.
+    if strict_extended_json {
+        parse_contents::<StrictDocsFile>(&path, &contents)?;
+    }
+    let mut test_data_file: TestDataFile = parse_contents(&path, &contents)?;
+
+    for (index, entry) in test_data_file.dataset.iter_mut().enumerate() {
+        entry.location = format!("{}, entry {index}", path.display());
+    }
+    let sources = |entry: &TestDataEntry| {
+        [
+            entry.collection.is_some(),
+            entry.view.is_some(),
+            entry.tpch.is_some(),
+        ]
+        .into_iter()
+        .filter(|specified| *specified)
+        .count()
+    };
+    if let Some(entry) = test_data_file
+        .dataset
+        .iter()
+        .find(|entry| sources(entry) != 1)
+    {
+        return Err(DataLoaderError::InvalidViewOrCollectionDataEntry(
+            entry.location.clone(),
+        ));
+    }
+
+    test_data_file.dataset = test_data_file
+        .dataset
+        .into_iter()
+        .flat_map(|entry| match &entry.tpch {
+            Some(options) => tpch::entries(&entry.db, options)
+                .into_iter()
+                .map(|table| TestDataEntry {
+                    location: entry.location.clone(),
+                    ..table
+                })
+                .collect(),
+            None => vec![entry],
+        })
+        .collect();
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    for c in test_data_file
+        .dataset
+        .iter_mut()
+        .filter_map(|entry| entry.collection.as_mut())
+    {
+        c.strict_extended_json = strict_extended_json;
+        if let Some(docs_file) = c.docs_file.as_mut() {
+            *docs_file = dir.join(&*docs_file);
+        }
+    }
+    for include in test_data_file.include.iter_mut() {
+        *include = dir.join(&*include);
+    }
+    if let Some(schema_file) = test_data_file.schema_file.as_mut() {
+        *schema_file = dir.join(&*schema_file);
+    }
+
+    test_data_file.path = path;
+    Ok(Some(test_data_file))
+}
+
+/// parse_contents parses the contents of the '.y[a]ml' or '.json' file at the provided path. Errors
+/// name the path, along with the line and column of the problem if they are known.
+fn parse_contents<T: DeserializeOwned>(path: &Path, contents: &str) -> Result<T> {
+    if path.extension().and_then(|ext| ext.to_str()) == Some("json") {
+        return serde_json::from_str(contents).map_err(|e| {
+            DataLoaderError::InvalidDataFile(format!(
+                "{}, line {}, column {}: {}",
+                path.display(),
+                e.line(),
+                e.column(),
+                source::without_location(&e)
+            ))
+        });
+    }
+    serde_yaml::from_str(contents).map_err(|e| {
+        let message = e.to_string();
+        DataLoaderError::InvalidDataFile(match e.location() {
+            Some(l) => format!(
+                "{}, line {}, column {}: {}",
+                path.display(),
+                l.line(),
+                l.column(),
+                message
+                    .strip_suffix(&format!(" at line {} column {}", l.line(), l.column()))
+                    .unwrap_or(&message)
+            ),
+            None => format!("{}: {message}", path.display()),
+        })
+    })
+}
+
+/// StrictDocsFile is the part of a data file that strict Extended JSON checking applies to: the
+/// docs of its collections. A data file is parsed into it, before being parsed into a
+/// TestDataFile, only to check its docs.
+#[derive(Deserialize)]
+struct StrictDocsFile {
+    #[serde(default, rename = "dataset")]
+    _dataset: Vec<StrictDocsEntry>,
+}
+
+#[derive(Deserialize)]
+struct StrictDocsEntry {
+    #[serde(rename = "collection")]
+    _collection: Option<StrictDocsCollection>,
+}
+
+#[derive(Deserialize)]
+struct StrictDocsCollection {
+    #[serde(
+        default,
+        rename = "docs",
+        deserialize_with = "tags::deserialize_strict_docs"
+    )]
+    _docs: Vec<Bson>,
+}
+
+/// drop_collections removes every namespace in the test data files, along with its schema
+/// document, so it can be reloaded. Existing collections are renamed to a backup name rather than
+/// dropped, except for time-series collections, which cannot be renamed, so their documents are
+/// copied to the backup instead. Existing views and schema documents are recorded in the journal,
+/// so that, like the backups, they can be restored if the run fails.
+async fn drop_collections(
+    client: Client,
+    test_data_files: Vec<TestDataFile>,
+    journal: &Journal,
+    hooks: &Hooks,
+) -> Result<()> {
+    for tdf in test_data_files {
+        for (index, entry) in tdf.dataset.into_iter().enumerate() {
+            let context = entry.context(&tdf.path, index);
+            let res: Result<()> = async {
+                let db = client.database(entry.db.as_str());
+                let name = entry.name();
+
+                let mut specs = db.list_collections().filter(doc! {"name": name}).await?;
+                if specs.advance().await? {
+                    let spec = specs.deserialize_current()?;
+                    match (spec.options.view_on.clone(), spec.options.pipeline.clone()) {
+                        // Time-series collections cannot be renamed, so their documents are
+                        // copied to the backup, and the collection is created again from its
+                        // options if the run fails.
+                        (None, _) if spec.collection_type == CollectionType::Timeseries => {
+                            let backup = format!("{BACKUP_PREFIX}{name}");
+                            db.collection::<Bson>(&backup).drop().await?;
+                            db.collection::<Bson>(name)
+                                .aggregate(vec![doc! {"$out": &backup}])
+                                .await?;
+                            let options = dump::creation_options(spec.options)?;
+                            journal.record(Operation::BackedUpTimeseries {
+                                db: entry.db.clone(),
+                                name: name.to_string(),
+                                options: bson::to_document(&options.unwrap_or_default())?,
+                            });
+                            db.collection::<Bson>(name).drop().await?;
+                        }
+                        (Some(view_on), pipeline) => {
+                            journal.record(Operation::DroppedView {
+                                db: entry.db.clone(),
+                                name: name.to_string(),
+                                view_on,
+                                pipeline: pipeline.unwrap_or_default(),
+                            });
+                            db.collection::<Bson>(name).drop().await?;
+                        }
+                        (None, _) => {
+                            client
+                                .database("admin")
+                                .run_command(doc! {
+                                    "renameCollection": entry.namespace(),
+                                    "to": format!("{}.{BACKUP_PREFIX}{name}", entry.db),
+                                    "dropTarget": true,
+                                })
+                                .await?;
+                            journal.record(Operation::BackedUpCollection {
+                                db: entry.db.clone(),
+                                name: name.to_string(),
+                            });
+                        }
+                    }
+                    println!("\tDropped {}", entry.namespace());
+                }
+
+                // We should also remove the schema for this namespace. Only this
+                // entry's schema document is deleted, so schemas of namespaces
+                // that are not being reloaded are preserved.
+                delete_schema(&db, &entry, journal).await
+            }
+            .await;
+            res.map_err(|e| e.context(context))?;
+            hooks.fire(Event::Dropped(&entry));
+        }
+    }
+
+    Ok(())
+}
+
+/// delete_schema deletes the entry's schema document from __sql_schemas, if there is one, and
+/// records it in the journal.
+async fn delete_schema(db: &Database, entry: &TestDataEntry, journal: &Journal) -> Result<()> {
+    let previous = db
+        .collection::<Document>(SCHEMA_COLLECTION)
+        .find_one_and_delete(doc! {"_id": entry.name()})
+        .await?;
+    if let Some(document) = previous {
+        journal.record(Operation::DeletedDocument {
+            db: entry.db.clone(),
+            collection: SCHEMA_COLLECTION.to_string(),
+            document,
+        });
+    }
+    Ok(())
+}
+
+/// LoadOptions controls how much work load_test_data sends to mongod at once.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LoadOptions {
+    pub(crate) concurrency: usize,
+    pub(crate) batch_size: usize,
+}
+
+/// load_test_data loads every entry concurrently. Every write operation must acquire a permit from
+/// a shared semaphore, so at most `concurrency` operations are in flight at any time. If any
+/// operation fails, the remaining operations are still awaited, so that the journal is complete
+/// before any rollback, and the first error is returned, naming the entry that failed.
+async fn load_test_data(
+    client: Client,
+    test_data_files: Vec<TestDataFile>,
+    options: &LoadOptions,
+    journal: &Journal,
+    hooks: &Hooks,
+) -> Result<()> {
+    let permits = Arc::new(Semaphore::new(options.concurrency));
+    let mut tasks = JoinSet::new();
+    for tdf in test_data_files {
+        for (index, entry) in tdf.dataset.into_iter().enumerate() {
+            let context = entry.context(&tdf.path, index);
+            let (client, permits, journal, hooks) = (
+                client.clone(),
+                permits.clone(),
+                journal.clone(),
+                hooks.clone(),
+            );
+            let batch_size = options.batch_size;
+            tasks.spawn(async move {
+                load_entry(client, &entry, permits, batch_size, journal)
+                    .await
+                    .map_err(|e| e.context(context))?;
+                hooks.fire(Event::Loaded(&entry));
+                Ok(())
+            });
+        }
+    }
+
+    let mut first_err = None;
+    while let Some(res) = tasks.join_next().await {
+        if let Err(e) = res.map_err(DataLoaderError::from).and_then(|r| r) {
+            first_err.get_or_insert(e);
+        }
+    }
+
+    first_err.map_or(Ok(()), Err)
+}
+
+/// load_entry writes a single entry's documents and indexes, or creates its view. Document batches
+/// for a collection are inserted concurrently, and its indexes are built once all batches are
+/// inserted. This task does not hold a permit itself while it waits on its batches.
+async fn load_entry(
+    client: Client,
+    entry: &TestDataEntry,
+    permits: Arc<Semaphore>,
+    batch_size: usize,
+    journal: Journal,
+) -> Result<()> {
+    let db = client.database(entry.db.as_str());
+
+    // Every write below may create the namespace, so it is recorded first.
+    journal.record(Operation::CreatedNamespace {
+        db: entry.db.clone(),
+        name: entry.name().to_string(),
+    });
+
+    // If the entry specifies a collection, insert the documents.
+    if let Some(c) = &entry.collection {
+        let collection = db.collection::<Bson>(c.name.as_str());
+
+        // Collections with options, including a schema validator, must be created explicitly,
+        // before any document is inserted.
+        if let Some(options) = validator::creation_options(c, entry.schema.as_ref())? {
+            let _permit = permits.acquire().await.unwrap();
+            println!("\tCreating collection {}.{} with options", entry.db, c.name);
+            db.create_collection(c.name.as_str())
+                .with_options(options)
+                .await?;
+        }
+
+        // A batch is only read once a permit is available to insert it, so at most
+        // `concurrency` batches are held in memory at once, however large the docs file.
+        let mut batches = JoinSet::new();
+        let mut first_err = None;
+        let mut docs = c.batches(entry.schema.as_ref(), batch_size)?;
+        loop {
+            let permit = permits.clone().acquire_owned().await.unwrap();
+            let batch = match docs.next() {
+                None => break,
+                Some(Ok(batch)) => batch,
+                Some(Err(e)) => {
+                    first_err = Some(e);
+                    break;
+                }
+            };
+            if batches.is_empty() {
+                println!(
+                    "\tAttempting to insert documents into {}.{} in batches of at most {batch_size}",
+                    entry.db, c.name
+                );
+            }
+            let collection = collection.clone();
+            batches.spawn(async move {
+                let _permit = permit;
+                collection.insert_many(batch).await
+            });
+        }
+        drop(docs);
+
+        let mut inserted = 0;
+        while let Some(res) = batches.join_next().await {
+            match res
+                .map_err(DataLoaderError::from)
+                .and_then(|r| r.map_err(DataLoaderError::from))
+            {
+                Ok(res) => inserted += res.inserted_ids.len(),
+                Err(e) => {
+                    first_err.get_or_insert(e);
+                }
+            }
+        }
+        if let Some(e) = first_err {
+            return Err(e);
+        }
+        if inserted == 0 {
+            println!(
+                "No documents specified for {}.{}, not inserting anything",
+                entry.db, c.name
+            );
+        } else {
+            println!(
+                "\tInserted {inserted} documents into {}.{}",
+                entry.db, c.name
+            );
+        }
+
+        // Also write indexes for this collection if any are specified.
+        if let Some(indexes) = &c.indexes {
+            let _permit = permits.acquire().await.unwrap();
+            println!("\tAttempting to create indexes for {}.{}", entry.db, c.name);
+            let res = collection.create_indexes(indexes.clone()).await?;
+            journal.record(Operation::CreatedIndexes {
+                db: entry.db.clone(),
+                name: c.name.clone(),
+                index_names: res.index_names.clone(),
+            });
+            println!(
+                "\tCreated indexes {:?} for {}.{}",
+                res.index_names, entry.db, c.name
+            );
+        }
+    } else if let Some(v) = &entry.view {
+        if let Some(d) = &v.definition {
+            // If this data entry describes a view and a definition is
+            // provided, then create the view. mongod allows a view to be
+            // created before the namespace it is defined on, so views do
+            // not need to wait for other entries.
+            let _permit = permits.acquire().await.unwrap();
+            println!(
+                "\tAttempting to create view {} on {}.{}",
+                v.name, entry.db, d.view_on,
+            );
+            db.create_collection(v.name.clone())
+                .view_on(d.view_on.clone())
+                .pipeline(d.pipeline.clone())
+                .await?;
+            println!(
+                "\tSuccessfully created view {} on {}.{}",
+                v.name, entry.db, d.view_on,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn set_schemas_in_adf(
+    client: Client,
+    test_data_files: Vec<TestDataFile>,
+    hooks: &Hooks,
+) -> Result<()> {
+    for tdf in test_data_files {
+        for (index, entry) in tdf.dataset.into_iter().enumerate() {
+            let context = entry.context(&tdf.path, index);
+            let res: Result<()> = async {
+                // Determine the name of the test data entry collection or view.
+                let datasource_name = match (&entry.collection, &entry.view) {
+                    (Some(c), None) => c.name.clone(),
+                    (None, Some(v)) => v.name.clone(),
+                    _ => unreachable!("Invariant failed: Each entry must specify exactly one of 'view' or 'collection'."),
+                };
+
+                let db: Database;
+                let command_doc: Document;
+                let command_name: &str;
+
+                match &entry.schema {
+                    Some(schema) => {
+                        // If schema is provided, write the schema using sqlSetSchema.
+                        db = client.database(entry.db.as_str());
+                        command_doc = doc! {"sqlSetSchema": datasource_name.clone(), "schema": {"jsonSchema": schema, "version": 1}};
+                        command_name = "sqlSetSchema";
+                    }
+                    _ => {
+                        // Otherwise, write the schema using sqlGenerateSchema. Note
+                        // this must be run against the admin db.
+                        db = client.database("admin");
+                        command_doc = doc! {"sqlGenerateSchema": 1, "setSchemas": true, "sampleNamespaces": vec![format!("{}.{}", entry.db, datasource_name.clone())]};
+                        command_name = "sqlGenerateSchema";
+                    }
+                }
+
+                let res = db.run_command(command_doc).await?;
+                println!(
+                    "\tSet schema for {}.{} via {}\n\t\tResult: {:?}",
+                    entry.db, datasource_name, command_name, res
+                );
+                Ok(())
+            }
+            .await;
+            res.map_err(|e| e.context(context))?;
+            hooks.fire(Event::SchemaSet(&entry));
+        }
+    }
+
+    Ok(())
+}
+
+async fn set_schemas_in_mongod(
+    client: Client,
+    test_data_files: Vec<TestDataFile>,
+    journal: &Journal,
+    hooks: &Hooks,
+) -> Result<()> {
+    for tdf in test_data_files {
+        for (index, entry) in tdf.dataset.into_iter().enumerate() {
+            let context = entry.context(&tdf.path, index);
+            let res: Result<()> = async {
+                let db = client.database(entry.db.as_str());
+
+                // Use the specified schema if there is one. Otherwise, infer
+                // it from the collection's documents or the view's results.
+                let (datasource_name, datasource_type, schema) =
+                    match (&entry.collection, &entry.view, &entry.schema) {
+                        (Some(c), None, Some(schema)) => (c.name.clone(), "collection", schema.clone()),
+                        (None, Some(v), Some(schema)) => (v.name.clone(), "view", schema.clone()),
+                        (Some(c), None, None) => {
+                            println!("\tInferring schema for {}.{}", entry.db, c.name);
+                            let mut read_err = None;
+                            let schema = schema::infer(
+                                c.documents(None)?
+                                    .map_while(|doc| doc.map_err(|e| read_err = Some(e)).ok()),
+                            );
+                            if let Some(e) = read_err {
+                                return Err(e);
+                            }
+                            (c.name.clone(), "collection", schema)
+                        }
+                        (None, Some(v), None) => {
+                            println!("\tInferring schema for {}.{}", entry.db, v.name);
+                            let mut cursor = db
+                                .collection::<Bson>(v.name.as_str())
+                                .find(doc! {})
+                                .await?;
+                            let mut docs = vec![];
+                            while cursor.advance().await? {
+                                docs.push(cursor.deserialize_current()?);
+                            }
+                            (v.name.clone(), "view", schema::infer(&docs))
+                        }
+                        _ => unreachable!("Invariant failed: Each entry must specify exactly one of 'view' or 'collection'."),
+                    };
+
+                let schema_collection = db.collection::<Document>(SCHEMA_COLLECTION);
+
+                let schema_doc = doc! {
+                    "_id": datasource_name.clone(),
+                    "type": datasource_type,
+                    "schema": schema,
+                    "lastUpdated": datetime::DateTime::now(),
+                };
+
+                let res = schema_collection.insert_one(schema_doc).await?;
+                journal.record(Operation::InsertedDocument {
+                    db: entry.db.clone(),
+                    collection: SCHEMA_COLLECTION.to_string(),
+                    id: res.inserted_id.clone(),
+                });
+                println!(
+                    "\tSet schema for {}.{}\n\t\tResult: {:?}",
+                    entry.db, datasource_name, res
+                );
+                Ok(())
+            }
+            .await;
+            res.map_err(|e| e.context(context))?;
+            hooks.fire(Event::SchemaSet(&entry));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,398 @@
+use crate::{
+    adf_config, delete_schema, drop_collections, fingerprint, journal::Journal, load_test_data,
+    read_and_check, set_schemas_in_adf, set_schemas_in_mongod, users, validate, validator,
+    FileFilter, LoadOptions, Result, SchemaValidator, TestDataEntry, TestDataFile,
+};
+use glob::Pattern;
+use mongodb::Client;
+use std::{fmt, num::NonZeroUsize, path::PathBuf, sync::Arc};
+
+/// Mode decides where schemas are written. It is passed to DataLoader::load and
+/// DataLoader::set_schemas along with the mongod client.
+#[derive(Debug, Clone, Default)]
+pub enum Mode {
+    /// Documents, indexes, views, and schemas are written directly to the mongod.
+    #[default]
+    Mongod,
+
+    /// Documents and indexes are written to the mongod, and schemas are written to ADF with this
+    /// client. Views are assumed to be specified in the ADF config, unless the loader is built
+    /// with adf_views, in which case they are installed in ADF before schemas are written.
+    Adf(Client),
+}
+
+/// Step names a step of DataLoader::load, in the order they are performed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// The data files are compared with the fingerprints of previously loaded namespaces.
+    Comparing,
+    /// The namespaces of changed entries are dropped.
+    Dropping,
+    /// The documents, indexes, and views of changed entries are written to the mongod.
+    Loading,
+    /// The users and roles of the data files are created.
+    CreatingUsers,
+    /// Schemas are written to the mongod or ADF.
+    SettingSchemas,
+    /// The fingerprints of the loaded entries are recorded.
+    RecordingFingerprints,
+}
+
+/// Event describes progress made by a DataLoader. Hooks are called with a Step event as each step
+/// of a load begins, and with an event for every entry once the step the event names has finished
+/// for that entry.
+#[derive(Debug, Clone, Copy)]
+pub enum Event<'a> {
+    /// A step of a load is starting.
+    Step(Step),
+    /// The entry's namespace and schema were removed.
+    Dropped(&'a TestDataEntry),
+    /// The entry's documents, indexes, or view were written to the mongod.
+    Loaded(&'a TestDataEntry),
+    /// The entry's schema was written to the mongod or ADF.
+    SchemaSet(&'a TestDataEntry),
+}
+
+type Hook = dyn Fn(Event) + Send + Sync;
+
+/// Hooks are the functions called with every Event. Entries are loaded concurrently, so hooks may
+/// be called from multiple tasks.
+#[derive(Clone, Default)]
+pub(crate) struct Hooks(pub(crate) Vec<Arc<Hook>>);
+
+impl Hooks {
+    pub(crate) fn fire(&self, event: Event) {
+        for hook in &self.0 {
+            hook(event);
+        }
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Hooks({})", self.0.len())
+    }
+}
+
+/// DataLoaderBuilder configures a DataLoader. Every setting has the same default as the
+/// corresponding command-line flag of the data-loader binary.
+#[derive(Debug)]
+pub struct DataLoaderBuilder {
+    targets: Vec<String>,
+    data_files: Vec<TestDataFile>,
+    filter: FileFilter,
+    strict_extended_json: bool,
+    all_errors: bool,
+    skip_document_validation: bool,
+    force: bool,
+    schema_validator: Option<SchemaValidator>,
+    adf_views: Option<String>,
+    concurrency: NonZeroUsize,
+    batch_size: NonZeroUsize,
+    hooks: Hooks,
+}
+
+/// DataLoader loads the test data files it was built with into a mongod. Building it does not
+/// connect to any server; the client for the mongod is passed to each operation instead.
+#[derive(Debug)]
+pub struct DataLoader {
+    test_data_files: Vec<TestDataFile>,
+    adf_views: Option<String>,
+    force: bool,
+    options: LoadOptions,
+    hooks: Hooks,
+}
+
+impl DataLoader {
+    /// builder returns a builder for a DataLoader.
+    pub fn builder() -> DataLoaderBuilder {
+        DataLoaderBuilder {
+            targets: vec![],
+            data_files: vec![],
+            filter: FileFilter::default(),
+            strict_extended_json: false,
+            all_errors: false,
+            skip_document_validation: false,
+            force: false,
+            schema_validator: None,
+            adf_views: None,
+            concurrency: NonZeroUsize::new(8).unwrap(),
+            batch_size: NonZeroUsize::new(10000).unwrap(),
+            hooks: Hooks::default(),
+        }
+    }
+
+    /// test_data_files returns the test data files the loader was built with.
+    pub fn test_data_files(&self) -> &[TestDataFile] {
+        &self.test_data_files
+    }
+
+    /// load drops and reloads every entry whose fingerprint shows it changed since the last load,
+    /// or every entry if the loader was built with force, recreates the users and roles of the
+    /// data files, and then sets the entries' schemas where the mode decides. In ADF mode, the
+    /// schema of every entry is set, including unchanged ones, since ADF schemas are not tracked by
+    /// fingerprints. Unless the loader was built with force, the load fails before making any
+    /// change if a user or role of the data files exists but was not created by the data loader.
+    /// If any step fails, every change made by the load is rolled back.
+    pub async fn load(&self, client: &Client, mode: &Mode) -> Result<()> {
+        self.hooks.fire(Event::Step(Step::Comparing));
+        let schema_target = fingerprint::schema_target(mode, self.adf_views.as_deref());
+        let changed_files =
+            fingerprint::changed_entries(client, &self.test_data_files, &schema_target, self.force)
+                .await?;
+        users::check_users_and_roles(client, &self.test_data_files, self.force).await?;
+
+        // Every change made from here on is recorded in the journal. If any
+        // step fails, exactly those changes are rolled back.
+        let journal = Journal::default();
+        let res: Result<()> = async {
+            self.hooks.fire(Event::Step(Step::Dropping));
+            fingerprint::forget_fingerprints(client, &changed_files, &journal).await?;
+            drop_collections(client.clone(), changed_files.clone(), &journal, &self.hooks).await?;
+
+            self.hooks.fire(Event::Step(Step::Loading));
+            load_test_data(
+                client.clone(),
+                changed_files.clone(),
+                &self.options,
+                &journal,
+                &self.hooks,
+            )
+            .await?;
+
+            // Users and roles are not tracked by fingerprints, so they are
+            // recreated on every load.
+            self.hooks.fire(Event::Step(Step::CreatingUsers));
+            users::create_users_and_roles(client, &self.test_data_files, &journal).await?;
+
+            self.hooks.fire(Event::Step(Step::SettingSchemas));
+            match mode {
+                Mode::Adf(adf_client) => self.set_schemas_in_adf(adf_client).await,
+                Mode::Mongod => {
+                    set_schemas_in_mongod(
+                        client.clone(),
+                        changed_files.clone(),
+                        &journal,
+                        &self.hooks,
+                    )
+                    .await
+                }
+            }
+        }
+        .await;
+        self.finish(client, &journal, res).await?;
+
+        // Only record fingerprints once everything for the changed entries has
+        // been written, so a failed run is always retried in full.
+        self.hooks.fire(Event::Step(Step::RecordingFingerprints));
+        fingerprint::record_fingerprints(client, &changed_files, &schema_target).await
+    }
+
+    /// drop removes the namespace and schema of every entry, along with its fingerprint, so the
+    /// next load reloads it, and then drops the users and roles of the data files. If any entry
+    /// fails to drop, every entry dropped is restored.
+    pub async fn drop(&self, client: &Client) -> Result<()> {
+        let journal = Journal::default();
+        let res: Result<()> = async {
+            fingerprint::forget_fingerprints(client, &self.test_data_files, &journal).await?;
+            drop_collections(
+                client.clone(),
+                self.test_data_files.clone(),
+                &journal,
+                &self.hooks,
+            )
+            .await
+        }
+        .await;
+        self.finish(client, &journal, res).await?;
+        users::drop_users_and_roles(client, &self.test_data_files).await
+    }
+
+    /// set_schemas writes the schema of every entry where the mode decides, without reloading any
+    /// data. In mongod mode, the existing schema documents are replaced, and are restored if any
+    /// entry fails.
+    pub async fn set_schemas(&self, client: &Client, mode: &Mode) -> Result<()> {
+        if let Mode::Adf(adf_client) = mode {
+            return self.set_schemas_in_adf(adf_client).await;
+        }
+
+        let journal = Journal::default();
+        let res: Result<()> = async {
+            for entry in self.test_data_files.iter().flat_map(|tdf| &tdf.dataset) {
+                delete_schema(&client.database(&entry.db), entry, &journal).await?;
+            }
+            set_schemas_in_mongod(
+                client.clone(),
+                self.test_data_files.clone(),
+                &journal,
+                &self.hooks,
+            )
+            .await
+        }
+        .await;
+        self.finish(client, &journal, res).await
+    }
+
+    /// set_schemas_in_adf writes the schema of every entry to ADF, first installing the views of
+    /// the data files in ADF if the loader was built with adf_views, so that their schemas can be
+    /// set or generated.
+    async fn set_schemas_in_adf(&self, adf_client: &Client) -> Result<()> {
+        if let Some(store_name) = &self.adf_views {
+            adf_config::install_views(adf_client, &self.test_data_files, store_name).await?;
+        }
+        set_schemas_in_adf(
+            adf_client.clone(),
+            self.test_data_files.clone(),
+            &self.hooks,
+        )
+        .await
+    }
+
+    /// finish commits the journal if res is Ok, and rolls back every change it records otherwise.
+    async fn finish(&self, client: &Client, journal: &Journal, res: Result<()>) -> Result<()> {
+        if let Err(e) = res {
+            println!(
+                "Error encountered while loading data. Rolling back changes made by this run."
+            );
+            if let Err(rollback_err) = journal.rollback(client).await {
+                println!("\tFailed to roll back changes: {rollback_err}");
+            }
+            return Err(e);
+        }
+        journal.commit(client).await
+    }
+}
+
+impl DataLoaderBuilder {
+    /// target adds a directory to search recursively for data files, or an individual data file.
+    pub fn target(mut self, path: impl Into<PathBuf>) -> Self {
+        self.targets.push(path.into().display().to_string());
+        self
+    }
+
+    /// targets adds every provided path as a target.
+    pub fn targets(self, paths: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        paths.into_iter().fold(self, Self::target)
+    }
+
+    /// data_file adds a test data file that was built in memory rather than read from a target.
+    pub fn data_file(mut self, test_data_file: TestDataFile) -> Self {
+        self.data_files.push(test_data_file);
+        self
+    }
+
+    /// data_files adds every provided test data file.
+    pub fn data_files(self, test_data_files: impl IntoIterator<Item = TestDataFile>) -> Self {
+        test_data_files.into_iter().fold(self, Self::data_file)
+    }
+
+    /// include adds a glob pattern for files to read from target directories. Patterns are
+    /// matched against paths relative to the directory being searched. If no include patterns are
+    /// added, every file is read.
+    pub fn include(mut self, pattern: Pattern) -> Self {
+        self.filter.include.push(pattern);
+        self
+    }
+
+    /// exclude adds a glob pattern for files to skip when searching target directories. Exclude
+    /// patterns take precedence over include patterns.
+    pub fn exclude(mut self, pattern: Pattern) -> Self {
+        self.filter.exclude.push(pattern);
+        self
+    }
+
+    /// strict_extended_json sets whether malformed Extended JSON type wrappers are rejected.
+    pub fn strict_extended_json(mut self, strict: bool) -> Self {
+        self.strict_extended_json = strict;
+        self
+    }
+
+    /// all_errors sets whether every error in every data file is reported by build, rather than
+    /// only the first.
+    pub fn all_errors(mut self, all_errors: bool) -> Self {
+        self.all_errors = all_errors;
+        self
+    }
+
+    /// skip_document_validation sets whether build skips checking that documents conform to
+    /// their entry's schema. The other checks build runs are unaffected.
+    pub fn skip_document_validation(mut self, skip: bool) -> Self {
+        self.skip_document_validation = skip;
+        self
+    }
+
+    /// force sets whether load reloads every entry, even those whose fingerprint shows they are
+    /// unchanged since the last load, and replaces users and roles the data loader did not create.
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// schema_validator sets the validator installed for every collection entry that has a
+    /// schema, unless the entry specifies its own schema_validator, or a validator,
+    /// validationLevel, or validationAction in its options. Defaults to None, which only installs
+    /// the validators the entries specify.
+    pub fn schema_validator(mut self, schema_validator: Option<SchemaValidator>) -> Self {
+        self.schema_validator = schema_validator;
+        self
+    }
+
+    /// adf_views sets whether the views of the data files are installed in ADF before schemas
+    /// are written to it, in ADF mode. When Some, views are added to ADF's storage configuration,
+    /// and any database ADF does not have yet is added, backed by the named store. Defaults to
+    /// None, which assumes the views are already specified in the ADF config.
+    pub fn adf_views(mut self, store_name: Option<String>) -> Self {
+        self.adf_views = store_name;
+        self
+    }
+
+    /// concurrency sets the maximum number of write operations sent to mongod at the same time.
+    /// Defaults to 8.
+    pub fn concurrency(mut self, concurrency: NonZeroUsize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// batch_size sets the maximum number of documents sent to mongod in a single insert.
+    /// Defaults to 10000.
+    pub fn batch_size(mut self, batch_size: NonZeroUsize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// hook adds a function that is called with an Event as each step of a load begins, and as
+    /// each entry is dropped, loaded, and has its schema set.
+    pub fn hook(mut self, hook: impl Fn(Event) + Send + Sync + 'static) -> Self {
+        self.hooks.0.push(Arc::new(hook));
+        self
+    }
+
+    /// build reads the data files in the targets and validates them, along with the data files
+    /// added in memory, and returns a DataLoader for them. Every check of the validate subcommand
+    /// is run, except that views may be defined on namespaces loaded by other data files. No
+    /// server is contacted.
+    pub fn build(self) -> Result<DataLoader> {
+        let skip_document_validation = self.skip_document_validation;
+        let mut test_data_files = read_and_check(
+            &self.targets,
+            &self.filter,
+            self.strict_extended_json,
+            self.data_files,
+            self.all_errors,
+            |test_data_files| validate::validate_load(test_data_files, !skip_document_validation),
+        )?;
+        if let Some(schema_validator) = &self.schema_validator {
+            validator::apply_default(&mut test_data_files, schema_validator);
+        }
+        Ok(DataLoader {
+            test_data_files,
+            adf_views: self.adf_views,
+            force: self.force,
+            options: LoadOptions {
+                concurrency: self.concurrency.get(),
+                batch_size: self.batch_size.get(),
+            },
+            hooks: self.hooks,
+        })
+    }
+}
@@ -0,0 +1,337 @@
+use crate::{DataLoaderError, Result};
+use mongodb::{
+    options::{AuthMechanism, ClientOptions, Tls, TlsOptions},
+    Client,
+};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// The name of the config file read when no --config is provided.
+pub(crate) const DEFAULT_CONFIG_FILE: &str = "data-loader.toml";
+
+/// ConfigFile is the format of a data-loader.toml file. It holds named connection profiles, one
+/// of which is selected with --profile, or by default_profile if --profile is not provided:
+///
+///   default_profile = "local"
+///
+///   [profiles.local]
+///   test_data_directories = ["resources/data"]
+///   mongod = { uri = "mongodb://localhost:${MDB_PORT:-27017}" }
+///
+///   [profiles.ci]
+///   use_adf = true
+///   [profiles.ci.mongod]
+///   uri = "mongodb://${MDB_HOST}:27017"
+///   username = "${MDB_USER}"
+///   password = "${MDB_PASSWORD}"
+///   tls_ca_file = "certs/ca.pem"
+///   [profiles.ci.adf]
+///   uri = "mongodb://${ADF_HOST}:27017"
+///
+/// Strings in the selected profile may reference environment variables as ${NAME}, or as
+/// ${NAME:-default} to fall back to a default when the variable is not set, and "$$" stands for
+/// a literal '$'. Relative paths are resolved relative to the config file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    default_profile: Option<String>,
+    #[serde(default)]
+    profiles: BTreeMap<String, toml::Value>,
+}
+
+/// Profile is a named set of connection settings and defaults.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Profile {
+    /// The settings for connecting to the mongod.
+    #[serde(default)]
+    pub(crate) mongod: ConnectionConfig,
+
+    /// The settings for connecting to ADF. They are only used in ADF mode.
+    #[serde(default)]
+    pub(crate) adf: ConnectionConfig,
+
+    /// Whether to run in ADF mode, as if the adf flag were provided.
+    #[serde(default)]
+    pub(crate) use_adf: bool,
+
+    /// The test data directories or files read when none are provided with -d.
+    #[serde(default)]
+    pub(crate) test_data_directories: Vec<PathBuf>,
+}
+
+/// ConnectionConfig holds the settings for connecting to a mongod or ADF. Settings that are not
+/// provided are taken from the URI, if it has them.
+#[derive(Debug, Default, Clone, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ConnectionConfig {
+    pub(crate) uri: Option<String>,
+    pub(crate) username: Option<String>,
+    pub(crate) password: Option<String>,
+    pub(crate) auth_source: Option<String>,
+    /// The authentication mechanism, e.g. "SCRAM-SHA-256" or "MONGODB-X509".
+    pub(crate) auth_mechanism: Option<String>,
+    /// The CA file used to verify the server's certificate. Enables TLS.
+    pub(crate) tls_ca_file: Option<PathBuf>,
+    /// The file holding the client's certificate and private key. Enables TLS.
+    pub(crate) tls_certificate_key_file: Option<PathBuf>,
+}
+
+/// Connection is a resolved ConnectionConfig: its URI comes from the command line, the profile,
+/// or the default environment variables, in that order of precedence.
+#[derive(Debug, Clone)]
+pub(crate) struct Connection {
+    /// The URI, or the reason there is none.
+    uri: std::result::Result<String, String>,
+    config: ConnectionConfig,
+}
+
+/// load reads the named profile from the config file. If no profile is named, the config file's
+/// default_profile is used, and if it has none, or the config file does not exist and was not
+/// explicitly provided, no profile is used and None is returned.
+pub(crate) fn load(config: Option<&Path>, profile: Option<&str>) -> Result<Option<Profile>> {
+    let path = config.unwrap_or(Path::new(DEFAULT_CONFIG_FILE));
+    if config.is_none() && profile.is_none() && !path.is_file() {
+        return Ok(None);
+    }
+    let error =
+        |message: String| DataLoaderError::InvalidConfig(format!("{}: {message}", path.display()));
+
+    let contents = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+    let mut config_file: ConfigFile =
+        toml::from_str(&contents).map_err(|e| error(e.message().to_string()))?;
+    let Some(name) = profile.map(str::to_string).or(config_file.default_profile) else {
+        return Ok(None);
+    };
+    let mut value = config_file.profiles.remove(&name).ok_or_else(|| {
+        error(format!(
+            "profile \"{name}\" is not defined; defined profiles are {:?}",
+            config_file.profiles.keys().collect::<Vec<_>>()
+        ))
+    })?;
+
+    interpolate_value(&mut value, "", &|name| env::var(name).ok())
+        .map_err(|e| error(format!("profile \"{name}\": {e}")))?;
+    let mut profile = Profile::deserialize(value)
+        .map_err(|e| error(format!("profile \"{name}\": {}", e.message())))?;
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    for directory in profile.test_data_directories.iter_mut() {
+        *directory = dir.join(&*directory);
+    }
+    for connection in [&mut profile.mongod, &mut profile.adf] {
+        for file in [
+            &mut connection.tls_ca_file,
+            &mut connection.tls_certificate_key_file,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *file = dir.join(&*file);
+        }
+    }
+    Ok(Some(profile))
+}
+
+/// interpolate_value replaces the environment variable references in every string of the value.
+/// Errors name the key of the string, e.g. "mongod.uri".
+fn interpolate_value(
+    value: &mut toml::Value,
+    key: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> std::result::Result<(), String> {
+    match value {
+        toml::Value::String(s) => {
+            *s = interpolate(s, lookup).map_err(|e| format!("{key}: {e}"))?;
+        }
+        toml::Value::Array(values) => {
+            for (i, value) in values.iter_mut().enumerate() {
+                interpolate_value(value, &format!("{key}[{i}]"), lookup)?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (name, value) in table.iter_mut() {
+                let key = if key.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{key}.{name}")
+                };
+                interpolate_value(value, &key, lookup)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// interpolate replaces every ${NAME} in the string with the value lookup returns for NAME, and
+/// every ${NAME:-default} with the value, or with the default if there is none. "$$" is replaced
+/// with '$', and any other '$' is left as is.
+pub(crate) fn interpolate(
+    s: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> std::result::Result<String, String> {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("$$") {
+            result.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = after
+                .find('}')
+                .ok_or_else(|| format!("unterminated variable reference in \"{s}\""))?;
+            let reference = &after[..end];
+            let (name, default) = match reference.split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (reference, None),
+            };
+            if name.is_empty() {
+                return Err(format!("empty variable reference in \"{s}\""));
+            }
+            match (lookup(name), default) {
+                (Some(value), _) => result.push_str(&value),
+                (None, Some(default)) => result.push_str(default),
+                (None, None) => return Err(format!("environment variable {name} is not set")),
+            }
+            rest = &after[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+impl Connection {
+    /// resolve returns the connection described by the profile's config, with the URI provided on
+    /// the command line, if any, taking precedence over the profile's. If neither provides a URI,
+    /// default_uri is used.
+    pub(crate) fn resolve(
+        uri: Option<String>,
+        config: &ConnectionConfig,
+        default_uri: impl FnOnce() -> std::result::Result<String, String>,
+    ) -> Self {
+        let config = config.clone();
+        let uri = match uri.or_else(|| config.uri.clone()) {
+            Some(uri) => Ok(uri),
+            None => default_uri(),
+        };
+        Connection { uri, config }
+    }
+
+    /// uri returns the URI to connect with, or an error explaining why there is none.
+    pub(crate) fn uri(&self) -> Result<&str> {
+        self.uri
+            .as_deref()
+            .map_err(|e| DataLoaderError::InvalidConfig(e.clone()))
+    }
+
+    /// redacted_uri returns the URI to connect with, with any credentials in it masked, so that it
+    /// can be printed.
+    pub(crate) fn redacted_uri(&self) -> Result<String> {
+        Ok(redact_uri(self.uri()?))
+    }
+
+    /// client_options returns the options for connecting: those parsed from the URI, overridden by
+    /// the credentials and TLS files of the config.
+    pub(crate) async fn client_options(&self) -> Result<ClientOptions> {
+        let mut options = ClientOptions::parse(self.uri()?).await?;
+        let config = &self.config;
+
+        if config.username.is_some()
+            || config.password.is_some()
+            || config.auth_source.is_some()
+            || config.auth_mechanism.is_some()
+        {
+            let mut credential = options.credential.take().unwrap_or_default();
+            if let Some(username) = &config.username {
+                credential.username = Some(username.clone());
+            }
+            if let Some(password) = &config.password {
+                credential.password = Some(password.clone());
+            }
+            if let Some(source) = &config.auth_source {
+                credential.source = Some(source.clone());
+            }
+            if let Some(mechanism) = &config.auth_mechanism {
+                credential.mechanism = Some(AuthMechanism::from_str(mechanism)?);
+            }
+            options.credential = Some(credential);
+        }
+
+        if config.tls_ca_file.is_some() || config.tls_certificate_key_file.is_some() {
+            let mut tls = match options.tls.take() {
+                Some(Tls::Enabled(tls)) => tls,
+                _ => TlsOptions::default(),
+            };
+            if let Some(ca_file) = &config.tls_ca_file {
+                tls.ca_file_path = Some(ca_file.clone());
+            }
+            if let Some(certificate_key_file) = &config.tls_certificate_key_file {
+                tls.cert_key_file_path = Some(certificate_key_file.clone());
+            }
+            options.tls = Some(Tls::Enabled(tls));
+        }
+
+        Ok(options)
+    }
+
+    /// connect returns a client for the connection.
+    pub(crate) async fn connect(&self) -> Result<Client> {
+        Ok(Client::with_options(self.client_options().await?)?)
+    }
+}
+
+/// redact_uri replaces the user information of a connection string, if it has any, with "***".
+/// The user information is everything between the scheme and the last '@' before the path or
+/// options, since a password may contain unescaped '@' characters.
+pub(crate) fn redact_uri(uri: &str) -> String {
+    let Some((scheme, rest)) = uri.split_once("://") else {
+        return uri.to_string();
+    };
+    let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+    match rest[..authority_end].rfind('@') {
+        Some(at) => format!("{scheme}://***{}", &rest[at..]),
+        None => uri.to_string(),
+    }
+}
+
+/// default_mongod_uri returns the mongod URI built from the MDB_TEST_LOCAL_* environment
+/// variables, used when no mongod URI is provided.
+pub(crate) fn default_mongod_uri() -> std::result::Result<String, String> {
+    Ok(format!(
+        "mongodb://{}:{}",
+        required_var("MDB_TEST_LOCAL_HOST", "mongod")?,
+        required_var("MDB_TEST_LOCAL_PORT", "mongod")?,
+    ))
+}
+
+/// default_adf_uri returns the ADF URI built from the ADF_TEST_LOCAL_* environment variables, used
+/// when no ADF URI is provided.
+pub(crate) fn default_adf_uri() -> std::result::Result<String, String> {
+    Ok(format!(
+        "mongodb://{}:{}@{}:{}",
+        required_var("ADF_TEST_LOCAL_USER", "adf")?,
+        required_var("ADF_TEST_LOCAL_PASSWORD", "adf")?,
+        required_var("ADF_TEST_LOCAL_HOST", "adf")?,
+        required_var("ADF_TEST_LOCAL_PORT", "adf")?,
+    ))
+}
+
+/// required_var returns the value of an environment variable a default URI is built from.
+fn required_var(name: &str, server: &str) -> std::result::Result<String, String> {
+    env::var(name).map_err(|_| {
+        format!(
+            "no {server} URI provided with --{server}-uri or by a profile, and {name} is not set"
+        )
+    })
+}
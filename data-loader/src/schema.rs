@@ -0,0 +1,255 @@
+use mongodb::bson::{doc, Bson, Document};
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, BTreeSet},
+};
+
+/// bson_type_name returns the MongoDB JSON-schema `bsonType` name of a value.
+pub(crate) fn bson_type_name(value: &Bson) -> &'static str {
+    match value {
+        Bson::Double(_) => "double",
+        Bson::String(_) => "string",
+        Bson::Array(_) => "array",
+        Bson::Document(_) => "object",
+        Bson::Boolean(_) => "bool",
+        Bson::Null => "null",
+        Bson::RegularExpression(_) => "regex",
+        Bson::JavaScriptCode(_) => "javascript",
+        Bson::JavaScriptCodeWithScope(_) => "javascriptWithScope",
+        Bson::Int32(_) => "int",
+        Bson::Int64(_) => "long",
+        Bson::Timestamp(_) => "timestamp",
+        Bson::Binary(_) => "binData",
+        Bson::ObjectId(_) => "objectId",
+        Bson::DateTime(_) => "date",
+        Bson::Symbol(_) => "symbol",
+        Bson::Decimal128(_) => "decimal",
+        Bson::Undefined => "undefined",
+        Bson::MaxKey => "maxKey",
+        Bson::MinKey => "minKey",
+        Bson::DbPointer(_) => "dbPointer",
+    }
+}
+
+/// matches_bson_type returns true if the value has the named type. The "number" alias matches any
+/// numeric type.
+fn matches_bson_type(value: &Bson, type_name: &str) -> bool {
+    let actual = bson_type_name(value);
+    actual == type_name
+        || (type_name == "number" && matches!(actual, "double" | "int" | "long" | "decimal"))
+}
+
+/// escape_pointer_token escapes a field name for use in a JSON pointer, per RFC 6901.
+fn escape_pointer_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+/// conform checks a value against a schema in the MongoDB JSON-schema dialect. It supports the
+/// bsonType, anyOf, required, properties, additionalProperties, and items keywords; other keywords
+/// are ignored. The pointer is the JSON pointer of the value within its document. Returns one
+/// message per violation, each prefixed with the JSON pointer of the offending value.
+pub(crate) fn conform(schema: &Document, value: &Bson, pointer: &str) -> Vec<String> {
+    let location = if pointer.is_empty() { "/" } else { pointer };
+    let mut violations = vec![];
+
+    match schema.get("bsonType") {
+        Some(Bson::String(t)) if !matches_bson_type(value, t) => violations.push(format!(
+            "{location}: expected bsonType {t:?}, found {:?}",
+            bson_type_name(value)
+        )),
+        Some(Bson::Array(types))
+            if !types
+                .iter()
+                .any(|t| matches!(t, Bson::String(t) if matches_bson_type(value, t))) =>
+        {
+            violations.push(format!(
+                "{location}: expected one of bsonTypes {}, found {:?}",
+                Bson::Array(types.clone()),
+                bson_type_name(value)
+            ))
+        }
+        _ => {}
+    }
+
+    if let Ok(alternatives) = schema.get_array("anyOf") {
+        let matched = alternatives.iter().any(|alternative| match alternative {
+            Bson::Document(alternative) => conform(alternative, value, pointer).is_empty(),
+            _ => false,
+        });
+        if !matched {
+            violations.push(format!(
+                "{location}: does not match any of the anyOf alternatives"
+            ));
+        }
+    }
+
+    match value {
+        Bson::Document(document) => violations.extend(conform_object(schema, document, pointer)),
+        Bson::Array(elements) => violations.extend(conform_array(schema, elements, pointer)),
+        _ => {}
+    }
+
+    violations
+}
+
+/// conform_object applies the object keywords of a schema to a document.
+fn conform_object(schema: &Document, document: &Document, pointer: &str) -> Vec<String> {
+    let location = if pointer.is_empty() { "/" } else { pointer };
+    let mut violations = vec![];
+
+    if let Ok(required) = schema.get_array("required") {
+        for field in required.iter().filter_map(Bson::as_str) {
+            if !document.contains_key(field) {
+                violations.push(format!("{location}: missing required field {field:?}"));
+            }
+        }
+    }
+
+    let properties = schema.get_document("properties").ok();
+    for (field, field_value) in document {
+        let field_pointer = format!("{pointer}/{}", escape_pointer_token(field));
+        match (
+            properties.and_then(|p| p.get(field)),
+            schema.get("additionalProperties"),
+        ) {
+            (Some(Bson::Document(property)), _) => {
+                violations.extend(conform(property, field_value, &field_pointer))
+            }
+            (Some(_), _) => {}
+            (None, Some(Bson::Boolean(false))) => violations.push(format!(
+                "{field_pointer}: additional property {field:?} is not allowed"
+            )),
+            (None, Some(Bson::Document(additional))) => {
+                violations.extend(conform(additional, field_value, &field_pointer))
+            }
+            (None, _) => {}
+        }
+    }
+
+    violations
+}
+
+/// conform_array applies the items keyword of a schema to an array. A single items schema applies
+/// to every element; an array of items schemas applies positionally.
+fn conform_array(schema: &Document, elements: &[Bson], pointer: &str) -> Vec<String> {
+    let item_schemas: Vec<Option<&Document>> = match schema.get("items") {
+        Some(Bson::Document(items)) => vec![Some(items); elements.len()],
+        Some(Bson::Array(items)) => items.iter().map(Bson::as_document).collect(),
+        _ => return vec![],
+    };
+
+    elements
+        .iter()
+        .zip(item_schemas)
+        .enumerate()
+        .filter_map(|(i, (element, item_schema))| {
+            item_schema.map(|s| conform(s, element, &format!("{pointer}/{i}")))
+        })
+        .flatten()
+        .collect()
+}
+
+/// InferredSchema accumulates the schema of every value seen at one position in a set of
+/// documents. It is converted to the MongoDB JSON-schema dialect by to_bson.
+#[derive(Debug, Default)]
+struct InferredSchema {
+    /// The names of the non-object, non-array types seen.
+    scalars: BTreeSet<&'static str>,
+    /// The merged schema of every object seen, if any were seen.
+    object: Option<InferredObject>,
+    /// The merged schema of every element of every array seen, if any arrays were seen.
+    array: Option<Box<InferredSchema>>,
+}
+
+#[derive(Debug, Default)]
+struct InferredObject {
+    properties: BTreeMap<String, InferredSchema>,
+    /// The fields present in every object seen.
+    required: BTreeSet<String>,
+}
+
+impl InferredSchema {
+    fn add(&mut self, value: &Bson) {
+        match value {
+            Bson::Document(document) => {
+                let keys = document.keys().cloned().collect::<BTreeSet<_>>();
+                let object = match self.object.as_mut() {
+                    Some(object) => {
+                        object.required.retain(|field| keys.contains(field));
+                        object
+                    }
+                    None => self.object.insert(InferredObject {
+                        properties: BTreeMap::new(),
+                        required: keys,
+                    }),
+                };
+                for (field, field_value) in document {
+                    object
+                        .properties
+                        .entry(field.clone())
+                        .or_default()
+                        .add(field_value);
+                }
+            }
+            Bson::Array(elements) => {
+                let items = self.array.get_or_insert_with(Default::default);
+                for element in elements {
+                    items.add(element);
+                }
+            }
+            _ => {
+                self.scalars.insert(bson_type_name(value));
+            }
+        }
+    }
+
+    fn to_bson(&self) -> Bson {
+        let mut alternatives = vec![];
+        if let Some(array) = &self.array {
+            alternatives.push(doc! {"bsonType": "array", "items": array.to_bson()});
+        }
+        if let Some(object) = &self.object {
+            let mut properties = Document::new();
+            for (field, schema) in &object.properties {
+                properties.insert(field.clone(), schema.to_bson());
+            }
+            alternatives.push(doc! {
+                "bsonType": "object",
+                "properties": properties,
+                "required": object.required.iter().cloned().collect::<Vec<_>>(),
+                "additionalProperties": false,
+            });
+        }
+        alternatives.extend(self.scalars.iter().map(|t| doc! {"bsonType": *t}));
+        alternatives.sort_by_key(|a| a.get_str("bsonType").unwrap_or_default().to_string());
+
+        match alternatives.len() {
+            0 => Bson::Document(Document::new()),
+            1 => Bson::Document(alternatives.remove(0)),
+            _ => Bson::Document(doc! {"anyOf": alternatives}),
+        }
+    }
+}
+
+/// infer derives a schema from a set of documents, in the same shape ADF's sqlGenerateSchema
+/// produces: a field seen with several types is an anyOf of one alternative per type, a field is
+/// required if every document contains it, objects disallow additional properties, and array items
+/// are described by the union of every element's schema. Properties and alternatives are sorted
+/// by name so the result is deterministic. No documents infer an object with no properties, the
+/// schema of an empty collection.
+pub(crate) fn infer<B: Borrow<Bson>>(docs: impl IntoIterator<Item = B>) -> Bson {
+    let mut schema = InferredSchema::default();
+    let mut empty = true;
+    for doc in docs {
+        schema.add(doc.borrow());
+        empty = false;
+    }
+    if empty {
+        return Bson::Document(doc! {
+            "bsonType": "object",
+            "properties": {},
+            "additionalProperties": false,
+        });
+    }
+    schema.to_bson()
+}
@@ -0,0 +1,217 @@
+use crate::{
+    csv::{self, CsvOptions},
+    tags, DataLoaderError, Result,
+};
+use mongodb::bson::{Bson, Document};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+/// The docs file extensions read_documents accepts.
+pub(crate) const DOCS_FILE_EXTENSIONS: &[&str] = &["jsonl", "ndjson", "json", "bson", "csv"];
+
+/// Documents is a stream of documents read from a docs file. Iteration stops after the first
+/// error.
+pub(crate) type Documents = Box<dyn Iterator<Item = Result<Bson>> + Send>;
+
+/// read_documents opens a docs file and returns its documents. The format is chosen by the file's
+/// extension:
+///   - '.jsonl' or '.ndjson': JSON Lines, one Extended JSON document per line. Blank lines are
+///     skipped.
+///   - '.json': a single Extended JSON array of documents.
+///   - '.bson': concatenated BSON documents, as written by mongodump.
+///   - '.csv': a header row followed by one row per document, converted according to the CSV
+///     options, or to string values if there are none.
+///
+/// JSON Lines, BSON, and CSV files are read one document at a time, so they can be streamed into a
+/// collection without holding the whole file in memory. Extended JSON arrays are parsed whole.
+///
+/// If strict_extended_json is true, JSON documents are checked as described in
+/// extjson::check_wrapper, and errors report the line and column of the malformed value.
+pub(crate) fn read_documents(
+    path: &Path,
+    csv_options: Option<&CsvOptions>,
+    strict_extended_json: bool,
+) -> Result<Documents> {
+    let file = path.display().to_string();
+    let reader = BufReader::new(
+        File::open(path).map_err(|e| DataLoaderError::InvalidDocsFile(format!("{file}: {e}")))?,
+    );
+
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("jsonl" | "ndjson") if strict_extended_json => {
+            let documents = reader
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !matches!(line, Ok(l) if l.trim().is_empty()))
+                .map(move |(i, line)| {
+                    let location = |column: Option<usize>| match column {
+                        Some(column) => format!("{file}, line {}, column {column}", i + 1),
+                        None => format!("{file}, line {}", i + 1),
+                    };
+                    let line = line.map_err(|e| {
+                        DataLoaderError::InvalidDocsFile(format!("{}: {e}", location(None)))
+                    })?;
+                    tags::from_json_str(&line, true)
+                        .map_err(|e| {
+                            DataLoaderError::InvalidDocsFile(format!(
+                                "{}: {}",
+                                location(Some(e.column())),
+                                without_location(&e)
+                            ))
+                        })
+                        .and_then(|value| {
+                            expect_document(value).map_err(|e| {
+                                DataLoaderError::InvalidDocsFile(format!("{}: {e}", location(None)))
+                            })
+                        })
+                });
+            Ok(Box::new(until_error(documents)))
+        }
+        Some("jsonl" | "ndjson") => {
+            let documents = reader
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !matches!(line, Ok(l) if l.trim().is_empty()))
+                .map(move |(i, line)| {
+                    line.map_err(|e| e.to_string())
+                        .and_then(|l| serde_json::from_str(&l).map_err(|e| e.to_string()))
+                        .and_then(to_document)
+                        .map_err(|e| {
+                            DataLoaderError::InvalidDocsFile(format!("{file}, line {}: {e}", i + 1))
+                        })
+                });
+            Ok(Box::new(until_error(documents)))
+        }
+        Some("json") if strict_extended_json => {
+            let values = tags::from_json_reader(reader, true).map_err(|e| {
+                DataLoaderError::InvalidDocsFile(format!(
+                    "{file}, line {}, column {}: {}",
+                    e.line(),
+                    e.column(),
+                    without_location(&e)
+                ))
+            })?;
+            let documents = values.into_iter().enumerate().map(move |(i, value)| {
+                expect_document(value).map_err(|e| {
+                    DataLoaderError::InvalidDocsFile(format!("{file}, document {i}: {e}"))
+                })
+            });
+            Ok(Box::new(until_error(documents)))
+        }
+        Some("json") => {
+            let values = match serde_json::from_reader(reader) {
+                Ok(serde_json::Value::Array(values)) => values,
+                Ok(_) => {
+                    return Err(DataLoaderError::InvalidDocsFile(format!(
+                        "{file}: must contain an array of documents"
+                    )))
+                }
+                Err(e) => return Err(DataLoaderError::InvalidDocsFile(format!("{file}: {e}"))),
+            };
+            let documents = values.into_iter().enumerate().map(move |(i, value)| {
+                to_document(value).map_err(|e| {
+                    DataLoaderError::InvalidDocsFile(format!("{file}, document {i}: {e}"))
+                })
+            });
+            Ok(Box::new(until_error(documents)))
+        }
+        Some("csv") => {
+            let options = csv_options.cloned().unwrap_or_default();
+            Ok(Box::new(until_error(csv::read_csv(
+                reader, file, &options,
+            )?)))
+        }
+        Some("bson") => Ok(Box::new(until_error(BsonDocuments {
+            reader,
+            file,
+            index: 0,
+        }))),
+        _ => Err(DataLoaderError::InvalidDocsFile(format!(
+            "{file}: unsupported extension, expected one of {DOCS_FILE_EXTENSIONS:?}"
+        ))),
+    }
+}
+
+/// to_document parses an Extended JSON value, which must be a document.
+fn to_document(value: serde_json::Value) -> std::result::Result<Bson, String> {
+    match value {
+        serde_json::Value::Object(map) => Document::try_from(map)
+            .map(Bson::Document)
+            .map_err(|e| e.to_string()),
+        _ => Err(format!("expected a document, found {value}")),
+    }
+}
+
+/// expect_document checks that a value parsed from a docs file is a document.
+fn expect_document(value: Bson) -> std::result::Result<Bson, String> {
+    match value {
+        Bson::Document(_) => Ok(value),
+        _ => Err(format!("expected a document, found {value}")),
+    }
+}
+
+/// without_location returns the message of a serde_json error without the line and column it
+/// ends with, so they can be reported in the same form as other locations.
+pub(crate) fn without_location(e: &serde_json::Error) -> String {
+    let message = e.to_string();
+    let suffix = format!(" at line {} column {}", e.line(), e.column());
+    message
+        .strip_suffix(&suffix)
+        .unwrap_or(&message)
+        .to_string()
+}
+
+/// until_error ends a stream of documents after its first error, since nothing after a malformed
+/// document can be trusted.
+fn until_error(
+    documents: impl Iterator<Item = Result<Bson>> + Send + 'static,
+) -> impl Iterator<Item = Result<Bson>> + Send + 'static {
+    let mut failed = false;
+    documents.map_while(move |doc| {
+        if failed {
+            return None;
+        }
+        failed = doc.is_err();
+        Some(doc)
+    })
+}
+
+/// BsonDocuments reads concatenated BSON documents from a file, one at a time.
+struct BsonDocuments {
+    reader: BufReader<File>,
+    file: String,
+    index: usize,
+}
+
+impl Iterator for BsonDocuments {
+    type Item = Result<Bson>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.reader.fill_buf() {
+            Ok([]) => return None,
+            Ok(_) => {}
+            Err(e) => {
+                return Some(Err(DataLoaderError::InvalidDocsFile(format!(
+                    "{}: {e}",
+                    self.file
+                ))))
+            }
+        }
+
+        let index = self.index;
+        self.index += 1;
+        Some(
+            Document::from_reader(&mut self.reader)
+                .map(Bson::Document)
+                .map_err(|e| {
+                    DataLoaderError::InvalidDocsFile(format!(
+                        "{}, document {index}: {e}",
+                        self.file
+                    ))
+                }),
+        )
+    }
+}
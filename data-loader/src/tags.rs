@@ -0,0 +1,315 @@
+use crate::{extjson, schema::bson_type_name};
+use mongodb::bson::{
+    self, oid::ObjectId, spec::BinarySubtype, Binary, Bson, DateTime, Decimal128, Document, Regex,
+    Timestamp,
+};
+use serde::{
+    de::{self, value, DeserializeSeed, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use std::{fmt, io::Read};
+
+/// The YAML tags understood in documents, without their leading '!'.
+pub(crate) const TAGS: &[&str] = &[
+    "oid",
+    "date",
+    "decimal",
+    "int32",
+    "int64",
+    "double",
+    "binary",
+    "regex",
+    "timestamp",
+    "minkey",
+    "maxkey",
+];
+
+/// deserialize_docs deserializes a list of documents like Vec<Bson> does, including Extended JSON
+/// wrappers, and additionally converts values with YAML tags to the BSON types they name:
+///   - !oid "5f0f1b9b9b9b9b9b9b9b9b9b": an ObjectId, from its hex string.
+///   - !date "2020-09-13T12:26:40Z": a Date, from an RFC 3339 string or milliseconds since the
+///     epoch.
+///   - !decimal "1.50": a Decimal128, from a string or number.
+///   - !int32 1, !int64 1, !double 1: a number of that type, from a number or string.
+///   - !binary "AQID": Binary data of the generic subtype, from a base64 string, or
+///     !binary { base64: "AQID", subType: "80" } for any other subtype, given in hex.
+///   - !regex "/^a.c$/i": a regular expression, from a string in slashes followed by options, or
+///     from { pattern, options }.
+///   - !timestamp { t: 1, i: 2 }: a Timestamp.
+///   - !minkey, !maxkey: MinKey and MaxKey. Any value is ignored.
+///
+/// Formats without tags, like JSON, are deserialized exactly as Vec<Bson> would be.
+pub(crate) fn deserialize_docs<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Bson>, D::Error> {
+    TaggedBsonSeq { strict: false }.deserialize(deserializer)
+}
+
+/// deserialize_strict_docs deserializes a list of documents like deserialize_docs, but every
+/// document with a field name starting with '$' must be a well-formed Extended JSON v2 type
+/// wrapper; see extjson::check_wrapper.
+pub(crate) fn deserialize_strict_docs<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Bson>, D::Error> {
+    TaggedBsonSeq { strict: true }.deserialize(deserializer)
+}
+
+/// from_json_str parses a single JSON value, with strict Extended JSON checking if strict is true.
+pub(crate) fn from_json_str(json: &str, strict: bool) -> serde_json::Result<Bson> {
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let value = TaggedBsonSeed { strict }.deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+/// from_json_reader parses a JSON array of values, with strict Extended JSON checking if strict is
+/// true.
+pub(crate) fn from_json_reader(reader: impl Read, strict: bool) -> serde_json::Result<Vec<Bson>> {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let values = TaggedBsonSeq { strict }.deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(values)
+}
+
+/// TaggedBsonSeed deserializes a Bson value that may have been written with a YAML tag. If strict
+/// is true, documents are checked with extjson::check_wrapper.
+#[derive(Clone, Copy)]
+struct TaggedBsonSeed {
+    strict: bool,
+}
+
+impl<'de> DeserializeSeed<'de> for TaggedBsonSeed {
+    type Value = Bson;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Bson, D::Error> {
+        deserializer.deserialize_any(TaggedBsonVisitor {
+            strict: self.strict,
+        })
+    }
+}
+
+/// TaggedBsonSeq deserializes a sequence of values with TaggedBsonSeed.
+struct TaggedBsonSeq {
+    strict: bool,
+}
+
+impl<'de> DeserializeSeed<'de> for TaggedBsonSeq {
+    type Value = Vec<Bson>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Vec<Bson>, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for TaggedBsonSeq {
+    type Value = Vec<Bson>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let seed = TaggedBsonSeed {
+            strict: self.strict,
+        };
+        let mut values = vec![];
+        while let Some(value) = seq.next_element_seed(seed)? {
+            values.push(value);
+        }
+        Ok(values)
+    }
+}
+
+/// TaggedBsonVisitor hands untagged scalars to Bson's own visitor, so they get the same BSON types
+/// they would without tag support, and recurses into documents and arrays so that tags can be
+/// used at any depth.
+struct TaggedBsonVisitor {
+    strict: bool,
+}
+
+impl TaggedBsonVisitor {
+    fn seed(&self) -> TaggedBsonSeed {
+        TaggedBsonSeed {
+            strict: self.strict,
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for TaggedBsonVisitor {
+    type Value = Bson;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a BSON value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Self::Value, E> {
+        Bson::deserialize(value::BoolDeserializer::new(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Bson::deserialize(value::I64Deserializer::new(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Bson::deserialize(value::U64Deserializer::new(v))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        Bson::deserialize(value::F64Deserializer::new(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Bson::String(v.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+        Ok(Bson::String(v))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(Bson::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(Bson::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        self.seed().deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut values = vec![];
+        while let Some(value) = seq.next_element_seed(self.seed())? {
+            values.push(value);
+        }
+        Ok(Bson::Array(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut doc = Document::new();
+        while let Some(key) = map.next_key::<String>()? {
+            let value = map.next_value_seed(self.seed())?;
+            doc.insert(key, value);
+        }
+        if self.strict {
+            extjson::check_wrapper(&doc).map_err(de::Error::custom)?;
+        }
+        // Documents whose keys start with '$' may be Extended JSON wrappers, which Bson's own
+        // visitor converts to the values they represent.
+        if doc.keys().next().is_some_and(|key| key.starts_with('$')) {
+            return bson::from_bson(Bson::Document(doc)).map_err(de::Error::custom);
+        }
+        Ok(Bson::Document(doc))
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        let (tag, variant) = data.variant::<String>()?;
+        let value = variant.newtype_variant_seed(self.seed())?;
+        from_tag(&tag, value).map_err(de::Error::custom)
+    }
+}
+
+/// from_tag converts the value of a tagged node to the BSON type the tag names.
+fn from_tag(tag: &str, value: Bson) -> Result<Bson, String> {
+    if !TAGS.contains(&tag) {
+        return Err(format!("unknown tag !{tag}, expected one of {TAGS:?}"));
+    }
+    let invalid = |reason: &dyn fmt::Display| format!("invalid !{tag} value {value}: {reason}");
+
+    match (tag, &value) {
+        ("oid", Bson::String(s)) => ObjectId::parse_str(s)
+            .map(Bson::ObjectId)
+            .map_err(|e| invalid(&e)),
+        ("date", Bson::String(s)) => DateTime::parse_rfc3339_str(s)
+            .map(Bson::DateTime)
+            .map_err(|e| invalid(&e)),
+        ("date", Bson::Int32(_) | Bson::Int64(_)) => {
+            integer(&value).map(|millis| Bson::DateTime(DateTime::from_millis(millis)))
+        }
+        ("decimal", Bson::String(s)) => s
+            .parse::<Decimal128>()
+            .map(Bson::Decimal128)
+            .map_err(|e| invalid(&e)),
+        ("decimal", Bson::Int32(_) | Bson::Int64(_) | Bson::Double(_)) => value
+            .to_string()
+            .parse::<Decimal128>()
+            .map(Bson::Decimal128)
+            .map_err(|e| invalid(&e)),
+        ("int32", _) => integer(&value)
+            .and_then(|i| i32::try_from(i).map_err(|e| e.to_string()))
+            .map(Bson::Int32)
+            .map_err(|e| invalid(&e)),
+        ("int64", _) => integer(&value).map(Bson::Int64).map_err(|e| invalid(&e)),
+        ("double", Bson::Int32(i)) => Ok(Bson::Double(*i as f64)),
+        ("double", Bson::Int64(i)) => Ok(Bson::Double(*i as f64)),
+        ("double", Bson::Double(_)) => Ok(value),
+        ("double", Bson::String(s)) => s.parse::<f64>().map(Bson::Double).map_err(|e| invalid(&e)),
+        ("binary", Bson::String(s)) => Binary::from_base64(s, BinarySubtype::Generic)
+            .map(Bson::Binary)
+            .map_err(|e| invalid(&e)),
+        ("binary", Bson::Document(d)) => {
+            let subtype = d
+                .get_str("subType")
+                .map_err(|e| e.to_string())
+                .and_then(|s| u8::from_str_radix(s, 16).map_err(|e| e.to_string()))
+                .map_err(|e| invalid(&format!("subType: {e}")))?;
+            Binary::from_base64(
+                d.get_str("base64").map_err(|e| invalid(&e))?,
+                BinarySubtype::from(subtype),
+            )
+            .map(Bson::Binary)
+            .map_err(|e| invalid(&e))
+        }
+        ("regex", Bson::String(s)) => {
+            let (pattern, options) = match s.strip_prefix('/').and_then(|s| s.rsplit_once('/')) {
+                Some((pattern, options)) => (pattern, options),
+                None => (s.as_str(), ""),
+            };
+            Ok(regex(pattern, options))
+        }
+        ("regex", Bson::Document(d)) => Ok(regex(
+            d.get_str("pattern").map_err(|e| invalid(&e))?,
+            d.get_str("options").unwrap_or_default(),
+        )),
+        ("timestamp", Bson::Document(d)) => {
+            let field = |name: &str| {
+                d.get(name)
+                    .ok_or_else(|| format!("missing {name}"))
+                    .and_then(integer)
+                    .and_then(|i| u32::try_from(i).map_err(|e| e.to_string()))
+                    .map_err(|e| invalid(&format!("{name}: {e}")))
+            };
+            Ok(Bson::Timestamp(Timestamp {
+                time: field("t")?,
+                increment: field("i")?,
+            }))
+        }
+        ("minkey", _) => Ok(Bson::MinKey),
+        ("maxkey", _) => Ok(Bson::MaxKey),
+        _ => Err(invalid(&format!("unexpected {}", bson_type_name(&value)))),
+    }
+}
+
+/// integer returns the value of an integer, or of a string holding one.
+fn integer(value: &Bson) -> Result<i64, String> {
+    match value {
+        Bson::Int32(i) => Ok(*i as i64),
+        Bson::Int64(i) => Ok(*i),
+        Bson::String(s) => s.parse::<i64>().map_err(|e| e.to_string()),
+        _ => Err(format!(
+            "expected an integer, found {}",
+            bson_type_name(value)
+        )),
+    }
+}
+
+/// regex returns a regular expression with its options sorted, as mongod stores them.
+fn regex(pattern: &str, options: &str) -> Bson {
+    let mut options = options.chars().collect::<Vec<_>>();
+    options.sort_unstable();
+    Bson::RegularExpression(Regex {
+        pattern: pattern.to_string(),
+        options: options.into_iter().collect(),
+    })
+}
@@ -0,0 +1,137 @@
+use crate::{
+    adf_config::{add_views, adf_databases, AdfDatabase, AdfView},
+    test::parse,
+};
+use mongodb::bson::{self, doc, Bson};
+use serde_json::json;
+
+#[test]
+fn databases_and_views_are_generated_from_data_files() {
+    let test_data_files = [
+        parse(
+            r#"
+dataset:
+  - db: "tdvt"
+    collection: { name: "calcs", docs: [] }
+  - db: "integration_test"
+    collection: { name: "foo", docs: [] }
+  - db: "integration_test"
+    view:
+      name: "baz"
+      view_on: "foo"
+      pipeline: [ { $project: { a: "$a", a_type: { $type: "$a" } } } ]
+"#,
+        ),
+        parse(
+            r#"
+dataset:
+  - db: "integration_test"
+    view: { name: "no_definition" }
+  - db: "db2"
+    view: { name: "v", view_on: "c", pipeline: [ { $match: { d: { $date: "2024-01-01T00:00:00Z" } } } ] }
+"#,
+        ),
+    ];
+
+    let databases = adf_databases(&test_data_files, "localstore");
+    let data_source = |db: &str| json!([{"name": "*", "dataSources": [{"storeName": "localstore", "database": db}]}]);
+    assert_eq!(
+        json!([
+            {
+                "name": "db2",
+                "collections": data_source("db2"),
+                "views": [{
+                    "name": "v",
+                    "source": "c",
+                    "pipeline": r#"[{"$match":{"d":{"$date":"2024-01-01T00:00:00Z"}}}]"#,
+                }],
+            },
+            {
+                "name": "integration_test",
+                "collections": data_source("integration_test"),
+                "views": [{
+                    "name": "baz",
+                    "source": "foo",
+                    "pipeline": r#"[{"$project":{"a":"$a","a_type":{"$type":"$a"}}}]"#,
+                }],
+            },
+            {
+                "name": "tdvt",
+                "collections": data_source("tdvt"),
+                "views": null,
+            },
+        ]),
+        serde_json::to_value(&databases).unwrap()
+    );
+
+    let round_trip: Vec<AdfDatabase> =
+        serde_json::from_value(serde_json::to_value(&databases).unwrap()).unwrap();
+    assert_eq!(databases, round_trip);
+}
+
+#[test]
+fn the_checked_in_config_can_be_read() {
+    let databases: Vec<AdfDatabase> = serde_json::from_str(
+        &std::fs::read_to_string("../test-environment/configuration/adf_db_config.json").unwrap(),
+    )
+    .unwrap();
+    assert_eq!("integration_test", databases[0].name);
+}
+
+#[test]
+fn views_are_added_to_the_storage_config() {
+    let store = doc! {"name": "localmongo", "provider": "mongodb"};
+    let mut storage = doc! {
+        "stores": [store.clone()],
+        "databases": [
+            {
+                "name": "integration_test",
+                "collections": [{"name": "foo", "dataSources": []}],
+                "views": [
+                    {"name": "baz", "source": "foo", "pipeline": "[]"},
+                    {"name": "kept", "source": "foo", "pipeline": "[]"},
+                ],
+            },
+            {"name": "db2", "collections": [], "views": Bson::Null},
+        ],
+    };
+    let test_data_files = [parse(
+        r#"
+dataset:
+  - db: "integration_test"
+    view: { name: "baz", view_on: "bar", pipeline: [ { $match: { a: 1 } } ] }
+  - db: "db2"
+    view: { name: "v", view_on: "c", pipeline: [] }
+  - db: "new_db"
+    view: { name: "w", view_on: "c", pipeline: [] }
+  - db: "no_views"
+    collection: { name: "c", docs: [] }
+"#,
+    )];
+    let databases = adf_databases(&test_data_files, "localmongo");
+
+    let installed = add_views(&mut storage, &databases).unwrap();
+    assert_eq!(vec!["db2.v", "integration_test.baz", "new_db.w"], installed);
+
+    let view = |name, source, pipeline: &[bson::Document]| {
+        bson::to_bson(&AdfView::new(name, source, pipeline)).unwrap()
+    };
+    assert_eq!(
+        doc! {
+            "stores": [store],
+            "databases": [
+                {
+                    "name": "integration_test",
+                    "collections": [{"name": "foo", "dataSources": []}],
+                    "views": [
+                        {"name": "kept", "source": "foo", "pipeline": "[]"},
+                        view("baz", "bar", &[doc! {"$match": {"a": 1}}]),
+                    ],
+                },
+                {"name": "db2", "collections": [], "views": [view("v", "c", &[])]},
+                bson::to_bson(&databases[2]).unwrap(),
+            ],
+        },
+        storage
+    );
+}
@@ -0,0 +1,143 @@
+use crate::{
+    test::{parse, write},
+    DataLoaderError, TestDataFile,
+};
+use mongodb::bson::{doc, Bson};
+
+/// batch_sizes returns the namespace of every collection entry, along with the sizes of the
+/// batches its documents are inserted in.
+fn batch_sizes(test_data_file: &TestDataFile, batch_size: usize) -> Vec<(String, Vec<usize>)> {
+    test_data_file
+        .dataset
+        .iter()
+        .map(|entry| {
+            let batches = entry
+                .collection
+                .as_ref()
+                .unwrap()
+                .batches(entry.schema.as_ref(), batch_size)
+                .unwrap()
+                .map(|batch| batch.unwrap().len())
+                .collect();
+            (entry.namespace(), batches)
+        })
+        .collect()
+}
+
+#[test]
+fn batches_respect_batch_size() {
+    let test_data_file = parse(
+        r#"
+dataset:
+  - db: "test"
+    collection:
+      name: "generated"
+      generate: { count: 25, fields: { _id: { sequence: {} } } }
+"#,
+    );
+    for (batch_size, expected) in [
+        (10, vec![10, 10, 5]),
+        (5, vec![5; 5]),
+        (25, vec![25]),
+        (100, vec![25]),
+        (1, vec![1; 25]),
+    ] {
+        assert_eq!(
+            vec![("test.generated".to_string(), expected)],
+            batch_sizes(&test_data_file, batch_size),
+            "batch_size {batch_size}"
+        );
+    }
+
+    let c = test_data_file.dataset[0].collection.as_ref().unwrap();
+    let ids = c
+        .batches(None, 10)
+        .unwrap()
+        .flat_map(|batch| batch.unwrap())
+        .map(|doc| doc.as_document().unwrap().get("_id").cloned().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!((0..25).map(Bson::Int32).collect::<Vec<_>>(), ids);
+}
+
+#[test]
+fn batches_are_grouped_per_namespace() {
+    let test_data_file = parse(
+        r#"
+dataset:
+  - db: "test"
+    collection:
+      name: "a"
+      docs: [ { _id: 1 }, { _id: 2 }, { _id: 3 } ]
+  - db: "test"
+    collection:
+      name: "b"
+      docs: [ { _id: 1 } ]
+      generate: { count: 3, fields: { _id: { sequence: { start: 2 } } } }
+  - db: "other"
+    collection:
+      name: "a"
+      docs: []
+"#,
+    );
+    assert_eq!(
+        vec![
+            ("test.a".to_string(), vec![3]),
+            ("test.b".to_string(), vec![4]),
+            ("other.a".to_string(), vec![]),
+        ],
+        batch_sizes(&test_data_file, 5)
+    );
+
+    let b = test_data_file.dataset[1].collection.as_ref().unwrap();
+    let batches = b
+        .batches(None, 3)
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        vec![
+            vec![
+                Bson::Document(doc! {"_id": 1}),
+                Bson::Document(doc! {"_id": 2}),
+                Bson::Document(doc! {"_id": 3}),
+            ],
+            vec![Bson::Document(doc! {"_id": 4})],
+        ],
+        batches
+    );
+}
+
+#[test]
+fn batches_stop_at_the_first_unreadable_document() {
+    let path = write(
+        "batches_malformed",
+        &[(
+            "malformed.jsonl",
+            "{\"_id\": 1}\n{\"_id\": 2}\n[1, 2]\n{\"_id\": 4}\n",
+        )],
+    )
+    .join("malformed.jsonl");
+    let test_data_file: TestDataFile = parse(&format!(
+        r#"
+dataset:
+  - db: "test"
+    collection:
+      name: "malformed"
+      docs_file: {path:?}
+"#
+    ));
+
+    let c = test_data_file.dataset[0].collection.as_ref().unwrap();
+    let batches = c.batches(None, 1).unwrap().collect::<Vec<_>>();
+
+    assert_eq!(3, batches.len(), "{batches:?}");
+    assert_eq!(
+        vec![Bson::Document(doc! {"_id": 2})],
+        *batches[1].as_ref().unwrap()
+    );
+    assert!(
+        matches!(&batches[2], Err(DataLoaderError::InvalidDocsFile(_))),
+        "{:?}",
+        batches[2]
+    );
+}
@@ -0,0 +1,220 @@
+use crate::{
+    coverage::{validate_schema_generate_options, SchemaGenerateOptions},
+    read_data_files,
+    schema::conform,
+    FileFilter,
+};
+use mongodb::bson::{doc, oid::ObjectId, Bson, Document};
+use std::collections::HashSet;
+
+fn generate(options: &str, schema: &Bson) -> Vec<Document> {
+    let options: SchemaGenerateOptions = serde_yaml::from_str(options).unwrap();
+    options.documents(Some(schema)).unwrap().collect()
+}
+
+fn schema() -> Bson {
+    Bson::Document(doc! {
+        "bsonType": "object",
+        "required": ["_id", "i", "v", "dec"],
+        "properties": {
+            "_id": { "bsonType": "int" },
+            "i": { "bsonType": "int" },
+            "v": { "anyOf": [ { "bsonType": "string" }, { "bsonType": "long" } ] },
+            "dec": { "bsonType": "decimal" },
+            "opt": { "bsonType": ["bool", "null"] },
+            "arr": {
+                "bsonType": "array",
+                "items": {
+                    "bsonType": "object",
+                    "properties": { "n": { "bsonType": "double" } },
+                    "additionalProperties": false,
+                },
+            },
+        },
+        "additionalProperties": false,
+    })
+}
+
+#[test]
+fn all_branches_are_covered() {
+    let schema = schema();
+    let docs = generate("{ count: 1, seed: 3 }", &schema);
+
+    let schema_doc = schema.as_document().unwrap();
+    for doc in &docs {
+        assert_eq!(
+            Vec::<String>::new(),
+            conform(schema_doc, &Bson::Document(doc.clone()), ""),
+            "{doc}"
+        );
+    }
+
+    let values = |field: &str| {
+        docs.iter()
+            .map(|d| d.get(field).cloned())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        (0..docs.len() as i32)
+            .map(|i| Some(Bson::Int32(i)))
+            .collect::<Vec<_>>(),
+        values("_id")
+    );
+    for expected in [
+        Some(Bson::Int32(i32::MIN)),
+        Some(Bson::Int32(i32::MAX)),
+        Some(Bson::Int32(0)),
+    ] {
+        assert!(values("i").contains(&expected), "{expected:?}");
+    }
+    for expected in [
+        Some(Bson::String(String::new())),
+        Some(Bson::Int64(i64::MIN)),
+        Some(Bson::Int64(i64::MAX)),
+    ] {
+        assert!(values("v").contains(&expected), "{expected:?}");
+    }
+    let mut decimals = values("dec");
+    decimals.sort_by_key(|d| format!("{d:?}"));
+    decimals.dedup();
+    assert_eq!(3, decimals.len(), "{decimals:?}");
+    for expected in [
+        None,
+        Some(Bson::Boolean(true)),
+        Some(Bson::Boolean(false)),
+        Some(Bson::Null),
+    ] {
+        assert!(values("opt").contains(&expected), "{expected:?}");
+    }
+    let arrays = values("arr");
+    assert!(arrays.contains(&Some(Bson::Array(vec![]))));
+    assert!(arrays
+        .iter()
+        .any(|a| matches!(a, Some(Bson::Array(a)) if a.len() == 2)));
+    let elements = arrays
+        .iter()
+        .flatten()
+        .flat_map(|a| a.as_array().unwrap().clone())
+        .collect::<Vec<_>>();
+    assert!(elements.contains(&Bson::Document(doc! {})));
+    assert!(elements.contains(&Bson::Document(doc! {"n": f64::MAX})));
+}
+
+#[test]
+fn count_is_a_minimum() {
+    let schema = schema();
+    let covering = generate("{ count: 1 }", &schema).len();
+    assert!(covering > 1);
+    assert_eq!(
+        covering + 50,
+        generate(&format!("{{ count: {} }}", covering + 50), &schema).len()
+    );
+    assert_eq!(7, generate("{ count: 7, coverage: random }", &schema).len());
+}
+
+#[test]
+fn generation_is_deterministic() {
+    let schema = schema();
+    assert_eq!(
+        generate("{ count: 100, seed: 1 }", &schema),
+        generate("{ count: 100, seed: 1 }", &schema)
+    );
+    assert_ne!(
+        generate("{ count: 100, seed: 1 }", &schema),
+        generate("{ count: 100, seed: 2 }", &schema)
+    );
+}
+
+#[test]
+fn generated_ids_are_unique() {
+    let test_data_files = read_data_files(
+        &["sample_files/sample.yml".to_string()],
+        &FileFilter::default(),
+        false,
+        [],
+    )
+    .unwrap();
+    let entry = test_data_files[0]
+        .dataset
+        .iter()
+        .find(|entry| entry.namespace() == "test.polymorphic")
+        .unwrap();
+    let options = entry
+        .collection
+        .as_ref()
+        .unwrap()
+        .generate_from_schema
+        .as_ref()
+        .unwrap();
+    let docs = options
+        .documents(entry.schema.as_ref())
+        .unwrap()
+        .collect::<Vec<_>>();
+    assert_eq!(50, docs.len());
+    let ids = docs
+        .iter()
+        .map(|d| d.get("_id").unwrap().to_string())
+        .collect::<HashSet<_>>();
+    assert_eq!(docs.len(), ids.len());
+
+    for (id_schema, expected) in [
+        (doc! {}, Bson::Int32(2)),
+        (doc! { "bsonType": "number" }, Bson::Int32(2)),
+        (doc! { "bsonType": ["bool", "long"] }, Bson::Int64(2)),
+        (doc! { "bsonType": "string" }, Bson::String("2".to_string())),
+        (
+            doc! { "bsonType": "objectId" },
+            Bson::ObjectId(ObjectId::from_bytes([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2])),
+        ),
+    ] {
+        let schema = Bson::Document(doc! {
+            "anyOf": [ { "required": ["_id"], "properties": { "_id": id_schema.clone() } } ],
+        });
+        assert_eq!(
+            doc! {"_id": expected},
+            generate("{ count: 3, coverage: random }", &schema)[2],
+            "{id_schema}"
+        );
+    }
+}
+
+#[test]
+fn ungeneratable_schemas_are_reported() {
+    assert_eq!(
+        vec!["generate_from_schema requires the entry to have a schema"],
+        validate_schema_generate_options(None)
+    );
+    assert_eq!(
+        vec![
+            "/properties/a: cannot generate values of bsonType \"symbol\"",
+            "/properties/b: anyOf must not be empty",
+            "/properties/_id: cannot generate unique _id values, _id must have a bsonType in \
+             [\"int\", \"long\", \"double\", \"decimal\", \"string\", \"objectId\"]",
+        ],
+        validate_schema_generate_options(Some(&Bson::Document(doc! {
+            "bsonType": "object",
+            "properties": {
+                "a": { "bsonType": "symbol" },
+                "b": { "anyOf": [] },
+                "_id": { "bsonType": "bool" },
+            },
+        })))
+    );
+
+    let not_documents = [
+        doc! {},
+        doc! { "bsonType": "int" },
+        doc! { "bsonType": ["object", "null"] },
+        doc! { "anyOf": [ { "bsonType": "object" }, { "bsonType": "string" } ] },
+    ];
+    for schema in not_documents {
+        assert_eq!(
+            vec![
+                "/: documents can only be generated for a schema with bsonType \"object\", or an \
+                 anyOf of such schemas"
+            ],
+            validate_schema_generate_options(Some(&Bson::Document(schema.clone()))),
+            "{schema}"
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{
+    csv::{read_csv, CsvOptions},
+    source::read_documents,
+    test::parse,
+    DataLoaderError, Result,
+};
+use mongodb::bson::{doc, Bson, DateTime};
+use std::io::Cursor;
+
+fn read(csv: &str, options: &CsvOptions) -> Result<Vec<Bson>> {
+    read_csv(
+        Cursor::new(csv.to_string()),
+        "test.csv".to_string(),
+        options,
+    )?
+    .collect()
+}
+
+fn error_message<T: std::fmt::Debug>(res: Result<T>) -> String {
+    match res {
+        Err(DataLoaderError::InvalidDocsFile(message)) => message,
+        res => panic!("expected InvalidDocsFile, got {res:?}"),
+    }
+}
+
+#[test]
+fn columns_are_converted_to_their_bson_types() {
+    let options = parse::<CsvOptions>(
+        r#"
+columns:
+  num0: "double"
+  int0: "int"
+  date0: { bsonType: "date", format: "[year]-[month]-[day]" }
+  bool0: "bool"
+  detail.zip: "int"
+null_values: [ "", "NULL" ]
+"#,
+    );
+    let docs = read_documents(
+        "sample_files/external/data/calcs.csv".as_ref(),
+        Some(&options),
+        false,
+    )
+    .unwrap()
+    .collect::<Result<Vec<_>>>()
+    .unwrap();
+
+    assert_eq!(
+        vec![
+            Bson::Document(doc! {
+                "key": "key00",
+                "num0": 12.3,
+                "int0": 1,
+                "date0": DateTime::parse_rfc3339_str("2004-04-15T00:00:00Z").unwrap(),
+                "bool0": true,
+                "str0": "FURNITURE, OFFICE",
+                "detail": { "city": "Seattle", "zip": 98101 },
+            }),
+            Bson::Document(doc! {
+                "key": "key01",
+                "num0": -12.3,
+                "int0": Bson::Null,
+                "date0": DateTime::parse_rfc3339_str("1972-07-04T00:00:00Z").unwrap(),
+                "bool0": false,
+                "str0": "say \"hi\"",
+                "detail": { "city": Bson::Null, "zip": Bson::Null },
+            }),
+            Bson::Document(doc! {
+                "key": "key02",
+                "num0": 15.7,
+                "int0": 3,
+                "date0": Bson::Null,
+                "bool0": true,
+                "str0": "multi\nline",
+                "detail": { "city": "Austin", "zip": 73301 },
+            }),
+        ],
+        docs
+    );
+}
+
+#[test]
+fn long_decimal_and_rfc3339_dates_are_supported() {
+    let options = parse::<CsvOptions>(
+        r#"{ columns: { l: "long", d: "decimal", t: "date" }, delimiter: ";" }"#,
+    );
+    assert_eq!(
+        vec![Bson::Document(doc! {
+            "l": 5_000_000_000i64,
+            "d": "1.50".parse::<mongodb::bson::Decimal128>().unwrap(),
+            "t": DateTime::parse_rfc3339_str("2020-01-02T03:04:05Z").unwrap(),
+        })],
+        read("l;d;t\n5000000000;1.50;2020-01-02T03:04:05Z\n", &options).unwrap()
+    );
+}
+
+#[test]
+fn errors_name_the_line_and_column() {
+    let options = parse::<CsvOptions>(r#"{ columns: { a: "int" } }"#);
+    assert_eq!(
+        "test.csv: line 3: column \"a\": cannot convert \"x\" to int",
+        error_message(read("a,b\n1,2\nx,3\n", &options))
+    );
+    assert_eq!(
+        "test.csv: line 2: expected 2 values, found 3",
+        error_message(read("a,b\n1,2,3\n", &options))
+    );
+    assert_eq!(
+        "test.csv: column \"a\" is not in the header",
+        error_message(read("b\n1\n", &options))
+    );
+    assert_eq!(
+        "test.csv: header \"b\" conflicts with header \"b.c\"",
+        error_message(read("a,b,b.c\n", &CsvOptions::default()))
+    );
+    assert_eq!(
+        "test.csv: delimiter '¦' is not an ASCII character",
+        error_message(read("a¦b\n", &parse::<CsvOptions>(r#"{ delimiter: "¦" }"#)))
+    );
+}
@@ -0,0 +1,36 @@
+use crate::dump::to_fixture_bson;
+use mongodb::bson::{
+    doc, oid::ObjectId, spec::BinarySubtype, Binary, Bson, DateTime, Decimal128, Regex, Timestamp,
+};
+
+#[test]
+fn fixture_bson_round_trips_through_yaml_and_json() {
+    let original = Bson::Document(doc! {
+        "int32": 1,
+        "negative_int32": -1,
+        "int64": 1i64,
+        "double": 1.0,
+        "nan": f64::NAN,
+        "string": "s",
+        "bool": true,
+        "null": Bson::Null,
+        "oid": ObjectId::parse_str("5f0f1b9b9b9b9b9b9b9b9b9b").unwrap(),
+        "date": DateTime::from_millis(1_600_000_000_000),
+        "decimal": "1.50".parse::<Decimal128>().unwrap(),
+        "binary": Binary { subtype: BinarySubtype::Generic, bytes: vec![1, 2, 3] },
+        "regex": Regex { pattern: "^a".to_string(), options: "i".to_string() },
+        "timestamp": Timestamp { time: 1, increment: 2 },
+        "minKey": Bson::MinKey,
+        "maxKey": Bson::MaxKey,
+        "nested": { "array": [1i64, { "x": 2i64 }] },
+    });
+
+    let fixture = to_fixture_bson(original.clone());
+
+    let from_yaml: Bson = serde_yaml::from_str(&serde_yaml::to_string(&fixture).unwrap()).unwrap();
+    let from_json: Bson = serde_json::from_str(&serde_json::to_string(&fixture).unwrap()).unwrap();
+
+    // NaN is never equal to itself, so compare the debug representations.
+    assert_eq!(format!("{original:?}"), format!("{from_yaml:?}"));
+    assert_eq!(format!("{original:?}"), format!("{from_json:?}"));
+}
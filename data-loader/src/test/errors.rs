@@ -0,0 +1,140 @@
+use crate::{
+    read_and_check, test::write, validate::validate, DataLoaderError, FileFilter, TestDataFile,
+};
+use std::{io, path::Path};
+
+fn read(path: &Path, all_errors: bool) -> Result<Vec<TestDataFile>, DataLoaderError> {
+    read_and_check(
+        &[path.display().to_string()],
+        &FileFilter::default(),
+        false,
+        None,
+        all_errors,
+        validate,
+    )
+}
+
+const UNPARSEABLE: &str = "dataset:\n  - db: test\n    collection: [\n";
+
+const INVALID_ENTRY: &str = r#"
+dataset:
+  - db: test
+    collection:
+      name: a
+  - db: test
+"#;
+
+const INVALID_INDEX: &str = r#"
+dataset:
+  - db: test
+    collection:
+      name: b
+      indexes:
+        - { key: { a: 0 } }
+"#;
+
+#[test]
+fn invalid_entries_are_named_by_index() {
+    let dir = write("errors_entry", &[("a.yml", INVALID_ENTRY)]);
+    match read(&dir, false) {
+        Err(e @ DataLoaderError::InvalidViewOrCollectionDataEntry(_)) => assert_eq!(
+            format!(
+                "Each entry must specify exactly one of 'view', 'collection', or 'tpch', but {}, entry 1 does not",
+                dir.join("a.yml").display()
+            ),
+            e.to_string()
+        ),
+        res => panic!("expected InvalidViewOrCollectionDataEntry, got {res:?}"),
+    }
+}
+
+#[test]
+fn the_first_error_stops_the_run_by_default() {
+    let dir = write(
+        "errors_first",
+        &[
+            ("a.yml", UNPARSEABLE),
+            ("b.yml", INVALID_ENTRY),
+            ("c.yml", INVALID_INDEX),
+        ],
+    );
+    match read(&dir, false) {
+        Err(DataLoaderError::InvalidDataFile(message)) => assert!(
+            message.starts_with(&format!(
+                "{}, line 3, column 17: ",
+                dir.join("a.yml").display()
+            )),
+            "{message}"
+        ),
+        res => panic!("expected InvalidDataFile, got {res:?}"),
+    }
+}
+
+#[test]
+fn all_errors_collects_every_error_across_files() {
+    let dir = write(
+        "errors_all",
+        &[
+            ("a.yml", UNPARSEABLE),
+            ("b.yml", INVALID_ENTRY),
+            ("c.yml", INVALID_INDEX),
+        ],
+    );
+    let errors = match read(&dir, true) {
+        Err(DataLoaderError::InvalidTestData(errors)) => errors,
+        res => panic!("expected InvalidTestData, got {res:?}"),
+    };
+    assert_eq!(3, errors.len(), "{errors:#?}");
+    assert!(errors[0].starts_with(&format!(
+        "Failed to parse data file {}, line 3, column 17: ",
+        dir.join("a.yml").display()
+    )));
+    assert!(errors[1].ends_with(&format!(
+        "but {}, entry 1 does not",
+        dir.join("b.yml").display()
+    )));
+    assert!(errors[2].starts_with(&format!(
+        "{}, entry 0: index 0: ",
+        dir.join("c.yml").display()
+    )));
+}
+
+#[test]
+fn all_errors_succeeds_when_there_are_none() {
+    let dir = write("errors_none", &[("c.yml", "dataset: []\n")]);
+    assert_eq!(1, read(&dir, true).unwrap().len());
+}
+
+#[test]
+fn errors_without_a_location_are_given_context() {
+    let dir = write("errors_missing", &[]);
+    let missing = dir.join("missing.yml");
+    let err = read(&missing, false).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with(&format!("{}: ", missing.display())),
+        "{err}"
+    );
+
+    let err = DataLoaderError::from(io::Error::other("boom")).context("a.yml, entry 2 (db.c)");
+    assert_eq!("a.yml, entry 2 (db.c): boom", err.to_string());
+}
+
+#[test]
+fn included_entries_are_named_by_their_own_file() {
+    let dir = write(
+        "errors_include",
+        &[
+            ("a.yml", "include: [c.yml]\ndataset: []\n"),
+            ("c.yml", INVALID_INDEX),
+        ],
+    );
+    let errors = match read(&dir.join("a.yml"), false) {
+        Err(DataLoaderError::InvalidTestData(errors)) => errors,
+        res => panic!("expected InvalidTestData, got {res:?}"),
+    };
+    assert!(
+        errors[0].starts_with(&format!("{}, entry 0: ", dir.join("c.yml").display())),
+        "{errors:?}"
+    );
+}
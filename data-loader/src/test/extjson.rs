@@ -0,0 +1,175 @@
+use crate::{
+    read_data_files, source::read_documents, test::write, DataLoaderError, FileFilter, TestDataFile,
+};
+use mongodb::bson::{doc, Bson};
+
+fn read(path: &str, strict: bool) -> Result<Vec<TestDataFile>, DataLoaderError> {
+    read_data_files(&[path.to_string()], &FileFilter::default(), strict, None)
+}
+
+fn invalid_data_file(result: Result<Vec<TestDataFile>, DataLoaderError>) -> String {
+    match result {
+        Err(DataLoaderError::InvalidDataFile(message)) => message,
+        Err(e) => panic!("expected InvalidDataFile, got {e:?}"),
+        Ok(_) => panic!("expected InvalidDataFile"),
+    }
+}
+
+#[test]
+fn strict_mode_accepts_the_sample_files() {
+    let test_data_files = read("sample_files", true).unwrap();
+    assert!(!test_data_files.is_empty());
+    assert!(test_data_files
+        .iter()
+        .flat_map(|tdf| &tdf.dataset)
+        .filter_map(|entry| entry.collection.as_ref())
+        .all(|c| c.strict_extended_json));
+}
+
+#[test]
+fn strict_mode_accepts_every_bson_type() {
+    let path = write(
+        "extjson_corpus",
+        &[("bson_types.yml", include_str!("bson_types.yml"))],
+    )
+    .join("bson_types.yml");
+    let strict = read(path.to_str().unwrap(), true).unwrap();
+    let lenient = read(path.to_str().unwrap(), false).unwrap();
+    // Compare the debug representations, since NaN is not equal to itself.
+    assert_eq!(
+        format!(
+            "{:?}",
+            lenient[0].dataset[0].collection.as_ref().unwrap().docs
+        ),
+        format!(
+            "{:?}",
+            strict[0].dataset[0].collection.as_ref().unwrap().docs
+        )
+    );
+}
+
+#[test]
+fn strict_mode_rejects_malformed_wrappers_with_their_location() {
+    let cases = [
+        (
+            "typo",
+            "{\"$dat\": \"2020-01-01T00:00:00Z\"}",
+            "is not an Extended JSON v2 type wrapper",
+            true,
+        ),
+        (
+            "unquoted_long",
+            "{\"$numberLong\": 1}",
+            "malformed Extended JSON $numberLong: expected a string holding a 64-bit integer",
+            false,
+        ),
+        (
+            "legacy_binary",
+            "{\"$binary\": \"AQID\", \"$type\": \"00\"}",
+            "is not an Extended JSON v2 type wrapper",
+            false,
+        ),
+        (
+            "stray_operator",
+            "{\"a\": 1, \"$b\": 2}",
+            "field name \"$b\" starts with '$' but the document is not an Extended JSON type wrapper",
+            true,
+        ),
+    ];
+    for (name, value, expected, lenient) in cases {
+        let yaml = format!(
+            "dataset:\n  - db: db\n    collection:\n      name: c\n      docs:\n        - _id: 1\n          v: {value}\n"
+        );
+        let path = write(&format!("extjson_{name}"), &[("data.yml", &yaml)]).join("data.yml");
+        let message = invalid_data_file(read(path.to_str().unwrap(), true));
+        let location = format!("{}, line 7, column ", path.display());
+        assert!(message.starts_with(&location), "{name}: {message}");
+        assert!(message.contains(expected), "{name}: {message}");
+
+        // Without strict mode, the document is read as before.
+        assert_eq!(
+            lenient,
+            read(path.to_str().unwrap(), false).is_ok(),
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn included_files_are_read_in_the_same_mode() {
+    let path = write(
+        "extjson_include",
+        &[
+            ("data.yml", "include: [ \"included.yml\" ]\n"),
+            (
+                "included.yml",
+                "dataset:\n  - db: db\n    collection: { name: c, docs: [ { v: { $dat: 1 } } ] }\n",
+            ),
+        ],
+    )
+    .join("data.yml");
+
+    let message = invalid_data_file(read(path.to_str().unwrap(), true));
+    assert!(
+        message.contains("is not an Extended JSON v2 type wrapper"),
+        "{message}"
+    );
+    assert!(read(path.to_str().unwrap(), false).is_ok());
+}
+
+#[test]
+fn json_data_files_are_reported_with_their_location() {
+    let path = write(
+        "extjson_json",
+        &[(
+            "data.json",
+            "{\"dataset\": [{\"db\": \"db\", \"collection\": {\"name\": \"c\",\n  \"docs\": [{\"_id\": {\"$oid\": \"xyz\"}}]}}]}",
+        )],
+    )
+    .join("data.json");
+    let message = invalid_data_file(read(path.to_str().unwrap(), true));
+    assert!(
+        message.starts_with(&format!("{}, line 2, column ", path.display())),
+        "{message}"
+    );
+    assert!(
+        message.contains("malformed Extended JSON $oid: expected a string of 24 hex digits"),
+        "{message}"
+    );
+    assert!(!message.contains(" at line "), "{message}");
+}
+
+#[test]
+fn strict_docs_files_are_reported_with_their_location() {
+    let path = write(
+        "extjson_docs",
+        &[(
+            "docs.jsonl",
+            "{\"_id\": 1, \"d\": {\"$date\": \"2020-01-01T00:00:00Z\"}}\n{\"_id\": 2, \"d\": {\"$dat\": \"2020-01-01T00:00:00Z\"}}\n",
+        )],
+    )
+    .join("docs.jsonl");
+
+    let docs = read_documents(&path, None, false)
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        Bson::Document(doc! {"$dat": "2020-01-01T00:00:00Z"}),
+        docs[1].as_document().unwrap().get("d").cloned().unwrap()
+    );
+
+    let docs = read_documents(&path, None, true)
+        .unwrap()
+        .collect::<Vec<_>>();
+    assert!(docs[0].is_ok());
+    let message = docs[1].as_ref().unwrap_err().to_string();
+    assert!(
+        message.contains(&format!("{}, line 2, column ", path.display())),
+        "{message}"
+    );
+    assert!(
+        message.contains("is not an Extended JSON v2 type wrapper"),
+        "{message}"
+    );
+}
@@ -0,0 +1,132 @@
+use crate::{
+    fingerprint::{add_dependent_views, changed_namespaces, fingerprint, schema_target},
+    test::parse,
+    Mode, TestDataFile,
+};
+use mongodb::Client;
+use std::collections::{HashMap, HashSet};
+
+#[test]
+fn fingerprint_is_deterministic() {
+    let entry = parse(r#"{ db: "test", collection: { name: "foo", docs: [ { _id: 1 } ] } }"#);
+    assert_eq!(
+        fingerprint(&entry, "mongod").unwrap(),
+        fingerprint(&entry.clone(), "mongod").unwrap()
+    );
+}
+
+#[test]
+fn fingerprint_covers_docs_indexes_and_schema() {
+    let base = fingerprint(
+        &parse(r#"{ db: "test", collection: { name: "foo", docs: [ { _id: 1 } ] } }"#),
+        "mongod",
+    )
+    .unwrap();
+
+    for changed in [
+        r#"{ db: "test", collection: { name: "foo", docs: [ { _id: 2 } ] } }"#,
+        r#"{ db: "test", collection: { name: "foo", docs: [ { _id: 1 } ], indexes: [ { key: { a: 1 } } ] } }"#,
+        r#"{ db: "test", collection: { name: "foo", docs: [ { _id: 1 } ] }, schema: { bsonType: "object" } }"#,
+        r#"{ db: "other", collection: { name: "foo", docs: [ { _id: 1 } ] } }"#,
+    ] {
+        assert_ne!(
+            base,
+            fingerprint(&parse(changed), "mongod").unwrap(),
+            "{changed}"
+        );
+    }
+}
+
+#[test]
+fn views_on_changed_namespaces_are_changed() {
+    let test_data_file: TestDataFile = serde_yaml::from_str(
+        r#"
+dataset:
+  - { db: "test", collection: { name: "base", docs: [] } }
+  - { db: "test", view: { name: "on_view", view_on: "on_base", pipeline: [] } }
+  - { db: "test", view: { name: "on_base", view_on: "base", pipeline: [] } }
+  - { db: "test", view: { name: "on_other", view_on: "other", pipeline: [] } }
+  - { db: "test", view: { name: "adf_view" } }
+  - { db: "other", view: { name: "on_base", view_on: "base", pipeline: [] } }
+"#,
+    )
+    .unwrap();
+    let test_data_files = [test_data_file];
+
+    let mut changed = HashSet::from(["test.base".to_string()]);
+    assert_eq!(
+        vec!["test.on_base", "test.on_view"],
+        add_dependent_views(&test_data_files, &mut changed)
+    );
+    assert_eq!(
+        HashSet::from(["test.base", "test.on_base", "test.on_view"].map(String::from)),
+        changed
+    );
+
+    let mut changed = HashSet::from(["test.on_base".to_string(), "test.other".to_string()]);
+    assert_eq!(
+        vec!["test.on_view", "test.on_other"],
+        add_dependent_views(&test_data_files, &mut changed)
+    );
+
+    let mut changed = HashSet::new();
+    assert!(add_dependent_views(&test_data_files, &mut changed).is_empty());
+    assert!(changed.is_empty());
+}
+
+#[tokio::test]
+async fn switching_modes_changes_every_entry() {
+    // The client is only used to tell the modes apart; it is never contacted.
+    let adf_client = Client::with_uri_str("mongodb://localhost:1").await.unwrap();
+    let adf = Mode::Adf(adf_client);
+    let targets = [
+        schema_target(&Mode::Mongod, None),
+        schema_target(&Mode::Mongod, Some("localmongo")),
+        schema_target(&adf, None),
+        schema_target(&adf, Some("localmongo")),
+        schema_target(&adf, Some("other")),
+    ];
+    assert_eq!(
+        [
+            "mongod",
+            "mongod",
+            "adf",
+            "adf with views in localmongo",
+            "adf with views in other"
+        ],
+        targets
+    );
+
+    let test_data_file: TestDataFile = parse(
+        r#"
+dataset:
+  - { db: "test", collection: { name: "a", docs: [ { _id: 1 } ] } }
+  - { db: "test", view: { name: "v", view_on: "a", pipeline: [] } }
+"#,
+    );
+    let test_data_files = [test_data_file];
+    let existing = HashMap::from([(
+        "test".to_string(),
+        HashSet::from(["a".to_string(), "v".to_string()]),
+    )]);
+    for loaded in &targets[1..] {
+        let recorded = test_data_files[0]
+            .dataset
+            .iter()
+            .map(|entry| (entry.namespace(), fingerprint(entry, loaded).unwrap()))
+            .collect::<HashMap<_, _>>();
+        for target in &targets[1..] {
+            let changed =
+                changed_namespaces(&test_data_files, target, &recorded, &existing).unwrap();
+            if target == loaded {
+                assert!(changed.is_empty(), "{loaded} -> {target}: {changed:?}");
+            } else {
+                assert_eq!(
+                    HashSet::from(["test.a", "test.v"].map(String::from)),
+                    changed,
+                    "{loaded} -> {target}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::{
+    generate::{validate_generate_options, GenerateOptions},
+    test::parse,
+    DataLoaderError,
+};
+use mongodb::bson::{doc, Bson, DateTime};
+
+fn generate(yaml: &str) -> Vec<Bson> {
+    parse::<GenerateOptions>(yaml)
+        .documents()
+        .unwrap()
+        .collect()
+}
+
+const OPTIONS: &str = r#"
+count: 200
+seed: 42
+fields:
+  _id: { sequence: { start: 10, step: 5 } }
+  i: { range: { min: -3, max: 3 } }
+  d: { range: { min: 0, max: 1.5 } }
+  c: { choice: [ "x", 1, { y: true } ] }
+  s: { string: { min_length: 2, max_length: 4, alphabet: "ab" } }
+  t: { date: { min: "2020-01-01T00:00:00Z", max: "2020-01-02T00:00:00Z" } }
+  o: { object: { n: { constant: null }, k: { constant: 1 } } }
+  a: { array: { max_length: 3, items: { sequence: {} } } }
+"#;
+
+#[test]
+fn generation_is_deterministic() {
+    assert_eq!(generate(OPTIONS), generate(OPTIONS));
+    assert_ne!(
+        generate(OPTIONS),
+        generate(&OPTIONS.replace("seed: 42", "seed: 43"))
+    );
+}
+
+#[test]
+fn generated_values_follow_their_generators() {
+    let docs = generate(OPTIONS);
+    assert_eq!(200, docs.len());
+
+    let min_date = DateTime::parse_rfc3339_str("2020-01-01T00:00:00Z").unwrap();
+    let max_date = DateTime::parse_rfc3339_str("2020-01-02T00:00:00Z").unwrap();
+    let mut array_items = vec![];
+    for (n, doc) in docs.iter().enumerate() {
+        let doc = doc.as_document().unwrap();
+        assert_eq!(
+            vec!["_id", "i", "d", "c", "s", "t", "o", "a"],
+            doc.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(10 + 5 * n as i32, doc.get_i32("_id").unwrap());
+        assert!((-3..=3).contains(&doc.get_i32("i").unwrap()));
+        assert!((0.0..=1.5).contains(&doc.get_f64("d").unwrap()));
+        assert!(
+            [Bson::from("x"), Bson::from(1), Bson::from(doc! {"y": true})]
+                .contains(doc.get("c").unwrap())
+        );
+        let s = doc.get_str("s").unwrap();
+        assert!((2..=4).contains(&s.len()) && s.chars().all(|c| c == 'a' || c == 'b'));
+        let t = *doc.get_datetime("t").unwrap();
+        assert!(min_date <= t && t <= max_date);
+        assert_eq!(
+            &doc! {"n": Bson::Null, "k": 1},
+            doc.get_document("o").unwrap()
+        );
+        let a = doc.get_array("a").unwrap();
+        assert!(a.len() <= 3);
+        array_items.extend(a.iter().cloned());
+    }
+
+    // A sequence continues across every array it generates items for.
+    assert_eq!(
+        (0..array_items.len() as i32)
+            .map(Bson::Int32)
+            .collect::<Vec<_>>(),
+        array_items
+    );
+}
+
+#[test]
+fn null_and_missing_probabilities_apply() {
+    let docs = generate(
+        r#"
+count: 5
+fields:
+  always_null: { constant: 1, null_probability: 1.0 }
+  always_missing: { constant: 1, missing_probability: 1.0 }
+  never: { constant: 1, null_probability: 0.0 }
+"#,
+    );
+    for doc in docs {
+        assert_eq!(
+            Bson::Document(doc! {"always_null": Bson::Null, "never": 1}),
+            doc
+        );
+    }
+}
+
+#[test]
+fn invalid_generators_are_reported() {
+    let options = parse(
+        r#"
+count: 1
+fields:
+  r: { range: { min: 5, max: 1 } }
+  c: { choice: [] }
+  o: { object: { s: { string: { min_length: 3, max_length: 1 } } } }
+  p: { constant: 1, null_probability: 2 }
+  d: { date: { min: "yesterday", max: "2020-01-01T00:00:00Z" } }
+"#,
+    );
+    let expected = vec![
+        "field \"r\": range min 5 is greater than max 1".to_string(),
+        "field \"c\": choice must list at least one value".to_string(),
+        "field \"o.s\": string min_length 3 is greater than max_length 1".to_string(),
+        "field \"p\": null_probability must be between 0 and 1".to_string(),
+        "field \"d\": date bounds must be RFC 3339 strings, found \"yesterday\" and \"2020-01-01T00:00:00Z\"".to_string(),
+    ];
+    assert_eq!(expected, validate_generate_options(&options));
+    match options.documents() {
+        Err(DataLoaderError::InvalidGenerator(errors)) => assert_eq!(expected, errors),
+        Err(e) => panic!("expected InvalidGenerator, got {e:?}"),
+        Ok(_) => panic!("expected InvalidGenerator"),
+    }
+}
+
+#[test]
+fn non_finite_and_overflowing_ranges_are_reported() {
+    let options = parse(
+        r#"
+count: 1
+fields:
+  inf: { range: { min: 0, max: .inf } }
+  neg: { range: { min: -.inf, max: 1.5 } }
+  nan: { range: { min: .nan, max: 1 } }
+  wide: { range: { min: -1.0e308, max: 1.0e308 } }
+"#,
+    );
+    let expected = vec![
+        "field \"inf\": range bounds must be finite, found 0 and inf".to_string(),
+        "field \"neg\": range bounds must be finite, found -inf and 1.5".to_string(),
+        "field \"nan\": range bounds must be finite, found NaN and 1".to_string(),
+        "field \"wide\": range is too wide: max - min must be finite".to_string(),
+    ];
+    assert_eq!(expected, validate_generate_options(&options));
+    assert!(matches!(
+        options.documents(),
+        Err(DataLoaderError::InvalidGenerator(_))
+    ));
+}
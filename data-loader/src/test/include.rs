@@ -0,0 +1,198 @@
+use crate::{read_data_files, test::write, DataLoaderError, FileFilter, TestDataFile};
+use mongodb::bson::{doc, Bson};
+use std::path::{Path, PathBuf};
+
+/// read reads the data file at the provided path.
+fn read(path: &Path) -> Result<Vec<TestDataFile>, DataLoaderError> {
+    read_data_files(
+        &[path.display().to_string()],
+        &FileFilter::default(),
+        false,
+        None,
+    )
+}
+
+fn errors(result: Result<Vec<TestDataFile>, DataLoaderError>) -> Vec<String> {
+    match result {
+        Err(DataLoaderError::UnresolvedReference(errors)) => errors,
+        Err(e) => panic!("expected UnresolvedReference, got {e:?}"),
+        Ok(_) => panic!("expected UnresolvedReference"),
+    }
+}
+
+#[test]
+fn includes_and_references_are_resolved() {
+    let test_data_files = read_data_files(
+        &["sample_files/include".to_string()],
+        &FileFilter::default(),
+        false,
+        None,
+    )
+    .unwrap();
+    assert_eq!(1, test_data_files.len());
+
+    let tdf = &test_data_files[0];
+    assert_eq!(
+        vec![
+            PathBuf::from("sample_files/include/shared/calcs.yml")
+                .canonicalize()
+                .unwrap(),
+            PathBuf::from("sample_files/include/shared/fragments.yml")
+                .canonicalize()
+                .unwrap(),
+        ],
+        tdf.include
+    );
+    assert_eq!(
+        vec!["include.calcs_view", "include.calcs"],
+        tdf.dataset
+            .iter()
+            .map(|entry| entry.namespace())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        Some(Bson::from(doc! {
+            "bsonType": "object",
+            "required": ["_id"],
+            "properties": {
+                "_id": {"bsonType": "int"},
+                "num": {"bsonType": ["double", "null"]},
+            },
+            "additionalProperties": false,
+        })),
+        tdf.dataset[0].schema
+    );
+    assert_eq!(
+        Some(Bson::from(doc! {
+            "bsonType": "object",
+            "required": ["_id", "str"],
+            "properties": {
+                "_id": {"bsonType": "int"},
+                "num": {"bsonType": ["double", "null"]},
+                "str": {"bsonType": "string"},
+            },
+            "additionalProperties": false,
+        })),
+        tdf.dataset[1].schema
+    );
+}
+
+#[test]
+fn schema_files_are_not_read_as_data_files() {
+    // The fragments are named like data file fields, so reading the schema file as a data file
+    // fails.
+    let dir = write(
+        "include_schema_file",
+        &[
+            (
+                "data.yml",
+                r#"
+schema_file: "fragments.yml"
+dataset:
+  - db: "test"
+    collection: { name: "a", docs: [ { _id: 1 } ] }
+    schema: { bsonType: "object", properties: { _id: { $ref: "dataset" } } }
+"#,
+            ),
+            (
+                "fragments.yml",
+                "dataset: { bsonType: \"int\" }\nusers: {}\n",
+            ),
+        ],
+    );
+    let test_data_files = read(&dir.join("data.yml")).unwrap();
+    assert_eq!(1, test_data_files.len());
+    assert_eq!(
+        Some(Bson::from(doc! {
+            "bsonType": "object",
+            "properties": { "_id": { "bsonType": "int" } },
+        })),
+        test_data_files[0].dataset[0].schema
+    );
+
+    let test_data_files = read(&dir).unwrap();
+    assert_eq!(
+        vec![dir.join("data.yml")],
+        test_data_files
+            .iter()
+            .map(|tdf| tdf.path.clone())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn files_without_a_dataset_only_define_fragments() {
+    let dir = write(
+        "include_fragments_only",
+        &[(
+            "fragments.yml",
+            "include: []\nschemas:\n  id: { bsonType: \"int\" }\n",
+        )],
+    );
+    let test_data_files = read(&dir.join("fragments.yml")).unwrap();
+    assert_eq!(1, test_data_files.len());
+    assert!(test_data_files[0].dataset.is_empty());
+    assert_eq!(
+        vec!["id".to_string()],
+        test_data_files[0]
+            .schemas
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn include_cycles_are_reported() {
+    let dir = write(
+        "include_cycle",
+        &[
+            ("a.yml", "include: [ b.yml ]"),
+            ("b.yml", "include: [ a.yml ]"),
+        ],
+    );
+    let errors = errors(read(&dir.join("a.yml")));
+    let canonical = |file: &str| dir.join(file).canonicalize().unwrap().display().to_string();
+    assert_eq!(
+        vec![format!(
+            "{}: include {}: include cycle {} -> {} -> {}",
+            dir.join("b.yml").display(),
+            dir.join("a.yml").display(),
+            canonical("a.yml"),
+            canonical("b.yml"),
+            canonical("a.yml"),
+        )],
+        errors
+    );
+}
+
+#[test]
+fn unresolved_references_are_reported() {
+    let dir = write(
+        "include_refs",
+        &[(
+            "refs.yml",
+            r##"
+schemas:
+  a: { $ref: "b" }
+  b: { items: [ { $ref: "#/schemas/a" } ] }
+dataset:
+  - { db: "test", view: { name: "v0" }, schema: { $ref: "missing" } }
+  - { db: "test", view: { name: "v1" }, schema: { $ref: "a" } }
+  - { db: "test", view: { name: "v2" }, schema: { $ref: "b", bsonType: "object" } }
+  - { db: "test", view: { name: "v3" }, schema: { properties: { x: { $ref: 1 } } } }
+"##,
+        )],
+    );
+    let errors = errors(read(&dir.join("refs.yml")));
+    let file = dir.join("refs.yml").display().to_string();
+    assert_eq!(
+        vec![
+            format!("{file}, entry 0: schema: $ref \"missing\" is not a defined schema fragment"),
+            format!("{file}, entry 1: schema: $ref cycle a -> b -> a"),
+            format!("{file}, entry 2: schema: $ref \"b\" must not have other keywords"),
+            format!("{file}, entry 3: schema: $ref must be a string, found 1"),
+        ],
+        errors
+    );
+}
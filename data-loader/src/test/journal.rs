@@ -0,0 +1,197 @@
+use crate::{
+    journal::{Journal, Operation},
+    DataLoaderError, RoleData, RoleName, UserData,
+};
+use mongodb::{
+    bson::{doc, Bson},
+    Client,
+};
+
+fn created(name: &str) -> Operation {
+    Operation::CreatedNamespace {
+        db: "test".to_string(),
+        name: name.to_string(),
+    }
+}
+
+fn indexes(name: &str) -> Operation {
+    Operation::CreatedIndexes {
+        db: "test".to_string(),
+        name: name.to_string(),
+        index_names: vec!["a_1".to_string()],
+    }
+}
+
+fn backed_up(name: &str) -> Operation {
+    Operation::BackedUpCollection {
+        db: "test".to_string(),
+        name: name.to_string(),
+    }
+}
+
+fn dropped_role() -> Operation {
+    Operation::DroppedRole(RoleData {
+        db: "test".to_string(),
+        role: "reader".to_string(),
+        privileges: vec![doc! {"resource": {"db": "test", "collection": "a"}, "actions": ["find"]}],
+        roles: vec![],
+    })
+}
+
+fn dropped_user() -> Operation {
+    Operation::DroppedUser(UserData {
+        db: "admin".to_string(),
+        user: "u".to_string(),
+        password: "pencil".to_string(),
+        mechanisms: vec![],
+        roles: vec![RoleName::Name("readAnyDatabase".to_string())],
+    })
+}
+
+fn deleted_schema(name: &str) -> Operation {
+    Operation::DeletedDocument {
+        db: "test".to_string(),
+        collection: "__sql_schemas".to_string(),
+        document: doc! {"_id": name, "schema": {}},
+    }
+}
+
+fn inserted_schema(name: &str) -> Operation {
+    Operation::InsertedDocument {
+        db: "test".to_string(),
+        collection: "__sql_schemas".to_string(),
+        id: Bson::String(name.to_string()),
+    }
+}
+
+#[test]
+fn operations_are_undone_most_recent_first() {
+    let journal = Journal::default();
+    let operations = vec![
+        backed_up("a"),
+        Operation::BackedUpTimeseries {
+            db: "test".to_string(),
+            name: "readings".to_string(),
+            options: doc! {"timeseries": {"timeField": "t"}},
+        },
+        Operation::DroppedView {
+            db: "test".to_string(),
+            name: "v".to_string(),
+            view_on: "a".to_string(),
+            pipeline: vec![doc! {"$match": {}}],
+        },
+        created("a"),
+        created("v"),
+        dropped_user(),
+        dropped_role(),
+        Operation::CreatedRole {
+            db: "test".to_string(),
+            role: "reader".to_string(),
+        },
+        Operation::CreatedUser {
+            db: "admin".to_string(),
+            user: "u".to_string(),
+        },
+    ];
+    for operation in &operations {
+        journal.record(operation.clone());
+    }
+
+    assert_eq!(
+        operations.into_iter().rev().collect::<Vec<_>>(),
+        journal.undo_plan()
+    );
+}
+
+#[test]
+fn document_operations_are_undone_after_namespace_operations() {
+    let journal = Journal::default();
+    for operation in [
+        deleted_schema("a"),
+        backed_up("a"),
+        created("a"),
+        inserted_schema("a"),
+        deleted_schema("b"),
+        created("b"),
+    ] {
+        journal.record(operation);
+    }
+
+    assert_eq!(
+        vec![
+            created("b"),
+            created("a"),
+            backed_up("a"),
+            deleted_schema("b"),
+            inserted_schema("a"),
+            deleted_schema("a"),
+        ],
+        journal.undo_plan()
+    );
+}
+
+#[test]
+fn indexes_on_created_namespaces_are_not_dropped_separately() {
+    let journal = Journal::default();
+    for operation in [created("a"), indexes("a"), indexes("existing")] {
+        journal.record(operation);
+    }
+
+    assert_eq!(vec![indexes("existing"), created("a")], journal.undo_plan());
+}
+
+#[test]
+fn an_empty_journal_undoes_nothing() {
+    assert_eq!(Vec::<Operation>::new(), Journal::default().undo_plan());
+}
+
+#[tokio::test]
+async fn rollback_continues_after_failures_and_clears_the_journal() {
+    // Nothing listens on this port, so every operation fails to be undone.
+    let client = Client::with_uri_str("mongodb://localhost:1/?serverSelectionTimeoutMS=50")
+        .await
+        .unwrap();
+    let journal = Journal::default();
+    for operation in [backed_up("a"), created("b"), deleted_schema("b")] {
+        journal.record(operation);
+    }
+
+    let errors = match journal.rollback(&client).await {
+        Err(DataLoaderError::Rollback(errors)) => errors,
+        res => panic!("expected Rollback, got {res:?}"),
+    };
+    assert_eq!(3, errors.len(), "{errors:?}");
+    assert!(errors[0].starts_with("dropping test.b: "), "{errors:?}");
+    assert!(
+        errors[1].starts_with("restoring test.a from its backup: "),
+        "{errors:?}"
+    );
+    assert!(errors[2].starts_with("restoring documents: "), "{errors:?}");
+    assert_eq!(Vec::<Operation>::new(), journal.undo_plan());
+}
+
+#[tokio::test]
+async fn rollback_recreates_dropped_users_and_roles() {
+    // Nothing listens on this port, so every operation fails to be undone.
+    let client = Client::with_uri_str("mongodb://localhost:1/?serverSelectionTimeoutMS=50")
+        .await
+        .unwrap();
+    let journal = Journal::default();
+    for operation in [dropped_user(), dropped_role()] {
+        journal.record(operation);
+    }
+
+    let errors = match journal.rollback(&client).await {
+        Err(DataLoaderError::Rollback(errors)) => errors,
+        res => panic!("expected Rollback, got {res:?}"),
+    };
+    assert_eq!(2, errors.len(), "{errors:?}");
+    assert!(
+        errors[0].starts_with("recreating role reader in test: "),
+        "{errors:?}"
+    );
+    assert!(
+        errors[1].starts_with("recreating user u in admin: "),
+        "{errors:?}"
+    );
+}
@@ -0,0 +1,187 @@
+use crate::{
+    test::write, CollectionData, DataLoader, DataLoaderError, Event, Mode, TestDataEntry,
+    TestDataFile, ViewData,
+};
+use glob::Pattern;
+use mongodb::{
+    bson::{bson, doc},
+    Client,
+};
+use std::sync::{Arc, Mutex};
+
+fn namespaces(loader: &DataLoader) -> Vec<String> {
+    loader
+        .test_data_files()
+        .iter()
+        .flat_map(|tdf| tdf.dataset.iter().map(TestDataEntry::namespace))
+        .collect()
+}
+
+const A: &str =
+    "dataset:\n  - db: test\n    collection:\n      name: a\n      docs: [{ _id: 1 }]\n";
+const B: &str = "dataset:\n  - db: test\n    collection:\n      name: b\n      docs: []\n";
+
+#[test]
+fn build_reads_targets_and_in_memory_files() {
+    let dir = write("loader_build", &[("a.yml", A), ("b.yml", B)]);
+    let view = TestDataFile::new(
+        "in memory",
+        vec![
+            TestDataEntry::collection("mem", CollectionData::new("c", vec![bson!({"_id": 1})])),
+            TestDataEntry::view(
+                "mem",
+                ViewData::new("v", "c", vec![doc! {"$match": {"_id": 1}}]),
+            ),
+        ],
+    );
+    let loader = DataLoader::builder()
+        .target(&dir)
+        .exclude(Pattern::new("b.yml").unwrap())
+        .data_file(view)
+        .build()
+        .unwrap();
+    assert_eq!(vec!["test.a", "mem.c", "mem.v"], namespaces(&loader));
+}
+
+#[test]
+fn build_checks_documents_unless_skipped() {
+    let schema = doc! {
+        "bsonType": "object",
+        "properties": {"_id": {"bsonType": "string"}},
+    };
+    let file = || {
+        TestDataFile::new(
+            "in memory",
+            vec![TestDataEntry::collection(
+                "db",
+                CollectionData::new("c", vec![bson!({"_id": 1})]),
+            )
+            .with_schema(schema.clone())],
+        )
+    };
+
+    let res = DataLoader::builder().data_file(file()).build();
+    assert!(
+        matches!(res, Err(DataLoaderError::InvalidTestData(_))),
+        "{res:?}"
+    );
+
+    let loader = DataLoader::builder()
+        .data_file(file())
+        .skip_document_validation(true)
+        .build()
+        .unwrap();
+    assert_eq!(vec!["db.c"], namespaces(&loader));
+}
+
+#[test]
+fn build_runs_the_checks_a_load_depends_on() {
+    let file = TestDataFile::new(
+        "in memory",
+        vec![
+            TestDataEntry::collection("db", CollectionData::new("c", vec![])),
+            TestDataEntry::collection("db", CollectionData::new("c", vec![])),
+            TestDataEntry::view("db", ViewData::new("v", "v", vec![])),
+        ],
+    );
+
+    let errors = match DataLoader::builder()
+        .data_file(file)
+        .skip_document_validation(true)
+        .build()
+    {
+        Err(DataLoaderError::InvalidTestData(errors)) => errors,
+        res => panic!("expected InvalidTestData, got {res:?}"),
+    };
+    assert_eq!(
+        vec![
+            "in memory, entry 1: namespace db.c is already defined at in memory, entry 0",
+            "in memory, entry 2: view v cannot be defined on itself",
+        ],
+        errors
+    );
+}
+
+#[test]
+fn build_allows_views_on_namespaces_of_other_files() {
+    let dir = write(
+        "loader_partial",
+        &[
+            ("a.yml", A),
+            (
+                "v.yml",
+                "dataset:\n  - db: test\n    view: { name: v, view_on: a, pipeline: [] }\n",
+            ),
+        ],
+    );
+    let loader = DataLoader::builder()
+        .target(&dir)
+        .exclude(Pattern::new("a.yml").unwrap())
+        .build()
+        .unwrap();
+    assert_eq!(vec!["test.v"], namespaces(&loader));
+
+    let loader = DataLoader::builder()
+        .target(dir.join("v.yml"))
+        .build()
+        .unwrap();
+    assert_eq!(vec!["test.v"], namespaces(&loader));
+}
+
+/// recording_loader builds a loader for the data files that records every event it fires.
+fn recording_loader(test_data_files: Vec<TestDataFile>) -> (DataLoader, Arc<Mutex<Vec<String>>>) {
+    let events = Arc::new(Mutex::new(vec![]));
+    let recorded = events.clone();
+    let loader = DataLoader::builder()
+        .data_files(test_data_files)
+        .force(true)
+        .hook(move |event| {
+            let name = match event {
+                Event::Step(step) => format!("step {step:?}"),
+                Event::Dropped(entry) => format!("dropped {}", entry.namespace()),
+                Event::Loaded(entry) => format!("loaded {}", entry.namespace()),
+                Event::SchemaSet(entry) => format!("schema set {}", entry.namespace()),
+            };
+            recorded.lock().unwrap().push(name);
+        })
+        .build()
+        .unwrap();
+    (loader, events)
+}
+
+#[tokio::test]
+async fn load_fires_every_step_in_order() {
+    // Nothing listens on this port. With force, and no entries, users, or roles, no step needs to
+    // contact the server.
+    let client = Client::with_uri_str("mongodb://localhost:1/?serverSelectionTimeoutMS=50")
+        .await
+        .unwrap();
+    let (loader, events) = recording_loader(vec![]);
+    loader.load(&client, &Mode::Mongod).await.unwrap();
+    assert_eq!(
+        vec![
+            "step Comparing",
+            "step Dropping",
+            "step Loading",
+            "step CreatingUsers",
+            "step SettingSchemas",
+            "step RecordingFingerprints",
+        ],
+        *events.lock().unwrap()
+    );
+
+    // The first entry fails to drop, so no entry events are fired and no later step begins.
+    let file = TestDataFile::new(
+        "in memory",
+        vec![TestDataEntry::collection(
+            "db",
+            CollectionData::new("c", vec![]),
+        )],
+    );
+    let (loader, events) = recording_loader(vec![file]);
+    assert!(loader.load(&client, &Mode::Mongod).await.is_err());
+    assert_eq!(
+        vec!["step Comparing", "step Dropping"],
+        *events.lock().unwrap()
+    );
+}
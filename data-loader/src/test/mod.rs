@@ -0,0 +1,72 @@
+use crate::TestDataFile;
+use serde::de::DeserializeOwned;
+use std::{env, fs, path::PathBuf, process};
+
+#[cfg(test)]
+mod adf_config;
+#[cfg(test)]
+mod batches;
+#[cfg(test)]
+mod coverage;
+#[cfg(test)]
+mod csv;
+#[cfg(test)]
+mod dump;
+#[cfg(test)]
+mod errors;
+#[cfg(test)]
+mod extjson;
+#[cfg(test)]
+mod fingerprint;
+#[cfg(test)]
+mod generate;
+#[cfg(test)]
+mod include;
+#[cfg(test)]
+mod journal;
+#[cfg(test)]
+mod loader;
+#[cfg(test)]
+mod profile;
+#[cfg(test)]
+mod read_data_files;
+#[cfg(test)]
+mod schema;
+#[cfg(test)]
+mod source;
+#[cfg(test)]
+mod tags;
+#[cfg(test)]
+mod tpch;
+#[cfg(test)]
+mod users;
+#[cfg(test)]
+mod validate;
+#[cfg(test)]
+mod validator;
+#[cfg(test)]
+mod verify;
+
+/// write writes the provided files to a fresh directory, and returns its path. The directory is
+/// named after the test, and after this process so that concurrent runs do not share it.
+pub(crate) fn write(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = env::temp_dir().join(format!("data_loader_{name}_{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (file, contents) in files {
+        fs::write(dir.join(file), contents).unwrap();
+    }
+    dir
+}
+
+/// parse parses a YAML value.
+pub(crate) fn parse<T: DeserializeOwned>(yaml: &str) -> T {
+    serde_yaml::from_str(yaml).unwrap()
+}
+
+/// parse_file parses a test data file, as if it had been read from the provided path.
+pub(crate) fn parse_file(path: &str, yaml: &str) -> TestDataFile {
+    let mut test_data_file: TestDataFile = parse(yaml);
+    test_data_file.path = path.into();
+    test_data_file
+}
@@ -0,0 +1,89 @@
+use crate::{read_data_files, FileFilter};
+use glob::Pattern;
+use std::path::PathBuf;
+
+fn read_paths(paths: &[&str], filter: FileFilter) -> Vec<PathBuf> {
+    let paths = paths.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+    read_data_files(&paths, &filter, false, None)
+        .unwrap()
+        .into_iter()
+        .map(|tdf| tdf.path)
+        .collect()
+}
+
+#[test]
+fn directories_are_searched_recursively() {
+    assert_eq!(
+        vec![
+            PathBuf::from("sample_files/external/external.yml"),
+            PathBuf::from("sample_files/include/include.yml"),
+            PathBuf::from("sample_files/nested/sample_nested.yml"),
+            PathBuf::from("sample_files/sample.json"),
+            PathBuf::from("sample_files/sample.yaml"),
+            PathBuf::from("sample_files/sample.yml"),
+            PathBuf::from("sample_files/tpch/tpch.yml"),
+        ],
+        read_paths(&["sample_files"], FileFilter::default()),
+    );
+}
+
+#[test]
+fn filters_apply_to_directories_but_not_files() {
+    let filter = FileFilter {
+        include: vec![Pattern::new("*.y*ml").unwrap()],
+        exclude: vec![
+            Pattern::new("external/**").unwrap(),
+            Pattern::new("include/**").unwrap(),
+            Pattern::new("nested/**").unwrap(),
+            Pattern::new("tpch/**").unwrap(),
+        ],
+    };
+    assert_eq!(
+        vec![
+            PathBuf::from("sample_files/sample.yaml"),
+            PathBuf::from("sample_files/sample.yml"),
+            PathBuf::from("sample_files/sample.json"),
+        ],
+        read_paths(&["sample_files", "sample_files/sample.json"], filter),
+    );
+}
+
+#[test]
+fn files_are_read_once() {
+    assert_eq!(
+        vec![PathBuf::from("sample_files/sample.json")],
+        read_paths(
+            &[
+                "sample_files/sample.json",
+                "sample_files/../sample_files/sample.json"
+            ],
+            FileFilter::default()
+        ),
+    );
+}
+
+#[test]
+fn docs_files_are_not_read_as_data_files() {
+    let test_data_files = read_data_files(
+        &["sample_files/external".to_string()],
+        &FileFilter::default(),
+        false,
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        vec![PathBuf::from("sample_files/external/external.yml")],
+        test_data_files
+            .iter()
+            .map(|tdf| tdf.path.clone())
+            .collect::<Vec<_>>(),
+    );
+
+    // Collections with a docs_file may omit their inline docs.
+    let inline_docs = test_data_files[0]
+        .dataset
+        .iter()
+        .map(|entry| entry.collection.as_ref().unwrap().docs.len())
+        .collect::<Vec<_>>();
+    assert_eq!(vec![0, 1, 0, 0], inline_docs);
+}
@@ -0,0 +1,55 @@
+use crate::schema::{conform, infer};
+use mongodb::bson::{doc, Bson};
+
+#[test]
+fn infer_matches_sql_generate_schema_shape() {
+    let docs = [
+        Bson::Document(doc! {"_id": 1, "a": true, "b": {"c": 1, "d": "x"}, "e": [1, 2]}),
+        Bson::Document(doc! {"_id": 2, "a": Bson::Null, "b": {"c": 2.5}, "e": []}),
+        Bson::Document(doc! {"_id": 3, "b": {"c": 3}, "e": ["x"]}),
+    ];
+
+    let expected = doc! {
+        "bsonType": "object",
+        "properties": {
+            "_id": {"bsonType": "int"},
+            "a": {"anyOf": [{"bsonType": "bool"}, {"bsonType": "null"}]},
+            "b": {
+                "bsonType": "object",
+                "properties": {
+                    "c": {"anyOf": [{"bsonType": "double"}, {"bsonType": "int"}]},
+                    "d": {"bsonType": "string"},
+                },
+                "required": ["c"],
+                "additionalProperties": false,
+            },
+            "e": {
+                "bsonType": "array",
+                "items": {"anyOf": [{"bsonType": "int"}, {"bsonType": "string"}]},
+            },
+        },
+        "required": ["_id", "b", "e"],
+        "additionalProperties": false,
+    };
+    assert_eq!(Bson::Document(expected.clone()), infer(&docs));
+
+    for doc in &docs {
+        assert_eq!(Vec::<String>::new(), conform(&expected, doc, ""));
+    }
+}
+
+#[test]
+fn infer_empty_arrays_and_no_documents() {
+    assert_eq!(
+        Bson::Document(doc! {
+            "bsonType": "object",
+            "properties": {},
+            "additionalProperties": false,
+        }),
+        infer(&[])
+    );
+    assert_eq!(
+        Bson::Document(doc! {"bsonType": "array", "items": {}}),
+        infer(&[Bson::Array(vec![])])
+    );
+}
@@ -0,0 +1,76 @@
+use crate::{
+    read_data_files, source::read_documents, test::write, DataLoaderError, FileFilter, Result,
+};
+use mongodb::bson::{doc, Bson};
+use std::path::PathBuf;
+
+fn read_all(path: &str) -> Result<Vec<Bson>> {
+    read_documents(path.as_ref(), None, false)?.collect()
+}
+
+#[test]
+fn all_formats_read_the_same_documents() {
+    let expected = vec![
+        Bson::Document(doc! {"_id": 1, "a": "yes", "b": 1.5}),
+        Bson::Document(doc! {"_id": 2, "a": "no", "b": 2.5}),
+        Bson::Document(doc! {"_id": 3, "a": "maybe", "b": 3.5}),
+    ];
+    for path in [
+        "sample_files/external/data/calcs.jsonl",
+        "sample_files/external/data/calcs.json",
+        "sample_files/external/data/calcs.bson",
+    ] {
+        assert_eq!(expected, read_all(path).unwrap(), "{path}");
+    }
+}
+
+#[test]
+fn docs_files_are_resolved_relative_to_the_data_file() {
+    let test_data_files = read_data_files(
+        &["sample_files/external/external.yml".to_string()],
+        &FileFilter::default(),
+        false,
+        None,
+    )
+    .unwrap();
+    let docs_files = test_data_files[0]
+        .dataset
+        .iter()
+        .filter_map(|entry| entry.collection.as_ref()?.docs_file.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            PathBuf::from("sample_files/external/data/calcs.jsonl"),
+            PathBuf::from("sample_files/external/data/calcs.json"),
+            PathBuf::from("sample_files/external/data/calcs.bson"),
+            PathBuf::from("sample_files/external/data/calcs.csv"),
+        ],
+        docs_files
+    );
+}
+
+#[test]
+fn malformed_documents_are_reported_with_their_line() {
+    let path = write(
+        "source_malformed",
+        &[("malformed.jsonl", "{\"_id\": 1}\n\n[1, 2]\n{\"_id\": 3}\n")],
+    )
+    .join("malformed.jsonl");
+
+    let docs = read_documents(&path, None, false)
+        .unwrap()
+        .collect::<Vec<_>>();
+
+    assert_eq!(2, docs.len());
+    assert_eq!(Bson::Document(doc! {"_id": 1}), *docs[0].as_ref().unwrap());
+    match &docs[1] {
+        Err(DataLoaderError::InvalidDocsFile(message)) => assert_eq!(
+            format!(
+                "{}, line 3: expected a document, found [1,2]",
+                path.display()
+            ),
+            *message
+        ),
+        res => panic!("expected InvalidDocsFile, got {res:?}"),
+    }
+}
@@ -0,0 +1,224 @@
+use crate::{dump::to_fixture_bson, CollectionData, TestDataFile};
+use mongodb::bson::{
+    doc, oid::ObjectId, spec::BinarySubtype, spec::ElementType, Binary, Bson, DateTime, DbPointer,
+    Decimal128, JavaScriptCodeWithScope, Regex, Timestamp,
+};
+
+const CORPUS: &str = include_str!("bson_types.yml");
+
+fn parse_docs(yaml: &str) -> Result<Vec<Bson>, serde_yaml::Error> {
+    let tdf: TestDataFile = serde_yaml::from_str(yaml)?;
+    Ok(tdf
+        .dataset
+        .into_iter()
+        .flat_map(|entry| entry.collection.unwrap().docs)
+        .collect())
+}
+
+fn values(docs: &[Bson]) -> Vec<(String, Bson)> {
+    docs.iter()
+        .map(|doc| {
+            let doc = doc.as_document().unwrap();
+            (
+                doc.get_str("_id").unwrap().to_string(),
+                doc.get("v").unwrap().clone(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn tags_produce_their_bson_types() {
+    let oid = ObjectId::parse_str("5f0f1b9b9b9b9b9b9b9b9b9b").unwrap();
+    let bytes = vec![1, 2, 3];
+    let expected: Vec<(&str, Bson)> = vec![
+        ("double", Bson::Double(1.0)),
+        ("double_nan", Bson::Double(f64::NAN)),
+        ("double_string", Bson::Double(-2.5)),
+        ("string", Bson::from("s")),
+        ("document", Bson::from(doc! {"a": 1i64})),
+        (
+            "array",
+            Bson::Array(vec![
+                Bson::Int32(1),
+                Bson::Decimal128("2".parse::<Decimal128>().unwrap()),
+            ]),
+        ),
+        (
+            "binary",
+            Bson::Binary(Binary {
+                subtype: BinarySubtype::Generic,
+                bytes: bytes.clone(),
+            }),
+        ),
+        (
+            "binary_subtype",
+            Bson::Binary(Binary {
+                subtype: BinarySubtype::UserDefined(0x80),
+                bytes,
+            }),
+        ),
+        ("undefined", Bson::Undefined),
+        ("oid", Bson::ObjectId(oid)),
+        ("bool", Bson::Boolean(true)),
+        (
+            "date",
+            Bson::DateTime(DateTime::from_millis(1_600_000_000_000)),
+        ),
+        (
+            "date_millis",
+            Bson::DateTime(DateTime::from_millis(1_600_000_000_000)),
+        ),
+        ("null", Bson::Null),
+        (
+            "regex",
+            Bson::RegularExpression(Regex {
+                pattern: "^a.c$".to_string(),
+                options: "im".to_string(),
+            }),
+        ),
+        (
+            "regex_document",
+            Bson::RegularExpression(Regex {
+                pattern: "a/b".to_string(),
+                options: "x".to_string(),
+            }),
+        ),
+        (
+            "db_pointer",
+            Bson::try_from(serde_json::json!({
+                "$dbPointer": {"$ref": "db.coll", "$id": {"$oid": oid.to_hex()}}
+            }))
+            .unwrap(),
+        ),
+        (
+            "javascript",
+            Bson::JavaScriptCode("function() {}".to_string()),
+        ),
+        ("symbol", Bson::Symbol("sym".to_string())),
+        (
+            "javascript_with_scope",
+            Bson::JavaScriptCodeWithScope(JavaScriptCodeWithScope {
+                code: "function() { return x; }".to_string(),
+                scope: doc! {"x": 1},
+            }),
+        ),
+        ("int32", Bson::Int32(-1)),
+        (
+            "timestamp",
+            Bson::Timestamp(Timestamp {
+                time: 1,
+                increment: 2,
+            }),
+        ),
+        ("int64", Bson::Int64(1)),
+        ("int64_string", Bson::Int64(i64::MIN)),
+        (
+            "decimal",
+            Bson::Decimal128("1.50".parse::<Decimal128>().unwrap()),
+        ),
+        ("min_key", Bson::MinKey),
+        ("max_key", Bson::MaxKey),
+    ];
+
+    let actual = values(&parse_docs(CORPUS).unwrap());
+    // NaN is never equal to itself, so compare the debug representations.
+    assert_eq!(format!("{expected:?}"), format!("{actual:?}"));
+    assert!(matches!(actual[16].1, Bson::DbPointer(DbPointer { .. })));
+
+    let element_types = actual
+        .iter()
+        .map(|(_, v)| v.element_type())
+        .collect::<Vec<_>>();
+    for element_type in [
+        ElementType::Double,
+        ElementType::String,
+        ElementType::EmbeddedDocument,
+        ElementType::Array,
+        ElementType::Binary,
+        ElementType::Undefined,
+        ElementType::ObjectId,
+        ElementType::Boolean,
+        ElementType::DateTime,
+        ElementType::Null,
+        ElementType::RegularExpression,
+        ElementType::DbPointer,
+        ElementType::JavaScriptCode,
+        ElementType::Symbol,
+        ElementType::JavaScriptCodeWithScope,
+        ElementType::Int32,
+        ElementType::Timestamp,
+        ElementType::Int64,
+        ElementType::Decimal128,
+        ElementType::MaxKey,
+        ElementType::MinKey,
+    ] {
+        assert!(
+            element_types.contains(&element_type),
+            "corpus is missing {element_type:?}"
+        );
+    }
+}
+
+#[test]
+fn corpus_round_trips_through_dumped_yaml_and_json() {
+    let docs = parse_docs(CORPUS).unwrap();
+    let fixture = docs
+        .iter()
+        .cloned()
+        .map(to_fixture_bson)
+        .collect::<Vec<_>>();
+
+    let yaml = serde_yaml::to_string(&doc! {"name": "c", "docs": fixture.clone()}).unwrap();
+    let from_yaml: CollectionData = serde_yaml::from_str(&yaml).unwrap();
+    let json = serde_json::to_string(&doc! {"name": "c", "docs": fixture}).unwrap();
+    let from_json: CollectionData = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(format!("{docs:?}"), format!("{:?}", from_yaml.docs));
+    assert_eq!(format!("{docs:?}"), format!("{:?}", from_json.docs));
+}
+
+#[test]
+fn untagged_values_keep_their_types() {
+    let docs = parse_docs(
+        r#"
+dataset:
+  - db: "types"
+    collection:
+      name: "untagged"
+      docs: [ { i: 1, n: -1, big: 3000000000, d: 1.5, l: { $numberLong: "1" } } ]
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+        vec![Bson::from(doc! {
+            "i": 1,
+            "n": -1i64,
+            "big": 3_000_000_000i64,
+            "d": 1.5,
+            "l": 1i64,
+        })],
+        docs
+    );
+}
+
+#[test]
+fn invalid_tags_are_reported() {
+    for (value, expected) in [
+        ("!oid \"xyz\"", "invalid !oid value \"xyz\""),
+        ("!int32 3000000000", "invalid !int32 value 3000000000"),
+        (
+            "!timestamp { t: 1 }",
+            "invalid !timestamp value { \"t\": 1 }: i: missing i",
+        ),
+        ("!date true", "invalid !date value true: unexpected bool"),
+        ("!uuid \"x\"", "unknown tag !uuid"),
+    ] {
+        let yaml = format!(
+            "dataset:\n  - db: \"types\"\n    collection:\n      name: \"c\"\n      docs: [ {{ v: {value} }} ]\n"
+        );
+        let error = parse_docs(&yaml).unwrap_err().to_string();
+        assert!(error.contains(expected), "{error}");
+        assert!(error.contains("line 5"), "{error}");
+    }
+}
@@ -0,0 +1,191 @@
+use crate::{
+    read_data_files, schema,
+    test::write,
+    tpch::{TpchTable, TpchTableOptions, TPCH_TABLES},
+    DataLoaderError, FileFilter,
+};
+use mongodb::bson::{Bson, Document};
+use std::collections::{HashMap, HashSet};
+
+fn generate(table: TpchTable, scale_factor: f64, seed: u64) -> Vec<Document> {
+    TpchTableOptions {
+        table,
+        scale_factor,
+        seed,
+    }
+    .documents()
+    .unwrap()
+    .map(|doc| doc.as_document().unwrap().clone())
+    .collect()
+}
+
+fn tables(seed: u64) -> HashMap<&'static str, Vec<Document>> {
+    TPCH_TABLES
+        .iter()
+        .map(|&table| (table.name(), generate(table, 0.001, seed)))
+        .collect()
+}
+
+#[test]
+fn generation_is_deterministic() {
+    assert_eq!(tables(3), tables(3));
+    assert_ne!(
+        generate(TpchTable::Lineitem, 0.001, 3),
+        generate(TpchTable::Lineitem, 0.001, 4)
+    );
+}
+
+#[test]
+fn tables_are_scaled_and_conform_to_their_schemas() {
+    let tables = tables(0);
+    for (name, expected) in [
+        ("region", 5),
+        ("nation", 25),
+        ("supplier", 10),
+        ("customer", 150),
+        ("part", 200),
+        ("partsupp", 800),
+        ("orders", 1500),
+    ] {
+        assert_eq!(expected, tables[name].len(), "{name}");
+    }
+    assert!((1500..=1500 * 7).contains(&tables["lineitem"].len()));
+
+    for &table in TPCH_TABLES {
+        let schema = table.schema();
+        for (i, doc) in tables[table.name()].iter().enumerate() {
+            assert_eq!(
+                Vec::<String>::new(),
+                schema::conform(
+                    schema.as_document().unwrap(),
+                    &Bson::Document(doc.clone()),
+                    ""
+                ),
+                "{} document {i}",
+                table.name()
+            );
+        }
+        let ids = tables[table.name()]
+            .iter()
+            .map(|doc| doc.get_i32("_id").unwrap())
+            .collect::<HashSet<_>>();
+        assert_eq!(tables[table.name()].len(), ids.len(), "{}", table.name());
+    }
+}
+
+#[test]
+fn tables_reference_each_other() {
+    let tables = tables(0);
+    let keys = |table: &str, field: &str| {
+        tables[table]
+            .iter()
+            .map(|doc| doc.get_i32(field).unwrap())
+            .collect::<HashSet<_>>()
+    };
+    let nations = keys("nation", "n_nationkey");
+    assert!(keys("nation", "n_regionkey").is_subset(&keys("region", "r_regionkey")));
+    assert!(keys("supplier", "s_nationkey").is_subset(&nations));
+    assert!(keys("customer", "c_nationkey").is_subset(&nations));
+    assert!(keys("orders", "o_custkey").is_subset(&keys("customer", "c_custkey")));
+    assert_eq!(keys("orders", "o_orderkey"), keys("lineitem", "l_orderkey"));
+
+    let part_suppliers = tables["partsupp"]
+        .iter()
+        .map(|doc| {
+            (
+                doc.get_i32("ps_partkey").unwrap(),
+                doc.get_i32("ps_suppkey").unwrap(),
+            )
+        })
+        .collect::<HashSet<_>>();
+    assert_eq!(tables["partsupp"].len(), part_suppliers.len());
+    assert!(part_suppliers
+        .iter()
+        .all(|(_, supplier)| (1..=10).contains(supplier)));
+
+    let mut line_totals = HashMap::new();
+    for line in &tables["lineitem"] {
+        let key = (
+            line.get_i32("l_partkey").unwrap(),
+            line.get_i32("l_suppkey").unwrap(),
+        );
+        assert!(part_suppliers.contains(&key), "{line}");
+        *line_totals
+            .entry(line.get_i32("l_orderkey").unwrap())
+            .or_insert(0.0) += line.get_f64("l_extendedprice").unwrap()
+            * (1.0 + line.get_f64("l_tax").unwrap())
+            * (1.0 - line.get_f64("l_discount").unwrap());
+    }
+    for order in &tables["orders"] {
+        let total = line_totals[&order.get_i32("o_orderkey").unwrap()];
+        assert!((total - order.get_f64("o_totalprice").unwrap()).abs() < 0.01);
+    }
+}
+
+#[test]
+fn tpch_entries_are_expanded_into_collections() {
+    let test_data_files = read_data_files(
+        &["sample_files/tpch/tpch.yml".to_string()],
+        &FileFilter::default(),
+        false,
+        None,
+    )
+    .unwrap();
+    let entries = &test_data_files[0].dataset;
+    assert_eq!(
+        TPCH_TABLES
+            .iter()
+            .map(|table| format!("tpch.{}", table.name()))
+            .collect::<Vec<_>>(),
+        entries.iter().map(|e| e.namespace()).collect::<Vec<_>>()
+    );
+    for (entry, &table) in entries.iter().zip(TPCH_TABLES) {
+        assert_eq!(Some(table.schema()), entry.schema);
+        let options = entry.collection.as_ref().unwrap().tpch.as_ref().unwrap();
+        assert_eq!(
+            (table, 0.001, 7),
+            (options.table, options.scale_factor, options.seed)
+        );
+    }
+}
+
+#[test]
+fn tpch_entries_must_not_specify_a_collection_or_view() {
+    let path = write(
+        "tpch_invalid",
+        &[(
+            "tpch.yml",
+            "dataset:\n  - db: tpch\n    tpch: { scale_factor: 0.001 }\n    collection: { name: a }\n",
+        )],
+    )
+    .join("tpch.yml");
+    let res = read_data_files(
+        &[path.display().to_string()],
+        &FileFilter::default(),
+        false,
+        None,
+    );
+    match res {
+        Err(DataLoaderError::InvalidViewOrCollectionDataEntry(location)) => {
+            assert_eq!(format!("{}, entry 0", path.display()), location)
+        }
+        res => panic!("expected InvalidViewOrCollectionDataEntry, got {res:?}"),
+    }
+}
+
+#[test]
+fn invalid_scale_factors_are_reported() {
+    let options = TpchTableOptions {
+        table: TpchTable::Orders,
+        scale_factor: 0.0,
+        seed: 0,
+    };
+    match options.documents() {
+        Err(DataLoaderError::InvalidGenerator(errors)) => assert_eq!(
+            vec!["scale_factor must be greater than 0 and at most 100, found 0".to_string()],
+            errors
+        ),
+        Err(e) => panic!("expected InvalidGenerator, got {e:?}"),
+        Ok(_) => panic!("expected InvalidGenerator"),
+    }
+}
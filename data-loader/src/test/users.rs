@@ -0,0 +1,182 @@
+use crate::{
+    test::parse_file,
+    users::{check_users_and_roles, order_roles, role_creation_order, validate_users_and_roles},
+    validate::validate,
+    DataLoaderError, RoleData, RoleName, UserData,
+};
+use mongodb::{bson::doc, Client};
+
+#[test]
+fn users_and_roles_are_parsed() {
+    let test_data_file = parse_file(
+        "test.yml",
+        r#"
+roles:
+  - db: "db2"
+    role: "calcsReader"
+    privileges:
+      - { resource: { db: "db2", collection: "calcs" }, actions: [ "find" ] }
+    roles: [ "read" ]
+users:
+  - { db: "admin", user: "db2reader", password: "pencil", roles: [ { role: "calcsReader", db: "db2" } ] }
+"#,
+    );
+    assert!(test_data_file.dataset.is_empty());
+    assert_eq!(
+        vec![RoleData {
+            db: "db2".to_string(),
+            role: "calcsReader".to_string(),
+            privileges: vec![
+                doc! {"resource": {"db": "db2", "collection": "calcs"}, "actions": ["find"]}
+            ],
+            roles: vec![RoleName::Name("read".to_string())],
+        }],
+        test_data_file.roles
+    );
+    assert_eq!(
+        vec![UserData {
+            db: "admin".to_string(),
+            user: "db2reader".to_string(),
+            password: "pencil".to_string(),
+            mechanisms: vec![],
+            roles: vec![RoleName::Qualified {
+                role: "calcsReader".to_string(),
+                db: "db2".to_string()
+            }],
+        }],
+        test_data_file.users
+    );
+}
+
+#[test]
+fn invalid_users_and_roles_are_reported() {
+    let a = parse_file(
+        "a.yml",
+        r#"
+roles:
+  - { db: "", role: "r" }
+users:
+  - { db: "admin", user: "u", password: "", mechanisms: [ "SCRAM-SHA-256", "PLAIN" ] }
+"#,
+    );
+    let b = parse_file(
+        "b.yml",
+        r#"
+users:
+  - { db: "admin", user: "u", password: "p" }
+"#,
+    );
+    assert_eq!(
+        vec![
+            r#"a.yml: role "r" in "": the database and name must not be empty"#,
+            "a.yml: user u in admin: the password must not be empty",
+            r#"a.yml: user u in admin: mechanism "PLAIN" must be one of ["SCRAM-SHA-1", "SCRAM-SHA-256"]"#,
+            "b.yml: user u in admin is already defined in a.yml",
+        ],
+        validate_users_and_roles(&[a.clone(), b.clone()])
+    );
+    assert!(matches!(
+        validate(&[a, b]),
+        Err(DataLoaderError::InvalidTestData(errors)) if errors.len() == 4
+    ));
+}
+
+#[test]
+fn roles_are_created_after_the_roles_they_inherit() {
+    let a = parse_file(
+        "a.yml",
+        r#"
+roles:
+  - { db: "db2", role: "reporter", roles: [ "calcsReader", { role: "auditor", db: "admin" }, "read" ] }
+  - { db: "db2", role: "calcsReader", roles: [ "base" ] }
+"#,
+    );
+    let b = parse_file(
+        "b.yml",
+        r#"
+roles:
+  - { db: "admin", role: "auditor" }
+  - { db: "db2", role: "base" }
+  - { db: "other", role: "base", roles: [ { role: "reporter", db: "db2" } ] }
+"#,
+    );
+    let test_data_files = [a, b];
+    assert_eq!(
+        vec![
+            ("db2", "base"),
+            ("db2", "calcsReader"),
+            ("admin", "auditor"),
+            ("db2", "reporter"),
+            ("other", "base"),
+        ],
+        role_creation_order(&test_data_files)
+            .unwrap()
+            .iter()
+            .map(|role| (role.db.as_str(), role.role.as_str()))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn roles_that_inherit_themselves_are_reported() {
+    let test_data_file = parse_file(
+        "test.yml",
+        r#"
+roles:
+  - { db: "db2", role: "a", roles: [ "b" ] }
+  - { db: "db2", role: "b", roles: [ { role: "a", db: "db2" } ] }
+  - { db: "db2", role: "c", roles: [ "c" ] }
+"#,
+    );
+    assert_eq!(
+        vec!["test.yml: role a in db2 inherits itself"],
+        validate_users_and_roles(&[test_data_file])
+    );
+}
+
+#[test]
+fn roles_are_ordered_after_the_roles_they_inherit() {
+    let role = |name: &str, roles: &[&str]| RoleData {
+        db: "db2".to_string(),
+        role: name.to_string(),
+        privileges: vec![],
+        roles: roles
+            .iter()
+            .map(|r| RoleName::Name(r.to_string()))
+            .collect(),
+    };
+    let derived = role("derived", &["base", "read"]);
+    let base = role("base", &[]);
+    let other = role("other", &[]);
+
+    assert_eq!(
+        vec!["base", "derived", "other"],
+        order_roles(vec![("", &derived), ("", &base), ("", &other)])
+            .unwrap()
+            .iter()
+            .map(|role| role.role.as_str())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[tokio::test]
+async fn existing_users_and_roles_are_not_checked_with_force() {
+    // Nothing listens on this port, so checking the users and roles fails.
+    let client = Client::with_uri_str("mongodb://localhost:1/?serverSelectionTimeoutMS=50")
+        .await
+        .unwrap();
+    let test_data_files = [parse_file(
+        "test.yml",
+        r#"
+users:
+  - { db: "admin", user: "u", password: "pencil" }
+"#,
+    )];
+
+    assert!(check_users_and_roles(&client, &test_data_files, true)
+        .await
+        .is_ok());
+    assert!(check_users_and_roles(&client, &test_data_files, false)
+        .await
+        .is_err());
+}
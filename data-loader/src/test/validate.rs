@@ -0,0 +1,143 @@
+use crate::{read_data_files, test::parse_file, validate::validate, DataLoaderError, FileFilter};
+
+#[test]
+fn sample_files_are_valid() {
+    let test_data_files = read_data_files(
+        &["sample_files".to_string()],
+        &FileFilter::default(),
+        false,
+        None,
+    )
+    .unwrap();
+    if let Err(e) = validate(&test_data_files) {
+        panic!("unexpected error: {e}")
+    }
+}
+
+#[test]
+fn all_errors_are_collected() {
+    let test_data_file = parse_file(
+        "test.yml",
+        r#"
+dataset:
+  - db: "bad.db"
+    collection:
+      name: "foo"
+      docs: []
+      indexes:
+        - { key: { a: 0 } }
+  - db: "test"
+    collection:
+      name: "foo"
+      docs: []
+  - db: "test"
+    collection:
+      name: "foo"
+      docs: []
+  - db: "test"
+    view:
+      name: "v"
+      view_on: "missing"
+      pipeline: [ { $project: { _id: 0 }, $limit: 1 } ]
+    schema: { bsonType: "integer", properties: { a: { anyOf: [ 1 ] } } }
+"#,
+    );
+
+    let errors = match validate(&[test_data_file]) {
+        Err(DataLoaderError::InvalidTestData(errors)) => errors,
+        res => panic!("expected InvalidTestData, got {res:?}"),
+    };
+
+    let expected = [
+        "test.yml, entry 0: database name \"bad.db\" contains invalid character '.'",
+        "test.yml, entry 2: namespace test.foo is already defined at test.yml, entry 1",
+        "test.yml, entry 0: index 0: invalid index key \"a\": 0",
+        "test.yml, entry 3: view v is defined on test.missing, which is not defined in any data file",
+        "test.yml, entry 3: pipeline stage 0 must be a document with exactly one '$'-prefixed key",
+        "test.yml, entry 3: schema: /bsonType: unknown bsonType \"integer\"",
+        "test.yml, entry 3: schema: /properties/a/anyOf/0: schema must be a document",
+    ];
+    assert_eq!(expected.as_slice(), errors.as_slice());
+}
+
+#[test]
+fn documents_must_conform_to_schema() {
+    let test_data_file = parse_file(
+        "test.yml",
+        r#"
+dataset:
+  - db: "test"
+    collection:
+      name: "foo"
+      docs:
+        - { _id: 1, a: "x", b: [ 1, 2 ], "c/d": { e: 1 } }
+        - { _id: 2, a: 1, b: [ 1, "2" ], "c/d": { e: 1.5 }, f: null }
+        - { _id: 3, b: [] }
+    schema:
+      bsonType: "object"
+      required: [ "_id", "a" ]
+      additionalProperties: false
+      properties:
+        _id: { bsonType: "int" }
+        a: { anyOf: [ { bsonType: "string" }, { bsonType: "null" } ] }
+        b: { bsonType: "array", items: { bsonType: "int" } }
+        "c/d":
+          bsonType: "object"
+          properties: { e: { bsonType: [ "int", "long" ] } }
+"#,
+    );
+
+    let errors = match validate(&[test_data_file]) {
+        Err(DataLoaderError::InvalidTestData(errors)) => errors,
+        res => panic!("expected InvalidTestData, got {res:?}"),
+    };
+
+    let expected = [
+        "test.yml, entry 0, document 1: /a: does not match any of the anyOf alternatives",
+        "test.yml, entry 0, document 1: /b/1: expected bsonType \"int\", found \"string\"",
+        "test.yml, entry 0, document 1: /c~1d/e: expected one of bsonTypes [\"int\", \"long\"], found \"double\"",
+        "test.yml, entry 0, document 1: /f: additional property \"f\" is not allowed",
+        "test.yml, entry 0, document 2: /: missing required field \"a\"",
+    ];
+    assert_eq!(expected.as_slice(), errors.as_slice());
+}
+
+#[test]
+fn invalid_collection_options_are_reported() {
+    let test_data_file = parse_file(
+        "test.yml",
+        r#"
+dataset:
+  - db: "test"
+    collection:
+      name: "capped"
+      options: { capped: true, max: 10 }
+  - db: "test"
+    collection:
+      name: "ts"
+      options: { timeseries: { timeField: "ts" }, clusteredIndex: { key: { _id: 1 }, unique: true } }
+  - db: "test"
+    collection:
+      name: "clustered"
+      options: { clusteredIndex: { key: { a: 1 }, unique: true }, viewOn: "foo" }
+  - db: "test"
+    collection:
+      name: "ttl"
+      options: { expireAfterSeconds: 60, collation: { locale: "fr" } }
+"#,
+    );
+
+    let errors = match validate(&[test_data_file]) {
+        Err(DataLoaderError::InvalidTestData(errors)) => errors,
+        res => panic!("expected InvalidTestData, got {res:?}"),
+    };
+
+    let expected = [
+        "test.yml, entry 0: options: a capped collection requires a size",
+        "test.yml, entry 1: options: a time-series collection cannot be clustered",
+        "test.yml, entry 2: options: viewOn and pipeline are not allowed; use a view entry instead",
+        "test.yml, entry 2: options: clusteredIndex must have key {_id: 1} and unique: true",
+        "test.yml, entry 3: options: expireAfterSeconds requires a time-series or clustered collection",
+    ];
+    assert_eq!(expected.as_slice(), errors.as_slice());
+}
@@ -0,0 +1,208 @@
+use crate::{
+    journal::Journal,
+    load_test_data,
+    loader::Hooks,
+    test::parse_file,
+    validate::validate,
+    validator::{apply_default, creation_options, parse_validation_level},
+    DataLoaderError, LoadOptions, SchemaValidator, TestDataEntry,
+};
+use mongodb::{
+    bson::{self, doc},
+    options::{ValidationAction, ValidationLevel},
+    Client,
+};
+
+/// options returns the creation options of the entry's collection, as a document.
+fn options(entry: &TestDataEntry) -> Option<bson::Document> {
+    creation_options(entry.collection.as_ref().unwrap(), entry.schema.as_ref())
+        .unwrap()
+        .map(|o| bson::to_document(&o).unwrap())
+}
+
+const DATASET: &str = r#"
+dataset:
+  - db: "test"
+    collection:
+      name: "entry"
+      docs: []
+      options: { capped: true, size: 4096 }
+      schema_validator: { validation_level: moderate, validation_action: warn }
+    schema: { bsonType: "object" }
+  - db: "test"
+    collection:
+      name: "global"
+      docs: []
+    schema: { bsonType: "object", required: [ "a" ] }
+  - db: "test"
+    collection:
+      name: "own_validator"
+      docs: []
+      options: { validator: { a: { $exists: true } } }
+    schema: { bsonType: "object" }
+  - db: "test"
+    collection:
+      name: "no_schema"
+      docs: []
+  - db: "test"
+    collection:
+      name: "own_level"
+      docs: []
+      options: { validationLevel: "moderate" }
+    schema: { bsonType: "object" }
+  - db: "test"
+    collection:
+      name: "own_action"
+      docs: []
+      options: { validationAction: "warn" }
+    schema: { bsonType: "object" }
+"#;
+
+#[test]
+fn schemas_are_installed_as_validators() {
+    let mut test_data_file = parse_file("test.yml", DATASET);
+    let dataset = &test_data_file.dataset;
+    assert_eq!(
+        Some(doc! {
+            "capped": true,
+            "size": 4096_i64,
+            "validator": {"$jsonSchema": {"bsonType": "object"}},
+            "validationLevel": "moderate",
+            "validationAction": "warn",
+        }),
+        options(&dataset[0])
+    );
+    assert_eq!(None, options(&dataset[1]));
+
+    apply_default(
+        std::slice::from_mut(&mut test_data_file),
+        &SchemaValidator {
+            validation_level: None,
+            validation_action: Some(ValidationAction::Error),
+        },
+    );
+    let dataset = &test_data_file.dataset;
+    assert_eq!(
+        Some(ValidationLevel::Moderate),
+        dataset[0]
+            .collection
+            .as_ref()
+            .unwrap()
+            .schema_validator
+            .as_ref()
+            .unwrap()
+            .validation_level
+    );
+    assert_eq!(
+        Some(doc! {
+            "validator": {"$jsonSchema": {"bsonType": "object", "required": ["a"]}},
+            "validationAction": "error",
+        }),
+        options(&dataset[1])
+    );
+    assert_eq!(
+        Some(doc! {"validator": {"a": {"$exists": true}}}),
+        options(&dataset[2])
+    );
+    assert_eq!(None, options(&dataset[3]));
+    assert_eq!(
+        Some(doc! {"validationLevel": "moderate"}),
+        options(&dataset[4])
+    );
+    assert_eq!(
+        Some(doc! {"validationAction": "warn"}),
+        options(&dataset[5])
+    );
+    assert_eq!(
+        Ok(()),
+        validate(&[test_data_file]).map_err(|e| e.to_string())
+    );
+}
+
+#[test]
+fn invalid_schema_validators_are_reported() {
+    let test_data_file = parse_file(
+        "test.yml",
+        r#"
+dataset:
+  - db: "test"
+    collection:
+      name: "no_schema"
+      docs: []
+      schema_validator: {}
+  - db: "test"
+    collection:
+      name: "conflict"
+      docs: []
+      options: { validationLevel: "off" }
+      schema_validator: {}
+    schema: { bsonType: "object" }
+"#,
+    );
+    let errors = match validate(&[test_data_file]) {
+        Err(DataLoaderError::InvalidTestData(errors)) => errors,
+        res => panic!("expected InvalidTestData, got {res:?}"),
+    };
+    let expected = [
+        "test.yml, entry 0: schema_validator: an entry without a schema cannot have a schema validator",
+        "test.yml, entry 1: schema_validator: options.validator, validationLevel, and validationAction cannot be combined with a schema validator",
+    ];
+    assert_eq!(expected.as_slice(), errors.as_slice());
+}
+
+#[test]
+fn validation_levels_are_parsed() {
+    assert_eq!(Ok(ValidationLevel::Off), parse_validation_level("off"));
+    assert_eq!(
+        Err("expected one of \"off\", \"strict\", or \"moderate\", got \"lax\"".to_string()),
+        parse_validation_level("lax")
+    );
+}
+
+#[tokio::test]
+async fn conflicting_validators_fail_to_load() {
+    // Nothing listens on this port; the conflict is found before the server is contacted.
+    let client = Client::with_uri_str("mongodb://localhost:1/?serverSelectionTimeoutMS=50")
+        .await
+        .unwrap();
+    let test_data_file = parse_file(
+        "test.yml",
+        r#"
+dataset:
+  - db: "test"
+    collection:
+      name: "conflict"
+      docs: [ { _id: 1 } ]
+      options: { validator: { a: { $exists: true } } }
+      schema_validator: { validation_action: warn }
+    schema: { bsonType: "object" }
+"#,
+    );
+    let entry = &test_data_file.dataset[0];
+    assert!(matches!(
+        creation_options(entry.collection.as_ref().unwrap(), entry.schema.as_ref()),
+        Err(DataLoaderError::ConflictingValidator)
+    ));
+
+    let res = load_test_data(
+        client,
+        vec![test_data_file],
+        &LoadOptions {
+            concurrency: 1,
+            batch_size: 1,
+        },
+        &Journal::default(),
+        &Hooks::default(),
+    )
+    .await;
+    match res {
+        Err(DataLoaderError::Context { context, source }) => {
+            assert_eq!("test.yml, entry 0 (test.conflict)", context);
+            assert!(
+                matches!(*source, DataLoaderError::ConflictingValidator),
+                "{source:?}"
+            );
+        }
+        res => panic!("expected ConflictingValidator, got {res:?}"),
+    }
+}
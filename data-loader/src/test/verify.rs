@@ -0,0 +1,102 @@
+use crate::{
+    test::parse,
+    verify::{diff_entries, diff_schema},
+};
+use mongodb::bson::{doc, Bson};
+
+#[test]
+fn identical_entries_have_no_differences() {
+    let expected = parse(
+        r#"{ db: "test", collection: { name: "foo", docs: [ { a: 1, _id: 1 }, { b: 2 } ], indexes: [ { key: { a: 1 }, unique: true } ] } }"#,
+    );
+    let actual = parse(
+        r#"{ db: "test", collection: { name: "foo", docs: [ { _id: 1, a: 1 }, { _id: 7, b: 2 } ], indexes: [ { key: { a: 1 }, unique: true, name: "a_1" } ] } }"#,
+    );
+    assert_eq!(Vec::<String>::new(), diff_entries(&expected, &actual));
+}
+
+#[test]
+fn differences_are_reported() {
+    let expected = parse(
+        r#"{ db: "test", collection: { name: "foo", docs: [ { _id: 1, a: 1 }, { _id: 2 }, { b: 2 } ], indexes: [ { key: { a: 1 }, unique: true }, { key: { c: 1 } } ] } }"#,
+    );
+    let actual = parse(
+        r#"{ db: "test", collection: { name: "foo", docs: [ { _id: 1, a: 1.0 }, { _id: 3 } ], indexes: [ { key: { a: 1 } }, { key: { d: 1 } } ] } }"#,
+    );
+    assert_eq!(
+        vec![
+            r#"document {"$numberInt":"1"} is {"_id":1,"a":1.0}, expected {"_id":1,"a":1}"#,
+            r#"missing document {"_id":2}"#,
+            r#"missing document {"b":2}"#,
+            r#"unexpected document {"_id":3}"#,
+            r#"index { "a": 1 } is missing option unique: true"#,
+            r#"missing index { "c": 1 }"#,
+            r#"unexpected index { "d": 1 }"#,
+        ],
+        diff_entries(&expected, &actual)
+    );
+}
+
+#[test]
+fn view_differences_are_reported() {
+    let expected = parse(
+        r#"{ db: "test", view: { name: "v", view_on: "foo", pipeline: [ { $limit: 1 } ] } }"#,
+    );
+    let actual = parse(
+        r#"{ db: "test", view: { name: "v", view_on: "bar", pipeline: [ { $limit: 1 } ] } }"#,
+    );
+    assert_eq!(
+        vec![r#"view is defined on "bar", expected "foo""#],
+        diff_entries(&expected, &actual)
+    );
+
+    let collection = parse(r#"{ db: "test", collection: { name: "v", docs: [] } }"#);
+    assert_eq!(
+        vec!["expected a view, found a collection"],
+        diff_entries(&expected, &collection)
+    );
+}
+
+#[test]
+fn missing_schemas_are_inferred_in_mongod_mode() {
+    let entry = parse(r#"{ db: "test", collection: { name: "foo", docs: [ { _id: 1 } ] } }"#);
+    let inferred = Bson::Document(doc! {
+        "bsonType": "object",
+        "properties": { "_id": { "bsonType": "int" } },
+        "required": ["_id"],
+        "additionalProperties": false,
+    });
+    assert_eq!(
+        Vec::<String>::new(),
+        diff_schema(&entry, false, Some(&inferred))
+    );
+    assert_eq!(vec!["no schema is set"], diff_schema(&entry, false, None));
+    assert_eq!(
+        Vec::<String>::new(),
+        diff_schema(&entry, true, Some(&Bson::Document(doc! {})))
+    );
+}
+
+#[test]
+fn collection_option_differences_are_reported() {
+    let expected = parse(
+        r#"{ db: "test", collection: { name: "foo", docs: [], options: { capped: true, size: 4096, timeseries: { timeField: "ts", granularity: "hours" } } } }"#,
+    );
+    let actual = parse(
+        r#"{ db: "test", collection: { name: "foo", docs: [], options: { capped: true, size: 4096.0, timeseries: { timeField: "ts", granularity: "hours", bucketMaxSpanSeconds: 2592000 } } } }"#,
+    );
+    assert_eq!(Vec::<String>::new(), diff_entries(&expected, &actual));
+
+    let actual = parse(
+        r#"{ db: "test", collection: { name: "foo", docs: [], options: { size: 8192, timeseries: { timeField: "time" } } } }"#,
+    );
+    assert_eq!(
+        vec![
+            "collection is missing option capped: true",
+            "collection has option size: 8192, expected 4096",
+            r#"collection has option timeseries.timeField: "time", expected "ts""#,
+            r#"collection is missing option timeseries.granularity: "hours""#,
+        ],
+        diff_entries(&expected, &actual)
+    );
+}
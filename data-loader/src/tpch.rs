@@ -0,0 +1,640 @@
+use crate::{CollectionData, DataLoaderError, Result, TestDataEntry};
+use mongodb::bson::{doc, Bson, DateTime, Document};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+/// The largest scale factor accepted. Keys are ints, and the number of lineitems at larger scale
+/// factors would not fit in 32 bits.
+const MAX_SCALE_FACTOR: f64 = 100.0;
+
+/// The number of milliseconds in a day.
+const DAY_MILLIS: i64 = 86_400_000;
+
+/// The earliest order date, 1992-01-01, in milliseconds since the epoch.
+const START_DATE_MILLIS: i64 = 694_224_000_000;
+
+/// The latest order date, 1998-08-02, in days since the earliest order date.
+const LAST_ORDER_DAY: i64 = 2405;
+
+/// The date that decides whether a lineitem is shipped or returned, 1995-06-17, in days since the
+/// earliest order date.
+const CURRENT_DAY: i64 = 1263;
+
+const REGIONS: &[&str] = &["AFRICA", "AMERICA", "ASIA", "EUROPE", "MIDDLE EAST"];
+
+/// The nations and the keys of their regions.
+const NATIONS: &[(&str, i32)] = &[
+    ("ALGERIA", 0),
+    ("ARGENTINA", 1),
+    ("BRAZIL", 1),
+    ("CANADA", 1),
+    ("EGYPT", 4),
+    ("ETHIOPIA", 0),
+    ("FRANCE", 3),
+    ("GERMANY", 3),
+    ("INDIA", 2),
+    ("INDONESIA", 2),
+    ("IRAN", 4),
+    ("IRAQ", 4),
+    ("JAPAN", 2),
+    ("JORDAN", 4),
+    ("KENYA", 0),
+    ("MOROCCO", 0),
+    ("MOZAMBIQUE", 0),
+    ("PERU", 1),
+    ("CHINA", 2),
+    ("ROMANIA", 3),
+    ("SAUDI ARABIA", 4),
+    ("VIETNAM", 2),
+    ("RUSSIA", 3),
+    ("UNITED KINGDOM", 3),
+    ("UNITED STATES", 1),
+];
+
+const SEGMENTS: &[&str] = &[
+    "AUTOMOBILE",
+    "BUILDING",
+    "FURNITURE",
+    "MACHINERY",
+    "HOUSEHOLD",
+];
+const PRIORITIES: &[&str] = &["1-URGENT", "2-HIGH", "3-MEDIUM", "4-NOT SPECIFIED", "5-LOW"];
+const SHIP_INSTRUCTIONS: &[&str] = &[
+    "DELIVER IN PERSON",
+    "COLLECT COD",
+    "NONE",
+    "TAKE BACK RETURN",
+];
+const SHIP_MODES: &[&str] = &["REG AIR", "AIR", "RAIL", "SHIP", "TRUCK", "MAIL", "FOB"];
+const TYPE_SIZES: &[&str] = &["STANDARD", "SMALL", "MEDIUM", "LARGE", "ECONOMY", "PROMO"];
+const TYPE_FINISHES: &[&str] = &["ANODIZED", "BURNISHED", "PLATED", "POLISHED", "BRUSHED"];
+const TYPE_MATERIALS: &[&str] = &["TIN", "NICKEL", "BRASS", "STEEL", "COPPER"];
+const CONTAINER_SIZES: &[&str] = &["SM", "LG", "MED", "JUMBO", "WRAP"];
+const CONTAINER_KINDS: &[&str] = &["CASE", "BOX", "BAG", "JAR", "PKG", "PACK", "CAN", "DRUM"];
+const COLORS: &[&str] = &[
+    "almond",
+    "antique",
+    "aquamarine",
+    "azure",
+    "beige",
+    "bisque",
+    "black",
+    "blanched",
+    "blue",
+    "blush",
+    "brown",
+    "burlywood",
+    "burnished",
+    "chartreuse",
+    "chiffon",
+    "chocolate",
+    "coral",
+    "cornflower",
+    "cornsilk",
+    "cream",
+    "cyan",
+    "dark",
+    "deep",
+    "dim",
+    "dodger",
+    "drab",
+];
+const WORDS: &[&str] = &[
+    "furiously",
+    "quickly",
+    "carefully",
+    "blithely",
+    "slyly",
+    "final",
+    "regular",
+    "special",
+    "pending",
+    "express",
+    "ironic",
+    "bold",
+    "even",
+    "silent",
+    "packages",
+    "requests",
+    "accounts",
+    "deposits",
+    "foxes",
+    "ideas",
+    "theodolites",
+    "pinto",
+    "beans",
+    "instructions",
+    "dependencies",
+    "sleep",
+    "wake",
+    "haggle",
+    "nag",
+    "use",
+    "boost",
+    "detect",
+    "cajole",
+    "among",
+    "along",
+    "across",
+];
+const ADDRESS_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789 ,";
+
+/// TpchTable is a table of the TPC-H-like dataset.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TpchTable {
+    Region,
+    Nation,
+    Supplier,
+    Customer,
+    Part,
+    Partsupp,
+    Orders,
+    Lineitem,
+}
+
+/// Every TPC-H table, in the order they are loaded.
+pub(crate) const TPCH_TABLES: &[TpchTable] = &[
+    TpchTable::Region,
+    TpchTable::Nation,
+    TpchTable::Supplier,
+    TpchTable::Customer,
+    TpchTable::Part,
+    TpchTable::Partsupp,
+    TpchTable::Orders,
+    TpchTable::Lineitem,
+];
+
+impl TpchTable {
+    /// name returns the name of the table, which is also the name of its collection.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            TpchTable::Region => "region",
+            TpchTable::Nation => "nation",
+            TpchTable::Supplier => "supplier",
+            TpchTable::Customer => "customer",
+            TpchTable::Part => "part",
+            TpchTable::Partsupp => "partsupp",
+            TpchTable::Orders => "orders",
+            TpchTable::Lineitem => "lineitem",
+        }
+    }
+
+    /// columns returns the fields of the table's documents, in order, with their BSON types.
+    fn columns(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            TpchTable::Region => &[
+                ("_id", "int"),
+                ("r_regionkey", "int"),
+                ("r_name", "string"),
+                ("r_comment", "string"),
+            ],
+            TpchTable::Nation => &[
+                ("_id", "int"),
+                ("n_nationkey", "int"),
+                ("n_name", "string"),
+                ("n_regionkey", "int"),
+                ("n_comment", "string"),
+            ],
+            TpchTable::Supplier => &[
+                ("_id", "int"),
+                ("s_suppkey", "int"),
+                ("s_name", "string"),
+                ("s_address", "string"),
+                ("s_nationkey", "int"),
+                ("s_phone", "string"),
+                ("s_acctbal", "double"),
+                ("s_comment", "string"),
+            ],
+            TpchTable::Customer => &[
+                ("_id", "int"),
+                ("c_custkey", "int"),
+                ("c_name", "string"),
+                ("c_address", "string"),
+                ("c_nationkey", "int"),
+                ("c_phone", "string"),
+                ("c_acctbal", "double"),
+                ("c_mktsegment", "string"),
+                ("c_comment", "string"),
+            ],
+            TpchTable::Part => &[
+                ("_id", "int"),
+                ("p_partkey", "int"),
+                ("p_name", "string"),
+                ("p_mfgr", "string"),
+                ("p_brand", "string"),
+                ("p_type", "string"),
+                ("p_size", "int"),
+                ("p_container", "string"),
+                ("p_retailprice", "double"),
+                ("p_comment", "string"),
+            ],
+            TpchTable::Partsupp => &[
+                ("_id", "int"),
+                ("ps_partkey", "int"),
+                ("ps_suppkey", "int"),
+                ("ps_availqty", "int"),
+                ("ps_supplycost", "double"),
+                ("ps_comment", "string"),
+            ],
+            TpchTable::Orders => &[
+                ("_id", "int"),
+                ("o_orderkey", "int"),
+                ("o_custkey", "int"),
+                ("o_orderstatus", "string"),
+                ("o_totalprice", "double"),
+                ("o_orderdate", "date"),
+                ("o_orderpriority", "string"),
+                ("o_clerk", "string"),
+                ("o_shippriority", "int"),
+                ("o_comment", "string"),
+            ],
+            TpchTable::Lineitem => &[
+                ("_id", "int"),
+                ("l_orderkey", "int"),
+                ("l_partkey", "int"),
+                ("l_suppkey", "int"),
+                ("l_linenumber", "int"),
+                ("l_quantity", "int"),
+                ("l_extendedprice", "double"),
+                ("l_discount", "double"),
+                ("l_tax", "double"),
+                ("l_returnflag", "string"),
+                ("l_linestatus", "string"),
+                ("l_shipdate", "date"),
+                ("l_commitdate", "date"),
+                ("l_receiptdate", "date"),
+                ("l_shipinstruct", "string"),
+                ("l_shipmode", "string"),
+                ("l_comment", "string"),
+            ],
+        }
+    }
+
+    /// schema returns the JSON schema every document of the table conforms to.
+    pub(crate) fn schema(self) -> Bson {
+        let columns = self.columns();
+        let properties = columns
+            .iter()
+            .map(|(name, bson_type)| (name.to_string(), Bson::from(doc! {"bsonType": *bson_type})))
+            .collect::<Document>();
+        Bson::from(doc! {
+            "bsonType": "object",
+            "required": columns.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+            "properties": properties,
+            "additionalProperties": false,
+        })
+    }
+}
+
+/// TpchOptions selects the TPC-H-like dataset for an entry. An entry with a tpch field instead
+/// of a collection or view is expanded into one collection entry per table when its data file is
+/// read, each with the table's schema, so the dataset gets matching __sql_schemas documents or ADF
+/// schemas.
+///
+/// Example:
+///   - db: "tpch"
+///     tpch: { scale_factor: 0.1 }
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TpchOptions {
+    /// scale_factor scales the number of rows in every table except region and nation, as in
+    /// TPC-H: at scale factor 1, there are 150,000 customers, 1,500,000 orders, and about
+    /// 6,000,000 lineitems. Required; must be greater than 0 and at most 100.
+    pub scale_factor: f64,
+
+    /// seed seeds the random number generator. Defaults to 0.
+    #[serde(default)]
+    pub seed: u64,
+
+    /// tables lists the tables to load. Optional. Defaults to every table.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tables: Vec<TpchTable>,
+}
+
+/// TpchTableOptions describes the documents of a single TPC-H table. The documents are generated
+/// from a seeded random number generator, so the same options always produce the same documents,
+/// and tables generated with the same scale factor and seed reference each other consistently.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TpchTableOptions {
+    /// table is the table to generate. Required.
+    pub table: TpchTable,
+
+    /// scale_factor scales the number of rows, as described for TpchOptions. Required.
+    pub scale_factor: f64,
+
+    /// seed seeds the random number generator. Defaults to 0.
+    #[serde(default)]
+    pub seed: u64,
+}
+
+/// entries returns a collection entry in the provided database for every table the options
+/// select. Each entry has the table's schema.
+pub(crate) fn entries(db: &str, options: &TpchOptions) -> Vec<TestDataEntry> {
+    let tables = if options.tables.is_empty() {
+        TPCH_TABLES
+    } else {
+        options.tables.as_slice()
+    };
+    tables
+        .iter()
+        .map(|&table| TestDataEntry {
+            db: db.to_string(),
+            collection: Some(CollectionData {
+                name: table.name().to_string(),
+                docs: vec![],
+                docs_file: None,
+                csv: None,
+                strict_extended_json: false,
+                generate: None,
+                generate_from_schema: None,
+                tpch: Some(TpchTableOptions {
+                    table,
+                    scale_factor: options.scale_factor,
+                    seed: options.seed,
+                }),
+                options: None,
+                schema_validator: None,
+                indexes: None,
+            }),
+            view: None,
+            schema: Some(table.schema()),
+            tpch: None,
+            location: String::new(),
+        })
+        .collect()
+}
+
+/// validate_scale_factor returns a message describing why the scale factor is invalid, if it is.
+pub(crate) fn validate_scale_factor(scale_factor: f64) -> Vec<String> {
+    if scale_factor > 0.0 && scale_factor <= MAX_SCALE_FACTOR {
+        vec![]
+    } else {
+        vec![format!(
+            "scale_factor must be greater than 0 and at most {MAX_SCALE_FACTOR}, found {scale_factor}"
+        )]
+    }
+}
+
+impl TpchTableOptions {
+    /// documents returns the table's documents, one at a time. Returns an InvalidGenerator error if
+    /// the scale factor is invalid.
+    pub(crate) fn documents(&self) -> Result<Box<dyn Iterator<Item = Bson> + Send>> {
+        let errors = validate_scale_factor(self.scale_factor);
+        if !errors.is_empty() {
+            return Err(DataLoaderError::InvalidGenerator(errors));
+        }
+
+        let scale = Scale::new(self.scale_factor);
+        let seed = self.seed;
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        rng.set_stream(self.table as u64);
+
+        Ok(match self.table {
+            TpchTable::Region => Box::new((0..REGIONS.len() as i32).map(move |key| {
+                Bson::from(doc! {
+                    "_id": key,
+                    "r_regionkey": key,
+                    "r_name": REGIONS[key as usize],
+                    "r_comment": comment(&mut rng),
+                })
+            })),
+            TpchTable::Nation => Box::new((0..NATIONS.len() as i32).map(move |key| {
+                let (name, region) = NATIONS[key as usize];
+                Bson::from(doc! {
+                    "_id": key,
+                    "n_nationkey": key,
+                    "n_name": name,
+                    "n_regionkey": region,
+                    "n_comment": comment(&mut rng),
+                })
+            })),
+            TpchTable::Supplier => Box::new((1..=scale.suppliers).map(move |key| {
+                let nation = rng.random_range(0..NATIONS.len() as i32);
+                Bson::from(doc! {
+                    "_id": key,
+                    "s_suppkey": key,
+                    "s_name": format!("Supplier#{key:09}"),
+                    "s_address": address(&mut rng),
+                    "s_nationkey": nation,
+                    "s_phone": phone(&mut rng, nation),
+                    "s_acctbal": cents(&mut rng, -99_999, 999_999),
+                    "s_comment": comment(&mut rng),
+                })
+            })),
+            TpchTable::Customer => Box::new((1..=scale.customers).map(move |key| {
+                let nation = rng.random_range(0..NATIONS.len() as i32);
+                Bson::from(doc! {
+                    "_id": key,
+                    "c_custkey": key,
+                    "c_name": format!("Customer#{key:09}"),
+                    "c_address": address(&mut rng),
+                    "c_nationkey": nation,
+                    "c_phone": phone(&mut rng, nation),
+                    "c_acctbal": cents(&mut rng, -99_999, 999_999),
+                    "c_mktsegment": pick(&mut rng, SEGMENTS),
+                    "c_comment": comment(&mut rng),
+                })
+            })),
+            TpchTable::Part => Box::new((1..=scale.parts).map(move |key| {
+                let manufacturer = rng.random_range(1..=5);
+                Bson::from(doc! {
+                    "_id": key,
+                    "p_partkey": key,
+                    "p_name": (0..5).map(|_| pick(&mut rng, COLORS)).collect::<Vec<_>>().join(" "),
+                    "p_mfgr": format!("Manufacturer#{manufacturer}"),
+                    "p_brand": format!("Brand#{manufacturer}{}", rng.random_range(1..=5)),
+                    "p_type": format!(
+                        "{} {} {}",
+                        pick(&mut rng, TYPE_SIZES),
+                        pick(&mut rng, TYPE_FINISHES),
+                        pick(&mut rng, TYPE_MATERIALS)
+                    ),
+                    "p_size": rng.random_range(1..=50),
+                    "p_container": format!(
+                        "{} {}",
+                        pick(&mut rng, CONTAINER_SIZES),
+                        pick(&mut rng, CONTAINER_KINDS)
+                    ),
+                    "p_retailprice": retail_price(key),
+                    "p_comment": comment(&mut rng),
+                })
+            })),
+            TpchTable::Partsupp => Box::new((1..=scale.parts).flat_map(move |part| {
+                (0..4)
+                    .map(|i| {
+                        Bson::from(doc! {
+                            "_id": (part - 1) * 4 + i + 1,
+                            "ps_partkey": part,
+                            "ps_suppkey": supplier_of_part(&scale, part, i),
+                            "ps_availqty": rng.random_range(1..=9999),
+                            "ps_supplycost": cents(&mut rng, 100, 100_000),
+                            "ps_comment": comment(&mut rng),
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })),
+            TpchTable::Orders => {
+                Box::new((1..=scale.orders).map(move |key| Bson::from(order(seed, &scale, key).0)))
+            }
+            TpchTable::Lineitem => Box::new(
+                (1..=scale.orders)
+                    .flat_map(move |key| order(seed, &scale, key).1)
+                    .zip(1..)
+                    .map(|(line, id): (Document, i32)| {
+                        let mut doc = doc! {"_id": id};
+                        doc.extend(line);
+                        Bson::from(doc)
+                    }),
+            ),
+        })
+    }
+}
+
+/// Scale holds the number of rows of the scaled tables at a scale factor.
+#[derive(Debug, Clone, Copy)]
+struct Scale {
+    suppliers: i32,
+    customers: i32,
+    parts: i32,
+    orders: i32,
+    clerks: i32,
+}
+
+impl Scale {
+    fn new(scale_factor: f64) -> Self {
+        let scaled = |base: f64, min: i32| ((base * scale_factor).round() as i32).max(min);
+        Scale {
+            // Every part is supplied by 4 different suppliers.
+            suppliers: scaled(10_000.0, 4),
+            customers: scaled(150_000.0, 1),
+            parts: scaled(200_000.0, 1),
+            orders: scaled(1_500_000.0, 1),
+            clerks: scaled(1_000.0, 1),
+        }
+    }
+}
+
+/// order returns the order with the provided key and its lineitems, which lack an _id. Every order
+/// has its own random number generator, so the orders and lineitem tables agree on them.
+fn order(seed: u64, scale: &Scale, key: i32) -> (Document, Vec<Document>) {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(TPCH_TABLES.len() as u64 + key as u64);
+
+    let customer = rng.random_range(1..=scale.customers);
+    let order_day = rng.random_range(0..=LAST_ORDER_DAY);
+    let mut total_price = 0.0;
+    let mut statuses = vec![];
+    let mut lines = vec![];
+    for line_number in 1..=rng.random_range(1..=7) {
+        let part = rng.random_range(1..=scale.parts);
+        let quantity = rng.random_range(1..=50);
+        let extended_price = round_cents(quantity as f64 * retail_price(part));
+        let discount = rng.random_range(0..=10) as f64 / 100.0;
+        let tax = rng.random_range(0..=8) as f64 / 100.0;
+        let ship_day = order_day + rng.random_range(1..=121);
+        let commit_day = order_day + rng.random_range(30..=90);
+        let receipt_day = ship_day + rng.random_range(1..=30);
+        let return_flag = if receipt_day <= CURRENT_DAY {
+            pick(&mut rng, &["R", "A"])
+        } else {
+            "N"
+        };
+        let status = if ship_day > CURRENT_DAY { "O" } else { "F" };
+
+        total_price += extended_price * (1.0 + tax) * (1.0 - discount);
+        statuses.push(status);
+        lines.push(doc! {
+            "l_orderkey": key,
+            "l_partkey": part,
+            "l_suppkey": supplier_of_part(scale, part, rng.random_range(0..4)),
+            "l_linenumber": line_number,
+            "l_quantity": quantity,
+            "l_extendedprice": extended_price,
+            "l_discount": discount,
+            "l_tax": tax,
+            "l_returnflag": return_flag,
+            "l_linestatus": status,
+            "l_shipdate": date(ship_day),
+            "l_commitdate": date(commit_day),
+            "l_receiptdate": date(receipt_day),
+            "l_shipinstruct": pick(&mut rng, SHIP_INSTRUCTIONS),
+            "l_shipmode": pick(&mut rng, SHIP_MODES),
+            "l_comment": comment(&mut rng),
+        });
+    }
+
+    let order_status = if statuses.iter().all(|s| *s == "F") {
+        "F"
+    } else if statuses.iter().all(|s| *s == "O") {
+        "O"
+    } else {
+        "P"
+    };
+    let order = doc! {
+        "_id": key,
+        "o_orderkey": key,
+        "o_custkey": customer,
+        "o_orderstatus": order_status,
+        "o_totalprice": round_cents(total_price),
+        "o_orderdate": date(order_day),
+        "o_orderpriority": pick(&mut rng, PRIORITIES),
+        "o_clerk": format!("Clerk#{:09}", rng.random_range(1..=scale.clerks)),
+        "o_shippriority": 0,
+        "o_comment": comment(&mut rng),
+    };
+    (order, lines)
+}
+
+/// supplier_of_part returns the key of the i-th of the 4 suppliers of a part. As in TPC-H, the
+/// suppliers are a quarter of the supplier table apart, so they are always distinct, and the
+/// starting supplier is offset for every pass over the supplier table.
+fn supplier_of_part(scale: &Scale, part: i32, i: i32) -> i32 {
+    let (suppliers, part, i) = (scale.suppliers as i64, part as i64, i as i64);
+    ((part - 1 + (part - 1) / suppliers + i * (suppliers / 4)) % suppliers + 1) as i32
+}
+
+/// retail_price returns the price of a part, using the TPC-H formula.
+fn retail_price(part: i32) -> f64 {
+    (90_000 + (part / 10) % 20_001 + 100 * (part % 1_000)) as f64 / 100.0
+}
+
+fn round_cents(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+/// cents returns a uniformly distributed amount of money between min and max cents, inclusive.
+fn cents(rng: &mut ChaCha8Rng, min: i64, max: i64) -> f64 {
+    rng.random_range(min..=max) as f64 / 100.0
+}
+
+fn date(day: i64) -> DateTime {
+    DateTime::from_millis(START_DATE_MILLIS + day * DAY_MILLIS)
+}
+
+fn pick<'a>(rng: &mut ChaCha8Rng, values: &[&'a str]) -> &'a str {
+    values[rng.random_range(0..values.len())]
+}
+
+fn comment(rng: &mut ChaCha8Rng) -> String {
+    (0..rng.random_range(3..=10))
+        .map(|_| pick(rng, WORDS))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn address(rng: &mut ChaCha8Rng) -> String {
+    (0..rng.random_range(10..=40))
+        .map(|_| ADDRESS_ALPHABET[rng.random_range(0..ADDRESS_ALPHABET.len())] as char)
+        .collect()
+}
+
+fn phone(rng: &mut ChaCha8Rng, nation: i32) -> String {
+    format!(
+        "{}-{}-{}-{}",
+        nation + 10,
+        rng.random_range(100..1000),
+        rng.random_range(100..1000),
+        rng.random_range(1000..10000)
+    )
+}
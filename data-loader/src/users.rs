@@ -0,0 +1,514 @@
+use crate::{
+    fingerprint::METADATA_DB,
+    journal::{Journal, Operation},
+    DataLoaderError, Result, TestDataFile,
+};
+use mongodb::{
+    bson::{self, doc, Bson, Document},
+    error::ErrorKind,
+    Client, Collection,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The authentication mechanisms a user's credentials can be created for.
+pub(crate) const MECHANISMS: &[&str] = &["SCRAM-SHA-1", "SCRAM-SHA-256"];
+
+/// The collection in which the definition of every user and role the data loader created is stored,
+/// so that a failed load can recreate the ones it replaced. Each document has the form
+/// { _id: "<user|role> <db>.<name>", definition: <UserData or RoleData> }. User definitions
+/// include their passwords, since the mongod never returns them.
+const PRINCIPAL_COLLECTION: &str = "principals";
+
+/// The server error codes returned when dropping a user or role that does not exist.
+const USER_NOT_FOUND: i32 = 11;
+const ROLE_NOT_FOUND: i32 = 31;
+
+/// UserData describes a user that is created on the mongod, so that tests can authenticate as a
+/// principal with known credentials and privileges.
+///
+/// Example:
+///   users:
+///     - { db: "admin", user: "db2reader", password: "pencil", roles: [ { role: "read", db: "db2" } ] }
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct UserData {
+    /// The database the user is created in, which is also its authentication database. Required.
+    pub db: String,
+
+    /// The name of the user. Required.
+    pub user: String,
+
+    /// The password of the user. Required.
+    pub password: String,
+
+    /// The SCRAM mechanisms the credentials are created for, "SCRAM-SHA-1" and/or
+    /// "SCRAM-SHA-256". Optional. Defaults to both.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mechanisms: Vec<String>,
+
+    /// The roles granted to the user, either built-in roles or roles defined in a data file.
+    /// Optional.
+    #[serde(default)]
+    pub roles: Vec<RoleName>,
+}
+
+/// RoleData describes a custom role that is created on the mongod, with the provided privileges
+/// and inherited roles.
+///
+/// Example:
+///   roles:
+///     - db: "db2"
+///       role: "calcsReader"
+///       privileges:
+///         - { resource: { db: "db2", collection: "calcs" }, actions: [ "find" ] }
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RoleData {
+    /// The database the role is created in. Required.
+    pub db: String,
+
+    /// The name of the role. Required.
+    pub role: String,
+
+    /// The privileges of the role, in the createRole command's format:
+    ///   { resource: { db: <db>, collection: <collection> }, actions: [ <action>, ... ] }
+    /// Optional.
+    #[serde(default)]
+    pub privileges: Vec<Document>,
+
+    /// The roles this role inherits. Optional.
+    #[serde(default)]
+    pub roles: Vec<RoleName>,
+}
+
+/// RoleName names a role, either as a string, for a role in the same database as the user or role
+/// it is granted to, or as a role and the database it is defined in.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum RoleName {
+    Name(String),
+    Qualified { role: String, db: String },
+}
+
+impl RoleName {
+    fn to_bson(&self) -> Bson {
+        match self {
+            RoleName::Name(role) => Bson::String(role.clone()),
+            RoleName::Qualified { role, db } => Bson::Document(doc! {"role": role, "db": db}),
+        }
+    }
+
+    /// qualified returns the database and name of the role, when granted to a user or role in db.
+    fn qualified<'a>(&'a self, db: &'a str) -> (&'a str, &'a str) {
+        match self {
+            RoleName::Name(role) => (db, role),
+            RoleName::Qualified { role, db } => (db, role),
+        }
+    }
+}
+
+fn role_names(roles: &[RoleName]) -> Vec<Bson> {
+    roles.iter().map(RoleName::to_bson).collect()
+}
+
+/// check_users_and_roles returns an UnownedPrincipals error naming every user and role of the test
+/// data files that already exists on the mongod but was not created by the data loader, unless
+/// force is true. Their definitions are unknown, so they could not be restored if a load that
+/// replaced them failed.
+pub(crate) async fn check_users_and_roles(
+    client: &Client,
+    test_data_files: &[TestDataFile],
+    force: bool,
+) -> Result<()> {
+    if force {
+        return Ok(());
+    }
+    let mut unowned = vec![];
+    for (kind, db, name) in principals(test_data_files) {
+        if exists(client, kind, db, name).await?
+            && recorded::<Document>(client, kind, db, name)
+                .await?
+                .is_none()
+        {
+            unowned.push(format!("{kind} {name} in {db}"));
+        }
+    }
+    if unowned.is_empty() {
+        Ok(())
+    } else {
+        Err(DataLoaderError::UnownedPrincipals(unowned))
+    }
+}
+
+/// create_users_and_roles creates every role, and then every user, in the test data files, so that
+/// users can be granted the roles. Roles are created in the order given by role_creation_order, so
+/// that they can inherit each other. Existing users and roles with the same names are dropped
+/// first. Every change is recorded in the journal: the users and roles created are dropped if the
+/// run fails, and those dropped are recreated from the definitions recorded when the data loader
+/// created them. Users and roles the data loader did not create are not restored; see
+/// check_users_and_roles.
+pub(crate) async fn create_users_and_roles(
+    client: &Client,
+    test_data_files: &[TestDataFile],
+    journal: &Journal,
+) -> Result<()> {
+    let roles = role_creation_order(test_data_files)
+        .map_err(|e| DataLoaderError::InvalidTestData(vec![e]))?;
+    let users = test_data_files
+        .iter()
+        .flat_map(|tdf| &tdf.users)
+        .collect::<Vec<_>>();
+
+    // Users are dropped before roles, since they may be granted them.
+    for user in &users {
+        let previous = recorded::<UserData>(client, "user", &user.db, &user.user).await?;
+        if drop_user(client, &user.db, &user.user).await? {
+            match previous {
+                Some(previous) => journal.record(Operation::DroppedUser(previous)),
+                None => println!(
+                    "\tReplacing user {} in {}, which is not restored if the load fails",
+                    user.user, user.db
+                ),
+            }
+        }
+    }
+
+    // Roles are dropped before the roles they inherited, so that rollback recreates them in the
+    // order they were created.
+    let mut previous_roles = vec![];
+    for role in &roles {
+        if !exists(client, "role", &role.db, &role.role).await? {
+            continue;
+        }
+        match recorded::<RoleData>(client, "role", &role.db, &role.role).await? {
+            Some(previous) => previous_roles.push(previous),
+            None => {
+                drop_role(client, &role.db, &role.role).await?;
+                println!(
+                    "\tReplacing role {} in {}, which is not restored if the load fails",
+                    role.role, role.db
+                );
+            }
+        }
+    }
+    let previous_roles = order_roles(previous_roles.iter().map(|role| ("", role)).collect())
+        .map_err(|e| DataLoaderError::InvalidTestData(vec![e]))?;
+    for previous in previous_roles.into_iter().rev() {
+        drop_role(client, &previous.db, &previous.role).await?;
+        journal.record(Operation::DroppedRole(previous.clone()));
+    }
+
+    for role in roles {
+        journal.record(Operation::CreatedRole {
+            db: role.db.clone(),
+            role: role.role.clone(),
+        });
+        create_role(client, role).await?;
+        record_definition(client, "role", &role.db, &role.role, role, journal).await?;
+        println!("\tCreated role {} in {}", role.role, role.db);
+    }
+    for user in users {
+        journal.record(Operation::CreatedUser {
+            db: user.db.clone(),
+            user: user.user.clone(),
+        });
+        create_user(client, user).await?;
+        record_definition(client, "user", &user.db, &user.user, user, journal).await?;
+        println!("\tCreated user {} in {}", user.user, user.db);
+    }
+    Ok(())
+}
+
+/// create_role runs the createRole command for the role.
+pub(crate) async fn create_role(client: &Client, role: &RoleData) -> Result<()> {
+    client
+        .database(&role.db)
+        .run_command(doc! {
+            "createRole": &role.role,
+            "privileges": role.privileges.clone(),
+            "roles": role_names(&role.roles),
+        })
+        .await?;
+    Ok(())
+}
+
+/// create_user runs the createUser command for the user.
+pub(crate) async fn create_user(client: &Client, user: &UserData) -> Result<()> {
+    let mut command = doc! {
+        "createUser": &user.user,
+        "pwd": &user.password,
+        "roles": role_names(&user.roles),
+    };
+    if !user.mechanisms.is_empty() {
+        command.insert("mechanisms", user.mechanisms.clone());
+    }
+    client.database(&user.db).run_command(command).await?;
+    Ok(())
+}
+
+/// principals returns the kind, database, and name of every role, and then every user, in the test
+/// data files.
+fn principals(test_data_files: &[TestDataFile]) -> impl Iterator<Item = (&str, &str, &str)> {
+    let roles = test_data_files
+        .iter()
+        .flat_map(|tdf| &tdf.roles)
+        .map(|r| ("role", r.db.as_str(), r.role.as_str()));
+    let users = test_data_files
+        .iter()
+        .flat_map(|tdf| &tdf.users)
+        .map(|u| ("user", u.db.as_str(), u.user.as_str()));
+    roles.chain(users)
+}
+
+/// exists returns whether the user or role exists on the mongod.
+async fn exists(client: &Client, kind: &str, db: &str, name: &str) -> Result<bool> {
+    let (command, field) = match kind {
+        "user" => ("usersInfo", "users"),
+        _ => ("rolesInfo", "roles"),
+    };
+    let res = client
+        .database(db)
+        .run_command(doc! {command: name})
+        .await?;
+    Ok(res.get_array(field).is_ok_and(|found| !found.is_empty()))
+}
+
+fn principal_collection(client: &Client) -> Collection<Document> {
+    client
+        .database(METADATA_DB)
+        .collection(PRINCIPAL_COLLECTION)
+}
+
+fn principal_id(kind: &str, db: &str, name: &str) -> String {
+    format!("{kind} {db}.{name}")
+}
+
+/// recorded returns the definition recorded when the data loader created the user or role, or
+/// None if it did not create it.
+async fn recorded<T: DeserializeOwned>(
+    client: &Client,
+    kind: &str,
+    db: &str,
+    name: &str,
+) -> Result<Option<T>> {
+    let Some(document) = principal_collection(client)
+        .find_one(doc! {"_id": principal_id(kind, db, name)})
+        .await?
+    else {
+        return Ok(None);
+    };
+    let definition = document.get("definition").cloned().unwrap_or(Bson::Null);
+    Ok(Some(bson::from_bson(definition)?))
+}
+
+/// record_definition replaces the recorded definition of the user or role with the one it was just
+/// created with. Both changes are recorded in the journal.
+async fn record_definition(
+    client: &Client,
+    kind: &str,
+    db: &str,
+    name: &str,
+    definition: &impl Serialize,
+    journal: &Journal,
+) -> Result<()> {
+    let collection = principal_collection(client);
+    let id = principal_id(kind, db, name);
+    if let Some(document) = collection.find_one_and_delete(doc! {"_id": &id}).await? {
+        journal.record(Operation::DeletedDocument {
+            db: METADATA_DB.to_string(),
+            collection: PRINCIPAL_COLLECTION.to_string(),
+            document,
+        });
+    }
+    collection
+        .insert_one(doc! {"_id": &id, "definition": bson::to_bson(definition)?})
+        .await?;
+    journal.record(Operation::InsertedDocument {
+        db: METADATA_DB.to_string(),
+        collection: PRINCIPAL_COLLECTION.to_string(),
+        id: Bson::String(id),
+    });
+    Ok(())
+}
+
+/// role_creation_order returns the roles of the test data files ordered so that every role comes
+/// after the roles it inherits that are defined in the data files. Otherwise, roles keep the order
+/// they are defined in. Returns an error naming the first role found to inherit itself, directly
+/// or through other roles.
+pub(crate) fn role_creation_order(
+    test_data_files: &[TestDataFile],
+) -> std::result::Result<Vec<&RoleData>, String> {
+    let files = test_data_files
+        .iter()
+        .map(|tdf| tdf.path.display().to_string())
+        .collect::<Vec<_>>();
+    order_roles(
+        test_data_files
+            .iter()
+            .zip(&files)
+            .flat_map(|(tdf, file)| tdf.roles.iter().map(move |role| (file.as_str(), role)))
+            .collect(),
+    )
+}
+
+/// order_roles orders the roles so that every role comes after the roles it inherits among them,
+/// and otherwise keeps their order. Every role is paired with the file it is defined in, which
+/// names it in the error returned if it inherits itself.
+pub(crate) fn order_roles<'a>(
+    roles: Vec<(&str, &'a RoleData)>,
+) -> std::result::Result<Vec<&'a RoleData>, String> {
+    let indexes = roles
+        .iter()
+        .enumerate()
+        .map(|(i, (_, role))| ((role.db.as_str(), role.role.as_str()), i))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut visited = vec![Visit::New; roles.len()];
+    let mut order = vec![];
+    for i in 0..roles.len() {
+        visit(i, &roles, &indexes, &mut visited, &mut order)?;
+    }
+    Ok(order)
+}
+
+/// Visit is the state of a role during order_roles's depth-first search.
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+    New,
+    InProgress,
+    Done,
+}
+
+/// visit appends the role at index i to the order, after every role it inherits, unless it has
+/// already been appended.
+fn visit<'a>(
+    i: usize,
+    roles: &[(&str, &'a RoleData)],
+    indexes: &BTreeMap<(&str, &str), usize>,
+    visited: &mut [Visit],
+    order: &mut Vec<&'a RoleData>,
+) -> std::result::Result<(), String> {
+    let (file, role) = roles[i];
+    match visited[i] {
+        Visit::Done => return Ok(()),
+        Visit::InProgress => {
+            return Err(format!(
+                "{file}: role {} in {} inherits itself",
+                role.role, role.db
+            ))
+        }
+        Visit::New => {}
+    }
+    visited[i] = Visit::InProgress;
+    for inherited in &role.roles {
+        if let Some(&j) = indexes.get(&inherited.qualified(&role.db)) {
+            visit(j, roles, indexes, visited, order)?;
+        }
+    }
+    visited[i] = Visit::Done;
+    order.push(role);
+    Ok(())
+}
+
+/// drop_users_and_roles drops every user, and then every role, in the test data files, if they
+/// exist.
+pub(crate) async fn drop_users_and_roles(
+    client: &Client,
+    test_data_files: &[TestDataFile],
+) -> Result<()> {
+    for user in test_data_files.iter().flat_map(|tdf| &tdf.users) {
+        if drop_user(client, &user.db, &user.user).await? {
+            println!("\tDropped user {} in {}", user.user, user.db);
+        }
+    }
+    for role in test_data_files.iter().flat_map(|tdf| &tdf.roles) {
+        if drop_role(client, &role.db, &role.role).await? {
+            println!("\tDropped role {} in {}", role.role, role.db);
+        }
+    }
+    let ids = principals(test_data_files)
+        .map(|(kind, db, name)| principal_id(kind, db, name))
+        .collect::<Vec<_>>();
+    principal_collection(client)
+        .delete_many(doc! {"_id": {"$in": ids}})
+        .await?;
+    Ok(())
+}
+
+/// drop_user drops a user, and returns false if it did not exist.
+pub(crate) async fn drop_user(client: &Client, db: &str, user: &str) -> Result<bool> {
+    ignore_not_found(
+        client
+            .database(db)
+            .run_command(doc! {"dropUser": user})
+            .await,
+        USER_NOT_FOUND,
+    )
+}
+
+/// drop_role drops a role, and returns false if it did not exist.
+pub(crate) async fn drop_role(client: &Client, db: &str, role: &str) -> Result<bool> {
+    ignore_not_found(
+        client
+            .database(db)
+            .run_command(doc! {"dropRole": role})
+            .await,
+        ROLE_NOT_FOUND,
+    )
+}
+
+fn ignore_not_found(res: mongodb::error::Result<Document>, not_found: i32) -> Result<bool> {
+    match res {
+        Ok(_) => Ok(true),
+        Err(e) if matches!(&*e.kind, ErrorKind::Command(c) if c.code == not_found) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// validate_users_and_roles checks that every user and role has a name and a database, that no
+/// user or role is defined more than once, that users only use supported mechanisms, and that no
+/// role inherits itself.
+pub(crate) fn validate_users_and_roles(test_data_files: &[TestDataFile]) -> Vec<String> {
+    let mut errors = vec![];
+    let mut defined = BTreeMap::new();
+    for tdf in test_data_files {
+        let file = tdf.path.display();
+        let principals = tdf
+            .roles
+            .iter()
+            .map(|r| ("role", &r.db, &r.role))
+            .chain(tdf.users.iter().map(|u| ("user", &u.db, &u.user)));
+        for (kind, db, name) in principals {
+            if db.is_empty() || name.is_empty() {
+                errors.push(format!(
+                    "{file}: {kind} {name:?} in {db:?}: the database and name must not be empty"
+                ));
+            } else if let Some(previous) = defined.insert((kind, db, name), file.to_string()) {
+                errors.push(format!(
+                    "{file}: {kind} {name} in {db} is already defined in {previous}"
+                ));
+            }
+        }
+        for user in &tdf.users {
+            if user.password.is_empty() {
+                errors.push(format!(
+                    "{file}: user {} in {}: the password must not be empty",
+                    user.user, user.db
+                ));
+            }
+            for mechanism in &user.mechanisms {
+                if !MECHANISMS.contains(&mechanism.as_str()) {
+                    errors.push(format!(
+                        "{file}: user {} in {}: mechanism {mechanism:?} must be one of {MECHANISMS:?}",
+                        user.user, user.db
+                    ));
+                }
+            }
+        }
+    }
+    if let Err(e) = role_creation_order(test_data_files) {
+        errors.push(e);
+    }
+    errors
+}
@@ -0,0 +1,552 @@
+use crate::{
+    coverage::validate_schema_generate_options,
+    csv::validate_csv_options,
+    fingerprint::METADATA_DB,
+    generate::validate_generate_options,
+    journal::BACKUP_PREFIX,
+    schema,
+    source::DOCS_FILE_EXTENSIONS,
+    tpch::validate_scale_factor,
+    users::{role_creation_order, validate_users_and_roles},
+    validator::{creation_options, validate_schema_validator},
+    DataLoaderError, Result, TestDataEntry, TestDataFile,
+};
+use mongodb::{
+    bson::{self, doc, Bson, Document},
+    options::CreateCollectionOptions,
+};
+use std::collections::BTreeMap;
+
+/// The name of the collection in which mongod-mode schemas are stored.
+pub(crate) const SCHEMA_COLLECTION: &str = "__sql_schemas";
+
+/// The values accepted by the MongoDB JSON-schema `bsonType` keyword.
+const BSON_TYPES: &[&str] = &[
+    "double",
+    "string",
+    "object",
+    "array",
+    "binData",
+    "undefined",
+    "objectId",
+    "bool",
+    "date",
+    "null",
+    "regex",
+    "dbPointer",
+    "javascript",
+    "symbol",
+    "javascriptWithScope",
+    "int",
+    "timestamp",
+    "long",
+    "decimal",
+    "minKey",
+    "maxKey",
+    "number",
+];
+
+/// The string values accepted as index key types.
+const INDEX_KEY_TYPES: &[&str] = &["text", "2d", "2dsphere", "hashed", "wildcard"];
+
+/// validate checks every entry in every test data file without connecting to a server, including
+/// that every document conforms to its entry's schema. All errors are collected, and if there are
+/// any, they are returned together as an InvalidTestData error.
+pub(crate) fn validate(test_data_files: &[TestDataFile]) -> Result<()> {
+    let mut errors = structure_errors(test_data_files, true);
+    errors.extend(document_violations(test_data_files));
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(DataLoaderError::InvalidTestData(errors))
+    }
+}
+
+/// validate_load runs the checks validate does that a load depends on, checking documents against
+/// their entry's schema only if check_documents is true. Views may be defined on namespaces that
+/// are not in the data files, since a load may only cover some of the files that define a
+/// database.
+pub(crate) fn validate_load(test_data_files: &[TestDataFile], check_documents: bool) -> Result<()> {
+    let mut errors = structure_errors(test_data_files, false);
+    if check_documents {
+        errors.extend(document_violations(test_data_files));
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(DataLoaderError::InvalidTestData(errors))
+    }
+}
+
+/// structure_errors returns a message for every problem with the entries, users, and roles of the
+/// test data files, other than documents that do not conform to their entry's schema. Views
+/// defined on namespaces that are not in the data files are only reported if check_view_targets
+/// is true.
+fn structure_errors(test_data_files: &[TestDataFile], check_view_targets: bool) -> Vec<String> {
+    let mut errors = vec![];
+
+    // Gather every namespace defined in the data files first, so view targets can be checked
+    // regardless of the order in which the files were read.
+    let mut namespaces: BTreeMap<(String, String), String> = BTreeMap::new();
+    for tdf in test_data_files {
+        for (index, entry) in tdf.dataset.iter().enumerate() {
+            let location = entry.location(&tdf.path, index);
+            let Some(name) = entry_name(entry) else {
+                errors.push(format!(
+                    "{location}: exactly one of 'view' or 'collection' must be specified"
+                ));
+                continue;
+            };
+
+            errors.extend(
+                validate_namespace(&entry.db, name)
+                    .into_iter()
+                    .map(|e| format!("{location}: {e}")),
+            );
+
+            if let Some(previous) =
+                namespaces.insert((entry.db.clone(), name.to_string()), location.clone())
+            {
+                errors.push(format!(
+                    "{location}: namespace {}.{name} is already defined at {previous}",
+                    entry.db
+                ));
+            }
+        }
+    }
+
+    for tdf in test_data_files {
+        for (index, entry) in tdf.dataset.iter().enumerate() {
+            let location = entry.location(&tdf.path, index);
+
+            if let Some(c) = &entry.collection {
+                if let Some(docs_file) = &c.docs_file {
+                    let extension = docs_file.extension().and_then(|ext| ext.to_str());
+                    if !extension.is_some_and(|ext| DOCS_FILE_EXTENSIONS.contains(&ext)) {
+                        errors.push(format!(
+                            "{location}: docs_file {} must have one of the extensions {DOCS_FILE_EXTENSIONS:?}",
+                            docs_file.display()
+                        ));
+                    } else if !docs_file.is_file() {
+                        errors.push(format!(
+                            "{location}: docs_file {} does not exist",
+                            docs_file.display()
+                        ));
+                    }
+                }
+                match (&c.csv, &c.docs_file) {
+                    (Some(options), Some(docs_file))
+                        if docs_file.extension().is_some_and(|ext| ext == "csv") =>
+                    {
+                        errors.extend(
+                            validate_csv_options(options)
+                                .into_iter()
+                                .map(|e| format!("{location}: csv: {e}")),
+                        );
+                    }
+                    (Some(_), _) => errors.push(format!(
+                        "{location}: csv options require a '.csv' docs_file"
+                    )),
+                    (None, _) => {}
+                }
+                if let Some(options) = &c.generate {
+                    errors.extend(
+                        validate_generate_options(options)
+                            .into_iter()
+                            .map(|e| format!("{location}: generate: {e}")),
+                    );
+                }
+                if c.generate_from_schema.is_some() {
+                    errors.extend(
+                        validate_schema_generate_options(entry.schema.as_ref())
+                            .into_iter()
+                            .map(|e| format!("{location}: generate_from_schema: {e}")),
+                    );
+                }
+                if let Some(options) = &c.tpch {
+                    errors.extend(
+                        validate_scale_factor(options.scale_factor)
+                            .into_iter()
+                            .map(|e| format!("{location}: tpch: {e}")),
+                    );
+                }
+                if let Some(options) = &c.options {
+                    errors.extend(
+                        validate_collection_options(options)
+                            .into_iter()
+                            .map(|e| format!("{location}: options: {e}")),
+                    );
+                }
+                errors.extend(
+                    validate_schema_validator(c, entry.schema.as_ref())
+                        .into_iter()
+                        .map(|e| format!("{location}: schema_validator: {e}")),
+                );
+                for (i, index_model) in c.indexes.iter().flatten().enumerate() {
+                    errors.extend(
+                        validate_index_key(&index_model.keys)
+                            .into_iter()
+                            .map(|e| format!("{location}: index {i}: {e}")),
+                    );
+                }
+            }
+
+            if let Some(d) = entry.view.as_ref().and_then(|v| v.definition.as_ref()) {
+                let view_name = &entry.view.as_ref().unwrap().name;
+                if &d.view_on == view_name {
+                    errors.push(format!(
+                        "{location}: view {view_name} cannot be defined on itself"
+                    ));
+                } else if check_view_targets
+                    && !namespaces.contains_key(&(entry.db.clone(), d.view_on.clone()))
+                {
+                    errors.push(format!(
+                        "{location}: view {view_name} is defined on {}.{}, which is not defined in any data file",
+                        entry.db, d.view_on
+                    ));
+                }
+                for (i, stage) in d.pipeline.iter().enumerate() {
+                    if stage.len() != 1 || !stage.keys().all(|k| k.starts_with('$')) {
+                        errors.push(format!(
+                            "{location}: pipeline stage {i} must be a document with exactly one '$'-prefixed key"
+                        ));
+                    }
+                }
+            }
+
+            if let Some(schema) = &entry.schema {
+                errors.extend(
+                    validate_schema(schema, "")
+                        .into_iter()
+                        .map(|e| format!("{location}: schema: {e}")),
+                );
+            }
+        }
+    }
+
+    errors.extend(validate_users_and_roles(test_data_files));
+    errors
+}
+
+/// document_violations returns a message for every place a document does not conform to its
+/// entry's schema, naming the file, entry index, document index, and JSON pointer. Docs files are
+/// read in full, even for entries without a schema, so malformed ones are reported as well.
+/// Documents are indexed across the inline docs followed by the docs file. TPC-H tables conform to
+/// their schemas by construction, so collections with no other documents are not checked.
+fn document_violations(test_data_files: &[TestDataFile]) -> Vec<String> {
+    let mut violations = vec![];
+    for tdf in test_data_files {
+        for (index, entry) in tdf.dataset.iter().enumerate() {
+            let location = entry.location(&tdf.path, index);
+            let Some(c) = &entry.collection else {
+                continue;
+            };
+            let schema = entry.schema.as_ref().and_then(Bson::as_document);
+            if schema.is_none() && c.docs_file.is_none() && c.generate.is_none() {
+                continue;
+            }
+            if c.tpch.is_some()
+                && c.docs.is_empty()
+                && c.docs_file.is_none()
+                && c.generate.is_none()
+                && c.generate_from_schema.is_none()
+            {
+                continue;
+            }
+
+            let documents = match c.documents(entry.schema.as_ref()) {
+                Ok(documents) => documents,
+                Err(e) => {
+                    violations.push(format!("{location}: {e}"));
+                    continue;
+                }
+            };
+            for (i, doc) in documents.enumerate() {
+                match (doc, schema) {
+                    (Ok(doc), Some(schema)) => violations.extend(
+                        schema::conform(schema, &doc, "")
+                            .into_iter()
+                            .map(|v| format!("{location}, document {i}: {v}")),
+                    ),
+                    (Ok(_), None) => {}
+                    (Err(e), _) => violations.push(format!("{location}: {e}")),
+                }
+            }
+        }
+    }
+    violations
+}
+
+/// print_load_plan prints every operation a load of the provided test data files would perform,
+/// in the order the loader performs them. The plan depends on whether the load targets ADF. Since
+/// no connection is made, the plan assumes every namespace has changed since the last load.
+pub(crate) fn print_load_plan(test_data_files: &[TestDataFile], adf_mode: bool) {
+    println!("\tNamespaces to drop, along with their {SCHEMA_COLLECTION} documents:");
+    for entry in test_data_files.iter().flat_map(|tdf| tdf.dataset.iter()) {
+        if let Some(name) = entry_name(entry) {
+            println!("\t\t{}.{name}", entry.db);
+        }
+    }
+
+    println!("\tData to load into mongod:");
+    for entry in test_data_files.iter().flat_map(|tdf| tdf.dataset.iter()) {
+        if let Some(c) = &entry.collection {
+            let mut sources = vec![format!("{} documents", c.docs.len())];
+            if let Some(docs_file) = &c.docs_file {
+                sources.push(format!("the documents in {}", docs_file.display()));
+            }
+            if let Some(generate) = &c.generate {
+                sources.push(format!("{} generated documents", generate.count));
+            }
+            if let Some(generate) = &c.generate_from_schema {
+                sources.push(format!(
+                    "at least {} documents generated from the schema",
+                    generate.count
+                ));
+            }
+            if let Some(tpch) = &c.tpch {
+                sources.push(format!(
+                    "the TPC-H {} table at scale factor {}",
+                    tpch.table.name(),
+                    tpch.scale_factor
+                ));
+            }
+            if let Ok(Some(options)) = creation_options(c, entry.schema.as_ref()) {
+                println!(
+                    "\t\tCreate collection {}.{} with options {}",
+                    entry.db,
+                    c.name,
+                    bson::to_document(&options).unwrap_or_default()
+                );
+            }
+            println!(
+                "\t\tInsert {} into {}.{}",
+                sources.join(" and "),
+                entry.db,
+                c.name
+            );
+            for index_model in c.indexes.iter().flatten() {
+                println!(
+                    "\t\tCreate index {} on {}.{}",
+                    index_model.keys, entry.db, c.name
+                );
+            }
+        } else if let Some(v) = &entry.view {
+            match &v.definition {
+                Some(d) => println!(
+                    "\t\tCreate view {}.{} on {} with pipeline [{}]",
+                    entry.db,
+                    v.name,
+                    d.view_on,
+                    d.pipeline
+                        .iter()
+                        .map(Document::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                None => println!(
+                    "\t\tSkip view {}.{}: no view_on or pipeline specified",
+                    entry.db, v.name
+                ),
+            }
+        }
+    }
+
+    println!("\tUsers and roles to create, replacing any that exist:");
+    for role in role_creation_order(test_data_files).unwrap_or_default() {
+        println!("\t\tRole {} in {}", role.role, role.db);
+    }
+    for user in test_data_files.iter().flat_map(|tdf| &tdf.users) {
+        println!("\t\tUser {} in {}", user.user, user.db);
+    }
+
+    if adf_mode {
+        println!("\tSchema commands to run against ADF:");
+    } else {
+        println!("\tSchema documents to write to mongod:");
+    }
+    for entry in test_data_files.iter().flat_map(|tdf| tdf.dataset.iter()) {
+        let Some(name) = entry_name(entry) else {
+            continue;
+        };
+        match (&entry.schema, adf_mode) {
+            (Some(_), true) => println!("\t\tsqlSetSchema for {}.{name}", entry.db),
+            (None, true) => println!("\t\tsqlGenerateSchema for {}.{name}", entry.db),
+            (Some(_), false) => println!(
+                "\t\tInsert {}.{SCHEMA_COLLECTION} document for {name}",
+                entry.db
+            ),
+            (None, false) => println!(
+                "\t\tInsert {}.{SCHEMA_COLLECTION} document with an inferred schema for {name}",
+                entry.db
+            ),
+        }
+    }
+}
+
+/// entry_name returns the collection or view name of an entry, or None if the entry does not
+/// specify exactly one of them.
+fn entry_name(entry: &TestDataEntry) -> Option<&str> {
+    match (&entry.collection, &entry.view) {
+        (Some(c), None) => Some(c.name.as_str()),
+        (None, Some(v)) => Some(v.name.as_str()),
+        _ => None,
+    }
+}
+
+/// validate_namespace checks the database and collection names against the server's naming
+/// restrictions.
+fn validate_namespace(db: &str, name: &str) -> Vec<String> {
+    let mut errors = vec![];
+
+    if db.is_empty() {
+        errors.push("database name must not be empty".to_string());
+    } else if let Some(c) = db.chars().find(|c| "/\\. \"$\0".contains(*c)) {
+        errors.push(format!(
+            "database name {db:?} contains invalid character {c:?}"
+        ));
+    } else if db.len() >= 64 {
+        errors.push(format!(
+            "database name {db:?} must be shorter than 64 bytes"
+        ));
+    } else if db == METADATA_DB {
+        errors.push(format!(
+            "database name {db:?} is reserved for loader metadata"
+        ));
+    }
+
+    if name.is_empty() {
+        errors.push("collection or view name must not be empty".to_string());
+    } else if name.contains(['$', '\0']) {
+        errors.push(format!(
+            "name {name:?} must not contain '$' or null characters"
+        ));
+    } else if name.starts_with("system.") {
+        errors.push(format!("name {name:?} must not start with 'system.'"));
+    } else if name == SCHEMA_COLLECTION {
+        errors.push(format!("name {name:?} is reserved for schema storage"));
+    } else if name.starts_with(BACKUP_PREFIX) {
+        errors.push(format!(
+            "name {name:?} must not start with {BACKUP_PREFIX:?}"
+        ));
+    }
+
+    if db.len() + 1 + name.len() > 255 {
+        errors.push(format!("namespace {db}.{name} must not exceed 255 bytes"));
+    }
+
+    errors
+}
+
+/// validate_collection_options checks the combinations of createCollection options that the
+/// server would reject, so they are reported before anything is loaded.
+fn validate_collection_options(options: &CreateCollectionOptions) -> Vec<String> {
+    let mut errors = vec![];
+    if options.view_on.is_some() || options.pipeline.is_some() {
+        errors.push("viewOn and pipeline are not allowed; use a view entry instead".to_string());
+    }
+    if options.capped == Some(true) && options.size.is_none() {
+        errors.push("a capped collection requires a size".to_string());
+    }
+    if options.capped != Some(true) && (options.size.is_some() || options.max.is_some()) {
+        errors.push("size and max require capped: true".to_string());
+    }
+    if options.timeseries.is_some() && options.capped == Some(true) {
+        errors.push("a time-series collection cannot be capped".to_string());
+    }
+    if options.timeseries.is_some() && options.clustered_index.is_some() {
+        errors.push("a time-series collection cannot be clustered".to_string());
+    }
+    if let Some(clustered_index) = &options.clustered_index {
+        if clustered_index.key != doc! {"_id": 1} || !clustered_index.unique {
+            errors.push("clusteredIndex must have key {_id: 1} and unique: true".to_string());
+        }
+    }
+    if options.expire_after_seconds.is_some()
+        && options.timeseries.is_none()
+        && options.clustered_index.is_none()
+    {
+        errors
+            .push("expireAfterSeconds requires a time-series or clustered collection".to_string());
+    }
+    errors
+}
+
+/// validate_index_key checks that an index key document is non-empty and that every field maps to
+/// a direction (a non-zero number) or a known index type.
+fn validate_index_key(keys: &Document) -> Vec<String> {
+    if keys.is_empty() {
+        return vec!["index key must not be empty".to_string()];
+    }
+
+    keys.iter()
+        .filter_map(|(field, value)| {
+            let valid = match value {
+                Bson::Int32(i) => *i != 0,
+                Bson::Int64(i) => *i != 0,
+                Bson::Double(d) => *d != 0.0,
+                Bson::String(s) => INDEX_KEY_TYPES.contains(&s.as_str()),
+                _ => false,
+            };
+            (field.is_empty() || !valid).then(|| format!("invalid index key {field:?}: {value}"))
+        })
+        .collect()
+}
+
+/// validate_schema checks that a schema is well-formed in the MongoDB JSON-schema dialect. Only the
+/// keywords the SQL engines rely on are checked in depth; others are accepted as-is. The path is a
+/// JSON pointer to the schema being checked, used for error messages.
+pub(crate) fn validate_schema(schema: &Bson, path: &str) -> Vec<String> {
+    let location = if path.is_empty() { "/" } else { path };
+    let Bson::Document(schema) = schema else {
+        return vec![format!("{location}: schema must be a document")];
+    };
+
+    let mut errors = vec![];
+    for (keyword, value) in schema {
+        let keyword_path = format!("{path}/{keyword}");
+        match (keyword.as_str(), value) {
+            ("bsonType", Bson::String(t)) if !BSON_TYPES.contains(&t.as_str()) => {
+                errors.push(format!("{keyword_path}: unknown bsonType {t:?}"));
+            }
+            ("bsonType", Bson::String(_)) => {}
+            ("bsonType", Bson::Array(types)) => {
+                for t in types {
+                    match t {
+                        Bson::String(t) if BSON_TYPES.contains(&t.as_str()) => {}
+                        _ => errors.push(format!("{keyword_path}: unknown bsonType {t}")),
+                    }
+                }
+            }
+            ("anyOf", Bson::Array(alternatives)) => {
+                for (i, alternative) in alternatives.iter().enumerate() {
+                    errors.extend(validate_schema(alternative, &format!("{keyword_path}/{i}")));
+                }
+            }
+            ("properties", Bson::Document(properties)) => {
+                for (name, property) in properties {
+                    errors.extend(validate_schema(property, &format!("{keyword_path}/{name}")));
+                }
+            }
+            ("required", Bson::Array(fields))
+                if fields.iter().all(|f| matches!(f, Bson::String(_))) => {}
+            ("additionalProperties", Bson::Boolean(_)) => {}
+            ("additionalProperties", s @ Bson::Document(_)) | ("items", s @ Bson::Document(_)) => {
+                errors.extend(validate_schema(s, &keyword_path));
+            }
+            ("items", Bson::Array(items)) => {
+                for (i, item) in items.iter().enumerate() {
+                    errors.extend(validate_schema(item, &format!("{keyword_path}/{i}")));
+                }
+            }
+            (
+                "bsonType" | "anyOf" | "properties" | "required" | "additionalProperties" | "items",
+                _,
+            ) => {
+                errors.push(format!("{keyword_path}: invalid value {value}"));
+            }
+            _ => {}
+        }
+    }
+    errors
+}
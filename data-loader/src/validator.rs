@@ -0,0 +1,103 @@
+use crate::{CollectionData, DataLoaderError, Result, TestDataFile};
+use mongodb::{
+    bson::{self, doc, Bson},
+    options::{CreateCollectionOptions, ValidationAction, ValidationLevel},
+};
+use serde::{Deserialize, Serialize};
+
+/// SchemaValidator describes how an entry's schema is installed as its collection's $jsonSchema
+/// validator, so that the mongod itself rejects documents that contradict the schema. Settings
+/// that are not provided are left to the server's defaults: strict and error.
+///
+/// Example:
+///   schema_validator: { validation_level: moderate, validation_action: warn }
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SchemaValidator {
+    /// validation_level is one of "off", "strict", or "moderate". Optional.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validation_level: Option<ValidationLevel>,
+
+    /// validation_action is one of "error" or "warn". Optional.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validation_action: Option<ValidationAction>,
+}
+
+/// apply_default gives the validator to every collection entry that has a schema, unless the entry
+/// specifies its own schema_validator, or a validator, validationLevel, or validationAction in its
+/// options.
+pub(crate) fn apply_default(test_data_files: &mut [TestDataFile], validator: &SchemaValidator) {
+    for entry in test_data_files.iter_mut().flat_map(|tdf| &mut tdf.dataset) {
+        if entry.schema.is_none() {
+            continue;
+        }
+        if let Some(c) = entry.collection.as_mut().filter(|c| {
+            c.schema_validator.is_none() && !c.options.as_ref().is_some_and(has_validator)
+        }) {
+            c.schema_validator = Some(validator.clone());
+        }
+    }
+}
+
+/// creation_options returns the options the collection is created with: its options, with the
+/// schema installed as a $jsonSchema validator if the collection has a schema_validator. Returns
+/// None if the collection can be created implicitly, and a ConflictingValidator error if the
+/// options already specify a validator, validationLevel, or validationAction.
+pub(crate) fn creation_options(
+    collection: &CollectionData,
+    schema: Option<&Bson>,
+) -> Result<Option<CreateCollectionOptions>> {
+    let (Some(validator), Some(schema)) = (&collection.schema_validator, schema) else {
+        return Ok(collection.options.clone());
+    };
+    if collection.options.as_ref().is_some_and(has_validator) {
+        return Err(DataLoaderError::ConflictingValidator);
+    }
+    let mut options = collection.options.clone().unwrap_or_default();
+    options.validator = Some(doc! {"$jsonSchema": schema});
+    options.validation_level = validator.validation_level.clone();
+    options.validation_action = validator.validation_action.clone();
+    Ok(Some(options))
+}
+
+/// has_validator returns whether the options specify any of the settings a schema validator sets.
+fn has_validator(options: &CreateCollectionOptions) -> bool {
+    options.validator.is_some()
+        || options.validation_level.is_some()
+        || options.validation_action.is_some()
+}
+
+/// validate_schema_validator checks that a collection's schema_validator has a schema to install,
+/// and does not conflict with a validator in its options.
+pub(crate) fn validate_schema_validator(
+    collection: &CollectionData,
+    schema: Option<&Bson>,
+) -> Vec<String> {
+    let mut errors = vec![];
+    if collection.schema_validator.is_none() {
+        return errors;
+    }
+    match schema {
+        None => errors.push("an entry without a schema cannot have a schema validator".to_string()),
+        Some(Bson::Document(_)) => {}
+        Some(_) => {
+            errors.push("the schema must be a document to be used as a validator".to_string())
+        }
+    }
+    if collection.options.as_ref().is_some_and(has_validator) {
+        errors.push(DataLoaderError::ConflictingValidator.to_string());
+    }
+    errors
+}
+
+/// parse_validation_level parses a validation level from the command line.
+pub(crate) fn parse_validation_level(s: &str) -> std::result::Result<ValidationLevel, String> {
+    bson::from_bson(Bson::String(s.to_string()))
+        .map_err(|_| format!("expected one of \"off\", \"strict\", or \"moderate\", got {s:?}"))
+}
+
+/// parse_validation_action parses a validation action from the command line.
+pub(crate) fn parse_validation_action(s: &str) -> std::result::Result<ValidationAction, String> {
+    bson::from_bson(Bson::String(s.to_string()))
+        .map_err(|_| format!("expected one of \"error\" or \"warn\", got {s:?}"))
+}
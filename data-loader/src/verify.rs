@@ -0,0 +1,369 @@
+use crate::{
+    dump::{read_namespace, read_schema},
+    schema, validator, DataLoaderError, Result, TestDataEntry, TestDataFile,
+};
+use mongodb::{
+    bson::{self, Bson, Document},
+    options::CreateCollectionOptions,
+    Client, IndexModel,
+};
+use std::collections::HashMap;
+
+/// The maximum number of document differences reported per namespace.
+const MAX_DOCUMENT_DIFFERENCES: usize = 10;
+
+/// verify compares every entry in the test data files with the namespace currently on the mongod,
+/// and prints a report of the differences for each namespace. Schemas are compared with ADF when
+/// an ADF client is provided, and with the mongod's __sql_schemas collection otherwise. Returns a
+/// Drift error if any namespace differs.
+pub(crate) async fn verify(
+    mdb_client: &Client,
+    adf_client: Option<&Client>,
+    test_data_files: &[TestDataFile],
+) -> Result<()> {
+    let mut drifted = 0;
+    for entry in test_data_files.iter().flat_map(|tdf| tdf.dataset.iter()) {
+        let entry = &with_inline_docs(entry)?;
+        let db = mdb_client.database(&entry.db);
+        let actual = read_namespace(&db, entry.name()).await?;
+
+        // Views without a definition only exist in ADF, so there is nothing to compare on the
+        // mongod.
+        let adf_only_view = entry.view.as_ref().is_some_and(|v| v.definition.is_none());
+        let mut differences = match (&actual, adf_only_view) {
+            (_, true) => vec![],
+            (None, false) => vec!["namespace does not exist".to_string()],
+            (Some(actual), false) => diff_entries(entry, actual),
+        };
+
+        if actual.is_some() || adf_only_view {
+            let actual_schema = read_schema(&db, adf_client, entry.name()).await?;
+            differences.extend(diff_schema(
+                entry,
+                adf_client.is_some(),
+                actual_schema.as_ref(),
+            ));
+        }
+
+        if differences.is_empty() {
+            println!("\t{}: OK", entry.namespace());
+        } else {
+            drifted += 1;
+            println!(
+                "\t{}: {} difference(s)",
+                entry.namespace(),
+                differences.len()
+            );
+            for difference in differences {
+                println!("\t\t- {difference}");
+            }
+        }
+    }
+
+    if drifted == 0 {
+        Ok(())
+    } else {
+        Err(DataLoaderError::Drift(drifted))
+    }
+}
+
+/// with_inline_docs returns a copy of the entry with the documents of its docs file, its
+/// generated documents, and its TPC-H table, if it has any, appended to its inline docs, so that
+/// all of its documents can be compared at once.
+fn with_inline_docs(entry: &TestDataEntry) -> Result<TestDataEntry> {
+    let mut entry = entry.clone();
+    if let Some(c) = entry.collection.as_mut() {
+        let docs = c
+            .documents(entry.schema.as_ref())?
+            .collect::<Result<Vec<_>>>()?;
+        c.docs = docs;
+        c.options = validator::creation_options(c, entry.schema.as_ref())?;
+        c.schema_validator = None;
+        c.docs_file = None;
+        c.generate = None;
+        c.generate_from_schema = None;
+        c.tpch = None;
+    }
+    Ok(entry)
+}
+
+/// diff_entries compares an entry from a data file with the entry read from the mongod, covering
+/// documents, indexes, and view definitions. Schemas are compared separately by diff_schema.
+pub(crate) fn diff_entries(expected: &TestDataEntry, actual: &TestDataEntry) -> Vec<String> {
+    match (
+        &expected.collection,
+        &expected.view,
+        &actual.collection,
+        &actual.view,
+    ) {
+        (Some(e), _, Some(a), _) => {
+            let mut differences = diff_options(e.options.as_ref(), a.options.as_ref());
+            differences.extend(diff_documents(&e.docs, &a.docs));
+            differences.extend(diff_indexes(
+                e.indexes.as_deref().unwrap_or_default(),
+                a.indexes.as_deref().unwrap_or_default(),
+            ));
+            differences
+        }
+        (_, Some(e), _, Some(a)) => match (&e.definition, &a.definition) {
+            (Some(e), Some(a)) => {
+                let mut differences = vec![];
+                if e.view_on != a.view_on {
+                    differences.push(format!(
+                        "view is defined on {:?}, expected {:?}",
+                        a.view_on, e.view_on
+                    ));
+                }
+                if e.pipeline != a.pipeline {
+                    differences.push(format!(
+                        "view pipeline is {}, expected {}",
+                        format_pipeline(&a.pipeline),
+                        format_pipeline(&e.pipeline)
+                    ));
+                }
+                differences
+            }
+            _ => vec![],
+        },
+        (Some(_), _, _, Some(_)) => vec!["expected a collection, found a view".to_string()],
+        _ => vec!["expected a view, found a collection".to_string()],
+    }
+}
+
+fn format_pipeline(pipeline: &[Document]) -> String {
+    Bson::Array(pipeline.iter().cloned().map(Bson::Document).collect()).to_string()
+}
+
+/// with_id_first moves a document's _id field to the front, as the server does on insert, so that
+/// documents can be compared field by field.
+fn with_id_first(doc: &Bson) -> Bson {
+    match doc {
+        Bson::Document(d) => match d.get("_id") {
+            Some(id) => {
+                let mut reordered = Document::new();
+                reordered.insert("_id", id.clone());
+                reordered.extend(d.clone());
+                Bson::Document(reordered)
+            }
+            None => doc.clone(),
+        },
+        _ => doc.clone(),
+    }
+}
+
+/// display formats a document as relaxed Extended JSON, which, unlike Bson's Display, tells
+/// doubles apart from integers.
+fn display(doc: &Bson) -> String {
+    doc.clone().into_relaxed_extjson().to_string()
+}
+
+fn id_key(doc: &Bson) -> Option<String> {
+    doc.as_document()
+        .and_then(|d| d.get("_id"))
+        .map(|id| id.clone().into_canonical_extjson().to_string())
+}
+
+fn without_id(doc: &Bson) -> Bson {
+    match doc {
+        Bson::Document(d) => {
+            let mut d = d.clone();
+            d.remove("_id");
+            Bson::Document(d)
+        }
+        _ => doc.clone(),
+    }
+}
+
+/// diff_documents compares the expected and actual documents of a collection as sets. Expected
+/// documents with an _id are matched by _id. Expected documents without one were assigned an _id
+/// by the server, so they are matched against the remaining actual documents with their _id
+/// removed.
+fn diff_documents(expected: &[Bson], actual: &[Bson]) -> Vec<String> {
+    let mut differences = vec![];
+    let mut unmatched = actual
+        .iter()
+        .map(|a| (id_key(a), a.clone()))
+        .collect::<Vec<_>>();
+    let mut by_id = unmatched
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (id, _))| id.clone().map(|id| (id, i)))
+        .collect::<HashMap<_, _>>();
+    let mut matched = vec![false; unmatched.len()];
+
+    let (with_ids, without_ids): (Vec<_>, Vec<_>) =
+        expected.iter().partition(|e| id_key(e).is_some());
+
+    for e in with_ids {
+        let e = with_id_first(e);
+        let id = id_key(&e).unwrap();
+        match by_id.remove(&id) {
+            Some(i) => {
+                matched[i] = true;
+                if unmatched[i].1 != e {
+                    differences.push(format!(
+                        "document {id} is {}, expected {}",
+                        display(&unmatched[i].1),
+                        display(&e)
+                    ));
+                }
+            }
+            None => differences.push(format!("missing document {}", display(&e))),
+        }
+    }
+
+    for e in without_ids {
+        let found = unmatched
+            .iter()
+            .enumerate()
+            .position(|(i, (_, a))| !matched[i] && without_id(a) == *e);
+        match found {
+            Some(i) => matched[i] = true,
+            None => differences.push(format!("missing document {}", display(e))),
+        }
+    }
+
+    for (i, (_, a)) in unmatched.drain(..).enumerate() {
+        if !matched[i] {
+            differences.push(format!("unexpected document {}", display(&a)));
+        }
+    }
+
+    if differences.len() > MAX_DOCUMENT_DIFFERENCES {
+        let remaining = differences.len() - MAX_DOCUMENT_DIFFERENCES;
+        differences.truncate(MAX_DOCUMENT_DIFFERENCES);
+        differences.push(format!("... and {remaining} more document difference(s)"));
+    }
+    differences
+}
+
+/// diff_options compares the options a collection was created with. Like index options, only the
+/// options specified in the data file are compared, down to the fields of nested options, since
+/// the server fills in others, such as the bucketing of a time-series collection.
+fn diff_options(
+    expected: Option<&CreateCollectionOptions>,
+    actual: Option<&CreateCollectionOptions>,
+) -> Vec<String> {
+    let to_document = |options: Option<&CreateCollectionOptions>| {
+        options
+            .and_then(|o| bson::to_document(o).ok())
+            .unwrap_or_default()
+    };
+    let mut differences = vec![];
+    diff_option_fields(
+        "",
+        &to_document(expected),
+        &to_document(actual),
+        &mut differences,
+    );
+    differences
+}
+
+fn diff_option_fields(
+    prefix: &str,
+    expected: &Document,
+    actual: &Document,
+    differences: &mut Vec<String>,
+) {
+    for (field, value) in expected {
+        let option = format!("{prefix}{field}");
+        match (value, actual.get(field)) {
+            (Bson::Document(e), Some(Bson::Document(a))) => {
+                diff_option_fields(&format!("{option}."), e, a, differences)
+            }
+            (e, Some(a)) if same_value(e, a) => {}
+            (e, Some(a)) => {
+                differences.push(format!("collection has option {option}: {a}, expected {e}"))
+            }
+            (e, None) => differences.push(format!("collection is missing option {option}: {e}")),
+        }
+    }
+}
+
+/// same_value compares option values, treating numbers of different types as equal if they have
+/// the same value, since the server may report a number with a different type than it was
+/// created with.
+fn same_value(expected: &Bson, actual: &Bson) -> bool {
+    let number = |value: &Bson| match value {
+        Bson::Int32(n) => Some(f64::from(*n)),
+        Bson::Int64(n) => Some(*n as f64),
+        Bson::Double(n) => Some(*n),
+        _ => None,
+    };
+    match (number(expected), number(actual)) {
+        (Some(e), Some(a)) => e == a,
+        _ => expected == actual,
+    }
+}
+
+fn options_document(index: &IndexModel) -> Document {
+    index
+        .options
+        .as_ref()
+        .and_then(|o| bson::to_document(o).ok())
+        .unwrap_or_default()
+}
+
+/// diff_indexes compares the expected and actual indexes of a collection, matching them by key.
+/// Only the options specified in the data file are compared, since the server fills in others,
+/// such as the index name and version.
+fn diff_indexes(expected: &[IndexModel], actual: &[IndexModel]) -> Vec<String> {
+    let mut differences = vec![];
+    let mut matched = vec![false; actual.len()];
+
+    for e in expected {
+        match actual
+            .iter()
+            .enumerate()
+            .position(|(i, a)| !matched[i] && a.keys == e.keys)
+        {
+            Some(i) => {
+                matched[i] = true;
+                let actual_options = options_document(&actual[i]);
+                for (option, value) in options_document(e) {
+                    match actual_options.get(&option) {
+                        Some(a) if *a == value => {}
+                        Some(a) => differences.push(format!(
+                            "index {} has {option}: {a}, expected {value}",
+                            e.keys
+                        )),
+                        None => differences.push(format!(
+                            "index {} is missing option {option}: {value}",
+                            e.keys
+                        )),
+                    }
+                }
+            }
+            None => differences.push(format!("missing index {}", e.keys)),
+        }
+    }
+
+    for (i, a) in actual.iter().enumerate() {
+        if !matched[i] {
+            differences.push(format!("unexpected index {}", a.keys));
+        }
+    }
+    differences
+}
+
+/// diff_schema compares an entry's schema with the schema currently set for its namespace. Entries
+/// without a schema get one inferred by the loader in mongod mode, so collection entries are
+/// compared with the schema inferred from their documents. In ADF mode, or for views, the
+/// generated schema cannot be predicted, so only its presence is checked.
+pub(crate) fn diff_schema(
+    expected: &TestDataEntry,
+    adf_mode: bool,
+    actual: Option<&Bson>,
+) -> Vec<String> {
+    let expected_schema = match (&expected.schema, &expected.collection, adf_mode) {
+        (Some(schema), _, _) => Some(schema.clone()),
+        (None, Some(c), false) => Some(schema::infer(&c.docs)),
+        (None, _, _) => None,
+    };
+
+    match (expected_schema, actual) {
+        (_, None) => vec!["no schema is set".to_string()],
+        (Some(e), Some(a)) if e != *a => vec![format!("schema is {a}, expected {e}")],
+        _ => vec![],
+    }
+}